@@ -0,0 +1,13 @@
+#[macro_use]
+extern crate colour;
+
+use collage::lsp::run;
+use std::io::{stdin, stdout, BufReader};
+
+/// Serves the Collage language to editors over stdin and stdout.
+fn main() {
+    if let Err(error) = run(BufReader::new(stdin()), stdout()) {
+        e_red_ln!("Language server stopped: {:}", error);
+        std::process::exit(1);
+    }
+}
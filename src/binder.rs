@@ -1,65 +1,1000 @@
-use std::string::ToString;
-
-use strum_macros::Display;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::rc::Rc;
+use std::str::FromStr;
 
 use crate::diagnostic::DiagnosticHolder;
-use crate::lexer::Token;
-use crate::parser::Expression;
-use std::any::TypeId;
+use crate::exhaustiveness::{useful, Constructor, ConstructorKind, SimplePattern};
+use crate::lexer::{Token, Type};
+use crate::parser::{
+    Declaration, Expression, Pattern, Program, SyntaxNode, TypeDeclaration, TypeExpression,
+};
+use crate::runtime::Builtin;
+use strum::IntoEnumIterator;
+
+#[derive(Debug, Clone)]
+pub struct Binder {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Number of outermost scopes holding the top-level definitions of the current module.
+    module_scopes: usize,
+    /// Prefix of the runtime names of the current module's items, empty for the root module.
+    module_prefix: String,
+    imports: Vec<String>,
+    /// Module whose exports are visible unqualified in every module, see [`Binder::set_prelude`].
+    prelude: Option<String>,
+    /// Schemes of the functions and constants provided by the embedder.
+    host: HashMap<String, TypeScheme>,
+    exports: HashMap<String, HashMap<String, TypeScheme>>,
+    exported_types: HashSet<String>,
+    types: HashMap<String, TypeDefinition>,
+    constructors: HashMap<String, ConstructorDefinition>,
+    substitution: HashMap<usize, BoundType>,
+    next_variable: usize,
+    /// Identifiers of the root module, see [`Binder::references`].
+    references: Vec<Reference>,
+    /// Top-level definitions of the root programs bound so far, visible to the next one, e.g.
+    /// the earlier inputs of a REPL session.
+    root_scope: HashMap<String, Binding>,
+    /// Modules imported by the root programs bound so far.
+    root_imports: Vec<String>,
+}
+
+/// A name in scope with its type and, in the root module, where it is bound.
+#[derive(Debug, Clone)]
+struct Binding {
+    scheme: TypeScheme,
+    definition: Option<Range<usize>>,
+}
+
+/// A user-defined sum type with its type parameters and constructors in declaration order.
+#[derive(Debug, Clone)]
+struct TypeDefinition {
+    parameters: Vec<usize>,
+    constructors: Vec<(String, usize)>,
+    definition: Option<Range<usize>>,
+}
+
+#[derive(Debug, Clone)]
+struct ConstructorDefinition {
+    type_name: String,
+    parameters: Vec<usize>,
+    fields: Vec<BoundType>,
+    definition: Option<Range<usize>>,
+}
+
+/// An identifier of the root module where a name is bound or used, e.g. a parameter, a
+/// variable, a constructor or a type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// Byte range of the identifier in the source.
+    pub span: Range<usize>,
+    pub name: String,
+    /// The type of the named value, or `None` for a type.
+    pub bound_type: Option<BoundType>,
+    /// Byte range of the identifier binding the name, or `None` if it is bound outside the root
+    /// module, e.g. in the prelude or by the host.
+    pub definition: Option<Range<usize>>,
+}
+
+impl ConstructorDefinition {
+    fn result_type(&self) -> BoundType {
+        BoundType::Named(
+            self.type_name.clone(),
+            self.parameters
+                .iter()
+                .map(|parameter| BoundType::Variable(*parameter))
+                .collect(),
+        )
+    }
+
+    /// The constructor used as a curried function from its fields to the constructed type.
+    fn scheme(&self) -> TypeScheme {
+        TypeScheme::new(
+            self.parameters.clone(),
+            self.fields
+                .iter()
+                .rev()
+                .fold(self.result_type(), |result, field| {
+                    BoundType::function(field.clone(), result)
+                }),
+        )
+    }
+}
 
-pub struct Binder {}
+impl Default for Binder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Mandarin spellings of the built-in types and constructors.
+const ALIASES: &[(&str, &str)] = &[
+    ("選項", "Option"),
+    ("無", "None"),
+    ("有", "Some"),
+    ("結果", "Result"),
+    ("成功", "Ok"),
+    ("失敗", "Err"),
+];
+
+fn canonical(name: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, canonical)| canonical)
+}
+
+fn is_builtin_name(name: &str) -> bool {
+    ALIASES.iter().any(|(_, canonical)| *canonical == name)
+}
 
 impl Binder {
     pub fn new() -> Self {
-        Self {}
+        let mut binder = Self {
+            scopes: vec![],
+            module_scopes: 0,
+            module_prefix: String::new(),
+            imports: vec![],
+            prelude: None,
+            host: HashMap::new(),
+            exports: HashMap::new(),
+            exported_types: HashSet::new(),
+            types: HashMap::new(),
+            constructors: HashMap::new(),
+            substitution: HashMap::new(),
+            next_variable: 0,
+            references: vec![],
+            root_scope: HashMap::new(),
+            root_imports: vec![],
+        };
+
+        let a = || BoundType::Variable(0);
+        let e = || BoundType::Variable(1);
+
+        binder.define_type(
+            "Option",
+            vec![0],
+            vec![("None", vec![]), ("Some", vec![a()])],
+        );
+        binder.define_type(
+            "Result",
+            vec![0, 1],
+            vec![("Ok", vec![a()]), ("Err", vec![e()])],
+        );
+
+        binder
+    }
+
+    fn define_type(
+        &mut self,
+        name: &str,
+        parameters: Vec<usize>,
+        constructors: Vec<(&str, Vec<BoundType>)>,
+    ) {
+        self.types.insert(
+            name.to_string(),
+            TypeDefinition {
+                parameters: parameters.clone(),
+                constructors: constructors
+                    .iter()
+                    .map(|(constructor, fields)| (constructor.to_string(), fields.len()))
+                    .collect(),
+                definition: None,
+            },
+        );
+
+        for (constructor, fields) in constructors {
+            self.constructors.insert(
+                constructor.to_string(),
+                ConstructorDefinition {
+                    type_name: name.to_string(),
+                    parameters: parameters.clone(),
+                    fields,
+                    definition: None,
+                },
+            );
+        }
     }
 
+    /// Binds an expression tree and resolves every inferred type inside it.
     pub fn bind_expression(
-        &self,
+        &mut self,
         expression: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        if !holder.success() {
-            return None;
+        let mut bound_expression = self.bind(expression, holder);
+
+        if let Some(bound_expression) = bound_expression.as_mut() {
+            self.resolve_expression(bound_expression);
+        }
+
+        bound_expression
+    }
+
+    /// Binds every top-level item of a program, which may refer to each other regardless of order.
+    pub fn bind_program(
+        &mut self,
+        program: Program,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundProgram {
+        self.bind_module(None, program, holder)
+    }
+
+    /// Makes the exports of `module` usable without qualification once it has been bound.
+    /// Definitions of the importing module still shadow them.
+    pub fn set_prelude(&mut self, module: &str) {
+        self.prelude = Some(module.to_string());
+    }
+
+    /// Whether `module` is bound already, so that it is not to be bound again.
+    pub fn is_bound(&self, module: &str) -> bool {
+        self.exports.contains_key(module)
+    }
+
+    /// Names usable without qualification outside the bound modules: the exports of the prelude,
+    /// the host functions and constants, the built-in functions and the constructors.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .prelude
+            .iter()
+            .filter_map(|prelude| self.exports.get(prelude))
+            .flat_map(|exports| exports.keys().cloned())
+            .chain(self.host.keys().cloned())
+            .chain(Builtin::iter().map(|builtin| builtin.to_string()))
+            .chain(
+                self.constructors
+                    .keys()
+                    .filter(|constructor| !constructor.contains("::"))
+                    .cloned(),
+            )
+            .chain(ALIASES.iter().map(|(alias, _)| alias.to_string()))
+            .collect::<Vec<String>>();
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Where names are bound and used in the root module, in the order they were met, with
+    /// their types resolved.
+    pub fn references(&self) -> Vec<Reference> {
+        self.references
+            .iter()
+            .map(|reference| Reference {
+                bound_type: reference
+                    .bound_type
+                    .as_ref()
+                    .map(|bound_type| self.resolve(bound_type)),
+                ..reference.clone()
+            })
+            .collect()
+    }
+
+    /// Where `token` binds a name, if it is in the root module, whose source is located.
+    fn located(&self, token: &Token) -> Option<Range<usize>> {
+        self.module_prefix.is_empty().then(|| token.span.clone())
+    }
+
+    /// Records a use or binding of a name by `token` in the root module, see [`Reference`].
+    fn refer(
+        &mut self,
+        token: &Token,
+        bound_type: Option<BoundType>,
+        definition: Option<Range<usize>>,
+    ) {
+        if self.module_prefix.is_empty() {
+            self.references.push(Reference {
+                span: token.span.clone(),
+                name: token.literal.clone(),
+                bound_type,
+                definition,
+            });
+        }
+    }
+
+    /// Records the binding of a name by `token` in the root module, see [`Binder::refer`].
+    fn refer_binding(&mut self, token: &Token, bound_type: &BoundType) {
+        let definition = self.located(token);
+
+        self.refer(token, Some(bound_type.clone()), definition);
+    }
+
+    /// Declares a host function or constant, see [`crate::host::HostEnvironment`]. Lowercase
+    /// names in its type which are not types themselves become type variables.
+    pub fn declare_host(
+        &mut self,
+        name: &str,
+        type_expression: &TypeExpression,
+        arity: Option<usize>,
+        holder: &mut DiagnosticHolder,
+    ) {
+        let mut variable_names = vec![];
+        collect_type_variables(type_expression, &mut variable_names);
+        variable_names.retain(|name| self.type_key(name).is_none());
+
+        let parameters = variable_names
+            .into_iter()
+            .map(|name| (name, self.fresh_variable()))
+            .collect::<HashMap<String, BoundType>>();
+
+        let Some(bound_type) = self.resolve_type_expression(type_expression, &parameters, holder)
+        else {
+            return;
+        };
+
+        if let Some(arity) = arity {
+            let mut parameter_count = 0;
+            let mut result = &bound_type;
+
+            while let BoundType::Function(_, next) = result {
+                parameter_count += 1;
+                result = next;
+            }
+
+            if parameter_count < arity {
+                holder.error(&format!(
+                    "Host function \"{:}\" takes {:} arguments but is declared as \"{:}\"",
+                    name, arity, bound_type
+                ));
+                return;
+            }
+        }
+
+        let variables = parameters
+            .values()
+            .filter_map(|variable| match variable {
+                BoundType::Variable(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+
+        self.host
+            .insert(name.to_string(), TypeScheme::new(variables, bound_type));
+    }
+
+    /// Binds imported modules, ordered so that each comes after the modules it imports, and then
+    /// the root program. Only the diagnostics of the root program are located. The root program
+    /// also sees the definitions and imports of the root programs this binder bound before, so
+    /// that the inputs of a REPL session can be bound one at a time.
+    pub fn bind_modules(
+        &mut self,
+        modules: Vec<(String, Program)>,
+        root: Program,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundProgram {
+        let mut definitions = vec![];
+
+        for (name, program) in modules {
+            if program.entry.is_some() {
+                holder.warning(&format!(
+                    "Entry expression of imported module \"{:}\" is ignored",
+                    name
+                ));
+            }
+
+            let bound_module =
+                holder.elsewhere(|holder| self.bind_module(Some(&name), program, holder));
+            definitions.extend(bound_module.definitions);
         }
 
-        if let Some(expression) = expression {
-            match expression {
-                Expression::Literal(token) => self.bind_literal(token.as_ref(), holder),
-                Expression::Identifier(token) => self.bind_identifier(token.as_ref(), holder),
-                Expression::Bool(token) => self.bind_bool(token.as_ref(), holder),
-                Expression::Number(token) => self.bind_number(token.as_ref(), holder),
-                Expression::Positive(expression) => self.bind_positive(*expression, holder),
-                Expression::Negative(expression) => self.bind_negative(*expression, holder),
-                Expression::NOT(expression) => self.bind_not(*expression, holder),
-                Expression::OR(left, right) => self.bind_or(*left, *right, holder),
-                Expression::AND(left, right) => self.bind_and(*left, *right, holder),
-                Expression::BangEqual(left, right) => self.bind_bang_equal(*left, *right, holder),
-                Expression::Equal(left, right) => self.bind_equal(*left, *right, holder),
-                Expression::Greater(left, right) => self.bind_greater(*left, *right, holder),
-                Expression::GreaterEqual(left, right) => {
-                    self.bind_greater_equal(*left, *right, holder)
+        let mut bound_program = self.bind_module(None, root, holder);
+        definitions.extend(bound_program.definitions);
+        bound_program.definitions = definitions;
+
+        bound_program
+    }
+
+    /// Binds a program as the module `name`, or as the root program when `name` is `None`.
+    fn bind_module(
+        &mut self,
+        name: Option<&str>,
+        program: Program,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundProgram {
+        let mut definitions = vec![];
+        let mut type_declarations = vec![];
+        let mut exported = HashSet::new();
+
+        self.module_prefix = name.map_or_else(String::new, |name| format!("{}::", name));
+        self.imports = match name {
+            Some(_) => vec![],
+            None => self.root_imports.clone(),
+        };
+
+        let declarations = program
+            .declarations
+            .into_iter()
+            .map(|declaration| match declaration {
+                Declaration::Export(declaration) => {
+                    match declaration.as_ref() {
+                        Declaration::Definition(name, _) => {
+                            exported.insert(name.literal.clone());
+                        }
+                        Declaration::Type(declaration) => {
+                            exported.insert(declaration.name.literal.clone());
+                        }
+                        _ => {}
+                    }
+
+                    *declaration
                 }
-                Expression::Less(left, right) => self.bind_less(*left, *right, holder),
-                Expression::LessEqual(left, right) => self.bind_less_equal(*left, *right, holder),
-                Expression::Addition(left, right) => self.bind_addition(*left, *right, holder),
-                Expression::Subtraction(left, right) => {
-                    self.bind_subtraction(*left, *right, holder)
+                declaration => declaration,
+            })
+            .collect::<Vec<Declaration>>();
+
+        for declaration in declarations {
+            match declaration {
+                Declaration::Import(module) => {
+                    if self.exports.contains_key(&module.literal) {
+                        self.imports.push(module.literal);
+                    } else {
+                        holder.at(Some(module.span.clone()), |holder| {
+                            holder.error(&format!("Unknown module \"{:}\"", module.literal))
+                        });
+                    }
                 }
-                Expression::Multiplication(left, right) => {
-                    self.bind_multiplication(*left, *right, holder)
+                Declaration::Export(_) => {}
+                Declaration::Definition(name, value) => {
+                    if definitions
+                        .iter()
+                        .any(|(other, _): &(Token, Option<Expression>)| {
+                            other.literal == name.literal
+                        })
+                    {
+                        holder.at(Some(name.span.clone()), |holder| {
+                            holder.error(&format!(
+                                "Duplicate top-level definition \"{:}\"",
+                                name.literal
+                            ))
+                        });
+                    } else {
+                        definitions.push((name, value));
+                    }
                 }
-                Expression::Division(left, right) => self.bind_division(*left, *right, holder),
-                Expression::Remainder(left, right) => self.bind_remainder(*left, *right, holder),
-                Expression::Parenthesis(expression) => self.bind_parenthesis(*expression, holder),
+                Declaration::Type(declaration) => type_declarations.push(declaration),
             }
-        } else {
-            None
+        }
+
+        for declaration in &type_declarations {
+            self.register_type(declaration);
+
+            if exported.contains(&declaration.name.literal) {
+                self.exported_types.insert(format!(
+                    "{}{}",
+                    self.module_prefix, declaration.name.literal
+                ));
+            }
+        }
+
+        for declaration in &type_declarations {
+            self.define_constructors(declaration, holder);
+        }
+
+        let names = definitions
+            .iter()
+            .map(|(name, _)| name.literal.clone())
+            .collect::<Vec<String>>();
+        let name_tokens = definitions
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<Token>>();
+        let dependencies = definitions
+            .iter()
+            .map(|(_, value)| {
+                let mut identifiers = vec![];
+                collect_identifiers(value, &mut identifiers);
+
+                names
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| identifiers.contains(name))
+                    .map(|(index, _)| index)
+                    .collect()
+            })
+            .collect::<Vec<Vec<usize>>>();
+        let mut values = definitions
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect::<Vec<Option<Expression>>>();
+        let mut bound_definitions = vec![];
+
+        // The root program sees the top-level definitions of the root programs bound before.
+        let outer_scopes = usize::from(name.is_none());
+
+        if name.is_none() {
+            self.scopes.push(self.root_scope.clone());
+        }
+
+        self.scopes.push(HashMap::new());
+        self.module_scopes = outer_scopes + 1;
+
+        // Definitions are bound one group of mutually recursive definitions at a time, each
+        // after the groups it depends on, so that every group can be generalized separately.
+        for group in strongly_connected_components(&dependencies) {
+            let recursive = group.len() > 1 || dependencies[group[0]].contains(&group[0]);
+            let variables = group
+                .iter()
+                .map(|_| self.fresh_variable())
+                .collect::<Vec<BoundType>>();
+
+            self.module_scopes = outer_scopes + 2;
+            let bindings = group
+                .iter()
+                .zip(&variables)
+                .map(|(index, variable)| {
+                    self.refer_binding(&name_tokens[*index], variable);
+
+                    (
+                        names[*index].clone(),
+                        Binding {
+                            scheme: TypeScheme::monomorphic(variable.clone()),
+                            definition: self.located(&name_tokens[*index]),
+                        },
+                    )
+                })
+                .collect();
+            self.scopes.push(bindings);
+
+            let mut bound_group = vec![];
+
+            for (index, variable) in group.iter().zip(&variables) {
+                let value = values[*index].take();
+                let span = name_tokens[*index].span.clone();
+
+                if recursive && !matches!(value, Some(Expression::Lambda(_, _))) {
+                    holder.at(Some(span), |holder| {
+                        holder.error(&format!(
+                            "Recursive binding \"{:}\" must be a function",
+                            names[*index]
+                        ))
+                    });
+                    continue;
+                }
+
+                let bound_value = self.bind(value, holder);
+
+                if !self.unify(variable, &bound_value.get_type()) {
+                    holder.at(Some(span), |holder| {
+                        holder.error(&format!(
+                            "Recursive binding \"{:}\" is used as type \"{:}\" but defined as \"{:}\"",
+                            names[*index],
+                            self.resolve(variable),
+                            self.resolve(&bound_value.get_type())
+                        ))
+                    });
+                }
+
+                bound_group.push((
+                    format!("{}{}", self.module_prefix, names[*index]),
+                    bound_value,
+                ));
+            }
+
+            self.scopes.pop();
+            self.module_scopes = outer_scopes + 1;
+
+            for (index, variable) in group.iter().zip(&variables) {
+                let scheme = self.generalize(variable);
+
+                if let Some(name) = name.filter(|_| exported.contains(&names[*index])) {
+                    self.exports
+                        .entry(name.to_string())
+                        .or_default()
+                        .insert(names[*index].clone(), scheme.clone());
+                }
+
+                let definition = self.located(&name_tokens[*index]);
+
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(names[*index].clone(), Binding { scheme, definition });
+                }
+            }
+
+            bound_definitions.extend(bound_group);
+        }
+
+        let mut entry = self.bind(program.entry, holder);
+
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.module_scopes = 0;
+
+        if name.is_none() {
+            self.scopes.pop();
+            self.root_scope.extend(scope);
+            self.root_imports = self.imports.clone();
+        }
+
+        if let Some(name) = name {
+            self.exports.entry(name.to_string()).or_default();
+        }
+
+        for (_, value) in bound_definitions.iter_mut() {
+            if let Some(value) = value.as_mut() {
+                self.resolve_expression(value);
+            }
+        }
+
+        if let Some(entry) = entry.as_mut() {
+            self.resolve_expression(entry);
+        }
+
+        BoundProgram {
+            definitions: bound_definitions,
+            entry,
+        }
+    }
+
+    /// Binds an expression, locating the diagnostics about it at its span.
+    fn bind(
+        &mut self,
+        expression: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let expression = expression?;
+
+        holder.at(expression.span(), |holder| {
+            self.bind_located(expression, holder)
+        })
+    }
+
+    fn bind_located(
+        &mut self,
+        expression: Expression,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        match expression {
+            Expression::Literal(token) => self.bind_literal(token.as_ref(), holder),
+            Expression::Identifier(token) => self.bind_identifier(token.as_ref(), holder),
+            Expression::Bool(token) => self.bind_bool(token.as_ref(), holder),
+            Expression::Number(token) => self.bind_number(token.as_ref(), holder),
+            Expression::Positive(expression) => self.bind_positive(*expression, holder),
+            Expression::Negative(expression) => self.bind_negative(*expression, holder),
+            Expression::NOT(expression) => self.bind_not(*expression, holder),
+            Expression::OR(left, right) => self.bind_or(*left, *right, holder),
+            Expression::AND(left, right) => self.bind_and(*left, *right, holder),
+            Expression::BangEqual(left, right) => self.bind_bang_equal(*left, *right, holder),
+            Expression::Equal(left, right) => self.bind_equal(*left, *right, holder),
+            Expression::Greater(left, right) => self.bind_greater(*left, *right, holder),
+            Expression::GreaterEqual(left, right) => self.bind_greater_equal(*left, *right, holder),
+            Expression::Less(left, right) => self.bind_less(*left, *right, holder),
+            Expression::LessEqual(left, right) => self.bind_less_equal(*left, *right, holder),
+            Expression::Addition(left, right) => self.bind_addition(*left, *right, holder),
+            Expression::Subtraction(left, right) => self.bind_subtraction(*left, *right, holder),
+            Expression::Multiplication(left, right) => {
+                self.bind_multiplication(*left, *right, holder)
+            }
+            Expression::Division(left, right) => self.bind_division(*left, *right, holder),
+            Expression::Remainder(left, right) => self.bind_remainder(*left, *right, holder),
+            Expression::Parenthesis(expression) => self.bind_parenthesis(*expression, holder),
+            Expression::List(elements) => self.bind_list(elements, holder),
+            Expression::Index(list, index) => self.bind_index(*list, *index, holder),
+            Expression::Lambda(parameters, body) => self.bind_lambda(parameters, *body, holder),
+            Expression::Call(callee, argument) => self.bind_call(*callee, *argument, holder),
+            Expression::Tuple(elements) => self.bind_tuple(elements, holder),
+            Expression::Record(fields) => self.bind_record(fields, holder),
+            Expression::RecordUpdate(record, fields) => {
+                self.bind_record_update(*record, fields, holder)
+            }
+            Expression::Field(record, field) => self.bind_field(*record, &field, holder),
+            Expression::TypeDeclaration(declaration, body) => {
+                self.bind_type_declaration(*declaration, *body, holder)
+            }
+            Expression::Match(scrutinee, arms) => self.bind_match(*scrutinee, arms, holder),
+            Expression::If(condition, then_branch, else_branch) => {
+                self.bind_if(*condition, *then_branch, *else_branch, holder)
+            }
+            Expression::Let(name, value, body) => self.bind_let(&name, *value, *body, holder),
+            Expression::LetRec(name, value, body) => {
+                self.bind_let_rec(&name, *value, *body, holder)
+            }
+        }
+    }
+
+    fn fresh_variable(&mut self) -> BoundType {
+        self.next_variable += 1;
+
+        BoundType::Variable(self.next_variable)
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<BoundType> {
+        let scheme = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|binding| &binding.scheme)
+            .or_else(|| self.host.get(name))
+            .cloned()
+            .or_else(|| Builtin::from_str(name).ok().map(builtin_scheme))?;
+
+        Some(self.instantiate(&scheme))
+    }
+
+    /// Quantifies the type variables which are not free in any enclosing scope.
+    fn generalize(&self, bound_type: &BoundType) -> TypeScheme {
+        let bound_type = self.resolve(bound_type);
+        let mut environment_variables = BTreeSet::new();
+
+        for scheme in self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.values().map(|binding| &binding.scheme))
+        {
+            let mut scheme_variables = BTreeSet::new();
+            self.resolve(&scheme.body)
+                .free_variables(&mut scheme_variables);
+
+            environment_variables.extend(
+                scheme_variables
+                    .into_iter()
+                    .filter(|variable| !scheme.variables.contains(variable)),
+            );
+        }
+
+        let mut variables = BTreeSet::new();
+        bound_type.free_variables(&mut variables);
+
+        TypeScheme::new(
+            variables
+                .difference(&environment_variables)
+                .copied()
+                .collect(),
+            bound_type,
+        )
+    }
+
+    fn instantiate(&mut self, scheme: &TypeScheme) -> BoundType {
+        let mapping = scheme
+            .variables
+            .iter()
+            .map(|variable| (*variable, self.fresh_variable()))
+            .collect::<HashMap<usize, BoundType>>();
+
+        scheme.body.substitute(&mapping)
+    }
+
+    /// Follows the substitution until the outermost constructor of a type is known.
+    ///
+    /// Records whose row variable has been substituted are flattened into a single record.
+    fn prune(&self, bound_type: &BoundType) -> BoundType {
+        match bound_type {
+            BoundType::Variable(variable) => match self.substitution.get(variable) {
+                Some(substituted) => self.prune(substituted),
+                None => bound_type.clone(),
+            },
+            BoundType::Record(fields, Some(row)) => match self.substitution.get(row) {
+                Some(substituted) => match self.prune(substituted) {
+                    BoundType::Record(mut rest, rest_row) => {
+                        rest.extend(fields.clone());
+
+                        BoundType::Record(rest, rest_row)
+                    }
+                    _ => bound_type.clone(),
+                },
+                None => bound_type.clone(),
+            },
+            _ => bound_type.clone(),
+        }
+    }
+
+    /// Applies the substitution to every nested type.
+    pub fn resolve(&self, bound_type: &BoundType) -> BoundType {
+        match self.prune(bound_type) {
+            BoundType::List(element) => BoundType::List(Box::new(self.resolve(&element))),
+            BoundType::Function(parameter, result) => BoundType::Function(
+                Box::new(self.resolve(&parameter)),
+                Box::new(self.resolve(&result)),
+            ),
+            BoundType::Tuple(elements) => BoundType::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.resolve(element))
+                    .collect(),
+            ),
+            BoundType::Named(name, arguments) => BoundType::Named(
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| self.resolve(argument))
+                    .collect(),
+            ),
+            BoundType::Record(fields, row) => BoundType::Record(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), self.resolve(field)))
+                    .collect(),
+                row,
+            ),
+            pruned => pruned,
+        }
+    }
+
+    fn occurs(&self, variable: usize, bound_type: &BoundType) -> bool {
+        match self.prune(bound_type) {
+            BoundType::Variable(other) => other == variable,
+            BoundType::List(element) => self.occurs(variable, &element),
+            BoundType::Function(parameter, result) => {
+                self.occurs(variable, &parameter) || self.occurs(variable, &result)
+            }
+            BoundType::Tuple(elements) | BoundType::Named(_, elements) => elements
+                .iter()
+                .any(|element| self.occurs(variable, element)),
+            BoundType::Record(fields, row) => {
+                row == Some(variable) || fields.values().any(|field| self.occurs(variable, field))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether values of this type may hold functions, which cannot be compared for equality.
+    /// `named` collects the sum types already looked into, which may be recursive.
+    fn contains_function(&self, bound_type: &BoundType, named: &mut HashSet<String>) -> bool {
+        match self.prune(bound_type) {
+            BoundType::Function(_, _) => true,
+            BoundType::List(element) => self.contains_function(&element, named),
+            BoundType::Tuple(elements) => elements
+                .iter()
+                .any(|element| self.contains_function(element, named)),
+            BoundType::Record(fields, _) => fields
+                .values()
+                .any(|field| self.contains_function(field, named)),
+            BoundType::Named(name, arguments) => {
+                arguments
+                    .iter()
+                    .any(|argument| self.contains_function(argument, named))
+                    || (named.insert(name.clone())
+                        && self.types.get(&name).is_some_and(|definition| {
+                            definition
+                                .constructors
+                                .iter()
+                                .filter_map(|(constructor, _)| self.constructors.get(constructor))
+                                .flat_map(|constructor| &constructor.fields)
+                                .any(|field| self.contains_function(field, named))
+                        }))
+            }
+            _ => false,
+        }
+    }
+
+    fn unify(&mut self, left: &BoundType, right: &BoundType) -> bool {
+        let left = self.prune(left);
+        let right = self.prune(right);
+
+        match (&left, &right) {
+            (BoundType::Unidentified, _) | (_, BoundType::Unidentified) => true,
+            (BoundType::Variable(left), BoundType::Variable(right)) if left == right => true,
+            (BoundType::Variable(variable), other) | (other, BoundType::Variable(variable)) => {
+                if self.occurs(*variable, other) {
+                    false
+                } else {
+                    self.substitution.insert(*variable, other.clone());
+                    true
+                }
+            }
+            (BoundType::List(left), BoundType::List(right)) => self.unify(left, right),
+            (
+                BoundType::Function(left_parameter, left_result),
+                BoundType::Function(right_parameter, right_result),
+            ) => {
+                self.unify(left_parameter, right_parameter) && self.unify(left_result, right_result)
+            }
+            (BoundType::Named(left_name, left), BoundType::Named(right_name, right))
+                if left_name == right_name =>
+            {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right)
+                        .all(|(left, right)| self.unify(left, right))
+            }
+            (BoundType::Tuple(left), BoundType::Tuple(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right)
+                        .all(|(left, right)| self.unify(left, right))
+            }
+            (
+                BoundType::Record(left_fields, left_row),
+                BoundType::Record(right_fields, right_row),
+            ) => self.unify_records(left_fields, *left_row, right_fields, *right_row),
+            _ => left == right,
+        }
+    }
+
+    /// Unifies two records structurally, extending open records through their row variables.
+    fn unify_records(
+        &mut self,
+        left_fields: &BTreeMap<String, BoundType>,
+        left_row: Option<usize>,
+        right_fields: &BTreeMap<String, BoundType>,
+        right_row: Option<usize>,
+    ) -> bool {
+        for (name, left_field) in left_fields {
+            if let Some(right_field) = right_fields.get(name) {
+                if !self.unify(left_field, right_field) {
+                    return false;
+                }
+            }
+        }
+
+        let only_left = left_fields
+            .iter()
+            .filter(|(name, _)| !right_fields.contains_key(*name))
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect::<BTreeMap<String, BoundType>>();
+        let only_right = right_fields
+            .iter()
+            .filter(|(name, _)| !left_fields.contains_key(*name))
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect::<BTreeMap<String, BoundType>>();
+
+        match (left_row, right_row) {
+            (None, None) => only_left.is_empty() && only_right.is_empty(),
+            (Some(left_row), None) => {
+                only_left.is_empty()
+                    && self.unify(
+                        &BoundType::Variable(left_row),
+                        &BoundType::Record(only_right, None),
+                    )
+            }
+            (None, Some(right_row)) => {
+                only_right.is_empty()
+                    && self.unify(
+                        &BoundType::Variable(right_row),
+                        &BoundType::Record(only_left, None),
+                    )
+            }
+            (Some(left_row), Some(right_row)) if left_row == right_row => {
+                only_left.is_empty() && only_right.is_empty()
+            }
+            (Some(left_row), Some(right_row)) => {
+                let rest_row = self.fresh_row();
+
+                self.unify(
+                    &BoundType::Variable(left_row),
+                    &BoundType::Record(only_right, rest_row),
+                ) && self.unify(
+                    &BoundType::Variable(right_row),
+                    &BoundType::Record(only_left, rest_row),
+                )
+            }
+        }
+    }
+
+    fn fresh_row(&mut self) -> Option<usize> {
+        match self.fresh_variable() {
+            BoundType::Variable(row) => Some(row),
+            _ => None,
+        }
+    }
+
+    /// Unifies both operands with the expected type, without stopping at the first mismatch.
+    fn unify_operands(
+        &mut self,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        expected: &BoundType,
+    ) -> bool {
+        let left_unified = self.unify(&left.get_type(), expected);
+        let right_unified = self.unify(&right.get_type(), expected);
+
+        left_unified && right_unified
+    }
+
+    fn resolve_expression(&self, expression: &mut BoundExpression) {
+        if let Some(bound_type) = expression.type_mut() {
+            *bound_type = self.resolve(bound_type);
+        }
+
+        for child in expression.children_mut().into_iter().flatten() {
+            self.resolve_expression(child);
         }
     }
 
     fn bind_literal(
-        &self,
+        &mut self,
         token: &Token,
         _holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
@@ -67,19 +1002,165 @@ impl Binder {
     }
 
     fn bind_identifier(
-        &self,
+        &mut self,
         token: &Token,
-        _holder: &mut DiagnosticHolder,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let scope_index = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&token.literal));
+
+        if scope_index.is_none() {
+            if let Some(key) = self.constructor_key(&token.literal) {
+                let constructor = self.constructors[&key].clone();
+                let bound_type = self.instantiate(&constructor.scheme());
+
+                self.refer(token, Some(bound_type.clone()), constructor.definition);
+
+                return Some(BoundExpression::Constructor(
+                    key,
+                    constructor.fields.len(),
+                    bound_type,
+                ));
+            }
+        }
+
+        if let Some((module, item)) = token.literal.rsplit_once("::") {
+            let bound_identifier = self.bind_qualified(module, item, holder);
+            self.refer(
+                token,
+                bound_identifier.as_ref().map(BoundExpression::get_type),
+                None,
+            );
+
+            return bound_identifier;
+        }
+
+        if scope_index.is_none() && !self.host.contains_key(&token.literal) {
+            if let Some((module, scheme)) = self.prelude_scheme(&token.literal) {
+                let bound_type = self.instantiate(&scheme);
+                self.refer(token, Some(bound_type.clone()), None);
+
+                return Some(BoundExpression::Identifier(
+                    format!("{}::{}", module, token.literal),
+                    bound_type,
+                ));
+            }
+        }
+
+        if let Some(bound_type) = self.lookup(&token.literal) {
+            let definition =
+                scope_index.and_then(|index| self.scopes[index][&token.literal].definition.clone());
+            self.refer(token, Some(bound_type.clone()), definition);
+
+            // Top-level definitions are known to the evaluator by their module qualified name.
+            let name = match scope_index {
+                Some(index) if index < self.module_scopes => {
+                    format!("{}{}", self.module_prefix, token.literal)
+                }
+                _ => token.literal.to_owned(),
+            };
+
+            Some(BoundExpression::Identifier(name, bound_type))
+        } else {
+            holder.error(&format!("Unknown identifier \"{:}\"", token.literal));
+            None
+        }
+    }
+
+    fn bind_qualified(
+        &mut self,
+        module: &str,
+        item: &str,
+        holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        Some(BoundExpression::Identifier(token.literal.to_owned()))
+        if !self.imports.iter().any(|import| import == module) {
+            holder.error(&format!("Module \"{:}\" is not imported", module));
+            return None;
+        }
+
+        match self
+            .exports
+            .get(module)
+            .and_then(|exports| exports.get(item))
+            .cloned()
+        {
+            Some(scheme) => Some(BoundExpression::Identifier(
+                format!("{}::{}", module, item),
+                self.instantiate(&scheme),
+            )),
+            None => {
+                holder.error(&format!(
+                    "Module \"{:}\" does not export \"{:}\"",
+                    module, item
+                ));
+                None
+            }
+        }
+    }
+
+    fn prelude_scheme(&self, name: &str) -> Option<(String, TypeScheme)> {
+        let module = self.prelude.as_ref()?;
+        let scheme = self.exports.get(module)?.get(name)?;
+
+        Some((module.clone(), scheme.clone()))
+    }
+
+    /// Resolves a constructor as written in the current module to the name it is registered by.
+    fn constructor_key(&self, name: &str) -> Option<String> {
+        let name = canonical(name);
+
+        if let Some((module, _)) = name.rsplit_once("::") {
+            let visible = self.imports.iter().any(|import| import == module)
+                && self.constructors.get(name).is_some_and(|constructor| {
+                    self.exported_types.contains(&constructor.type_name)
+                });
+
+            return visible.then(|| name.to_string());
+        }
+
+        let key = format!("{}{}", self.module_prefix, name);
+
+        if self.constructors.contains_key(&key) {
+            Some(key)
+        } else if is_builtin_name(name) && self.constructors.contains_key(name) {
+            Some(name.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Resolves a type as written in the current module to the name it is registered by.
+    fn type_key(&self, name: &str) -> Option<String> {
+        if let Some((module, _)) = name.rsplit_once("::") {
+            let visible = self.imports.iter().any(|import| import == module)
+                && self.exported_types.contains(name);
+
+            return visible.then(|| name.to_string());
+        }
+
+        let key = format!("{}{}", self.module_prefix, name);
+
+        if self.types.contains_key(&key) {
+            Some(key)
+        } else if is_builtin_name(name) && self.types.contains_key(name) {
+            Some(name.to_string())
+        } else {
+            None
+        }
     }
 
-    fn bind_bool(&self, token: &Token, _holder: &mut DiagnosticHolder) -> Option<BoundExpression> {
+    fn bind_bool(
+        &mut self,
+        token: &Token,
+        _holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
         Some(BoundExpression::Bool(token.literal.to_owned()))
     }
 
     fn bind_number(
-        &self,
+        &mut self,
         token: &Token,
         _holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
@@ -87,16 +1168,16 @@ impl Binder {
     }
 
     fn bind_positive(
-        &self,
+        &mut self,
         expression: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_expression = self.bind_expression(expression, holder);
+        let bound_expression = self.bind(expression, holder);
 
-        if bound_expression.get_type() != BoundType::Number {
-            holder.error(&*format!(
+        if !self.unify(&bound_expression.get_type(), &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply positive on type \"{:}\"",
-                bound_expression.get_type().to_string()
+                self.resolve(&bound_expression.get_type())
             ))
         }
 
@@ -104,16 +1185,16 @@ impl Binder {
     }
 
     fn bind_negative(
-        &self,
+        &mut self,
         expression: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_expression = self.bind_expression(expression, holder);
+        let bound_expression = self.bind(expression, holder);
 
-        if bound_expression.get_type() != BoundType::Number {
-            holder.error(&*format!(
+        if !self.unify(&bound_expression.get_type(), &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply negative on type \"{:}\"",
-                bound_expression.get_type().to_string()
+                self.resolve(&bound_expression.get_type())
             ))
         }
 
@@ -121,16 +1202,16 @@ impl Binder {
     }
 
     fn bind_not(
-        &self,
+        &mut self,
         expression: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_expression = self.bind_expression(expression, holder);
+        let bound_expression = self.bind(expression, holder);
 
-        if bound_expression.get_type() != BoundType::Bool {
-            holder.error(&*format!(
+        if !self.unify(&bound_expression.get_type(), &BoundType::Bool) {
+            holder.error(&format!(
                 "Cannot apply logical NOT on type \"{:}\"",
-                bound_expression.get_type().to_string()
+                self.resolve(&bound_expression.get_type())
             ))
         }
 
@@ -138,19 +1219,19 @@ impl Binder {
     }
 
     fn bind_or(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Bool || bound_right.get_type() != BoundType::Bool {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Bool) {
+            holder.error(&format!(
                 "Cannot apply logical OR on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -161,19 +1242,19 @@ impl Binder {
     }
 
     fn bind_and(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Bool || bound_right.get_type() != BoundType::Bool {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Bool) {
+            holder.error(&format!(
                 "Cannot apply logical AND on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -184,19 +1265,24 @@ impl Binder {
     }
 
     fn bind_bang_equal(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != bound_right.get_type() {
-            holder.error(&*format!(
+        if !self.unify(&bound_left.get_type(), &bound_right.get_type()) {
+            holder.error(&format!(
                 "Cannot check equality on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
+            ))
+        } else if self.contains_function(&bound_left.get_type(), &mut HashSet::new()) {
+            holder.error(&format!(
+                "Cannot check equality on type \"{:}\" holding functions",
+                self.resolve(&bound_left.get_type())
             ))
         }
 
@@ -207,19 +1293,24 @@ impl Binder {
     }
 
     fn bind_equal(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != bound_right.get_type() {
-            holder.error(&*format!(
+        if !self.unify(&bound_left.get_type(), &bound_right.get_type()) {
+            holder.error(&format!(
                 "Cannot check equality on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
+            ))
+        } else if self.contains_function(&bound_left.get_type(), &mut HashSet::new()) {
+            holder.error(&format!(
+                "Cannot check equality on type \"{:}\" holding functions",
+                self.resolve(&bound_left.get_type())
             ))
         }
 
@@ -230,20 +1321,19 @@ impl Binder {
     }
 
     fn bind_greater(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply greater on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -254,20 +1344,19 @@ impl Binder {
     }
 
     fn bind_greater_equal(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply greater equal than on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -278,20 +1367,19 @@ impl Binder {
     }
 
     fn bind_less(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply less than on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -302,20 +1390,19 @@ impl Binder {
     }
 
     fn bind_less_equal(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply less equal than on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -326,20 +1413,19 @@ impl Binder {
     }
 
     fn bind_addition(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply addition on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -350,20 +1436,19 @@ impl Binder {
     }
 
     fn bind_subtraction(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply subtraction on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -374,20 +1459,19 @@ impl Binder {
     }
 
     fn bind_multiplication(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply multiplication on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
@@ -398,23 +1482,26 @@ impl Binder {
     }
 
     fn bind_division(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply division on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
+        if is_zero_literal(&bound_right) {
+            holder.warning("Division by zero");
+        }
+
         Some(BoundExpression::Division(
             Box::new(bound_left),
             Box::new(bound_right),
@@ -422,23 +1509,26 @@ impl Binder {
     }
 
     fn bind_remainder(
-        &self,
+        &mut self,
         left: Option<Expression>,
         right: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
-        let bound_left = self.bind_expression(left, holder);
-        let bound_right = self.bind_expression(right, holder);
+        let bound_left = self.bind(left, holder);
+        let bound_right = self.bind(right, holder);
 
-        if bound_left.get_type() != BoundType::Number || bound_right.get_type() != BoundType::Number
-        {
-            holder.error(&*format!(
+        if !self.unify_operands(&bound_left, &bound_right, &BoundType::Number) {
+            holder.error(&format!(
                 "Cannot apply remainder on type \"{:}\" and \"{:}\"",
-                bound_left.get_type().to_string(),
-                bound_right.get_type().to_string()
+                self.resolve(&bound_left.get_type()),
+                self.resolve(&bound_right.get_type())
             ))
         }
 
+        if is_zero_literal(&bound_right) {
+            holder.warning("Remainder by zero");
+        }
+
         Some(BoundExpression::Remainder(
             Box::new(bound_left),
             Box::new(bound_right),
@@ -446,29 +1536,1361 @@ impl Binder {
     }
 
     fn bind_parenthesis(
-        &self,
+        &mut self,
         expression: Option<Expression>,
         holder: &mut DiagnosticHolder,
     ) -> Option<BoundExpression> {
         Some(BoundExpression::Parenthesis(Box::new(
-            self.bind_expression(expression, holder),
+            self.bind(expression, holder),
         )))
     }
+
+    fn bind_list(
+        &mut self,
+        elements: Vec<Option<Expression>>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let element_type = self.fresh_variable();
+        let mut bound_elements = vec![];
+
+        for element in elements {
+            let bound_element = self.bind(element, holder);
+
+            if !self.unify(&element_type, &bound_element.get_type()) {
+                holder.error(&format!(
+                    "List elements must have the same type, found \"{:}\" and \"{:}\"",
+                    self.resolve(&element_type),
+                    self.resolve(&bound_element.get_type())
+                ))
+            }
+
+            bound_elements.push(bound_element);
+        }
+
+        Some(BoundExpression::List(
+            bound_elements,
+            BoundType::list(element_type),
+        ))
+    }
+
+    fn bind_index(
+        &mut self,
+        list: Option<Expression>,
+        index: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_list = self.bind(list, holder);
+        let bound_index = self.bind(index, holder);
+        let element_type = self.fresh_variable();
+
+        if !self.unify(
+            &bound_list.get_type(),
+            &BoundType::list(element_type.clone()),
+        ) {
+            holder.error(&format!(
+                "Cannot index into type \"{:}\"",
+                self.resolve(&bound_list.get_type())
+            ))
+        }
+
+        if !self.unify(&bound_index.get_type(), &BoundType::Number) {
+            holder.error(&format!(
+                "Cannot index with type \"{:}\"",
+                self.resolve(&bound_index.get_type())
+            ))
+        }
+
+        Some(BoundExpression::Index(
+            Box::new(bound_list),
+            Box::new(bound_index),
+            element_type,
+        ))
+    }
+
+    fn bind_lambda(
+        &mut self,
+        parameters: Vec<Token>,
+        body: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let parameters = parameters
+            .into_iter()
+            .map(|parameter| {
+                let parameter_type = self.fresh_variable();
+                self.refer_binding(&parameter, &parameter_type);
+
+                (parameter, parameter_type)
+            })
+            .collect::<Vec<(Token, BoundType)>>();
+
+        self.scopes.push(
+            parameters
+                .iter()
+                .map(|(parameter, bound_type)| {
+                    (
+                        parameter.literal.clone(),
+                        Binding {
+                            scheme: TypeScheme::monomorphic(bound_type.clone()),
+                            definition: self.located(parameter),
+                        },
+                    )
+                })
+                .collect(),
+        );
+        let mut lambda = self.bind(body, holder);
+        self.scopes.pop();
+
+        // Multiple parameters are curried into nested single parameter lambdas.
+        for (parameter, parameter_type) in parameters.into_iter().rev() {
+            let lambda_type = BoundType::function(parameter_type, lambda.get_type());
+
+            lambda = Some(BoundExpression::Lambda(
+                parameter.literal,
+                Rc::new(lambda),
+                lambda_type,
+            ));
+        }
+
+        lambda
+    }
+
+    fn bind_if(
+        &mut self,
+        condition: Option<Expression>,
+        then_branch: Option<Expression>,
+        else_branch: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_condition = self.bind(condition, holder);
+        let bound_then = self.bind(then_branch, holder);
+        let bound_else = self.bind(else_branch, holder);
+
+        if !self.unify(&bound_condition.get_type(), &BoundType::Bool) {
+            holder.error(&format!(
+                "Condition must be of type \"bool\", found \"{:}\"",
+                self.resolve(&bound_condition.get_type())
+            ))
+        }
+
+        if !self.unify(&bound_then.get_type(), &bound_else.get_type()) {
+            holder.error(&format!(
+                "If branches must have the same type, found \"{:}\" and \"{:}\"",
+                self.resolve(&bound_then.get_type()),
+                self.resolve(&bound_else.get_type())
+            ))
+        }
+
+        Some(BoundExpression::If(
+            Box::new(bound_condition),
+            Box::new(bound_then),
+            Box::new(bound_else),
+        ))
+    }
+
+    fn bind_let(
+        &mut self,
+        name: &Token,
+        value: Option<Expression>,
+        body: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_value = self.bind(value, holder);
+        let scheme = self.generalize(&bound_value.get_type());
+
+        self.refer_binding(name, &bound_value.get_type());
+        self.scopes.push(HashMap::from([(
+            name.literal.clone(),
+            Binding {
+                scheme,
+                definition: self.located(name),
+            },
+        )]));
+        let bound_body = self.bind(body, holder);
+        self.scopes.pop();
+
+        Some(BoundExpression::Let(
+            name.literal.clone(),
+            Box::new(bound_value),
+            Box::new(bound_body),
+        ))
+    }
+
+    fn bind_let_rec(
+        &mut self,
+        name: &Token,
+        value: Option<Expression>,
+        body: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        if !matches!(value, Some(Expression::Lambda(_, _))) {
+            holder.at(Some(name.span.clone()), |holder| {
+                holder.error(&format!(
+                    "Recursive binding \"{:}\" must be a function",
+                    name.literal
+                ))
+            });
+            return None;
+        }
+
+        let function_type = self.fresh_variable();
+
+        self.refer_binding(name, &function_type);
+        self.scopes.push(HashMap::from([(
+            name.literal.clone(),
+            Binding {
+                scheme: TypeScheme::monomorphic(function_type.clone()),
+                definition: self.located(name),
+            },
+        )]));
+        let bound_value = self.bind(value, holder);
+        self.scopes.pop();
+
+        if !self.unify(&function_type, &bound_value.get_type()) {
+            holder.error(&format!(
+                "Recursive binding \"{:}\" is used as type \"{:}\" but defined as \"{:}\"",
+                name.literal,
+                self.resolve(&function_type),
+                self.resolve(&bound_value.get_type())
+            ))
+        }
+
+        let scheme = self.generalize(&function_type);
+
+        self.scopes.push(HashMap::from([(
+            name.literal.clone(),
+            Binding {
+                scheme,
+                definition: self.located(name),
+            },
+        )]));
+        let bound_body = self.bind(body, holder);
+        self.scopes.pop();
+
+        Some(BoundExpression::LetRec(
+            name.literal.clone(),
+            Box::new(bound_value),
+            Box::new(bound_body),
+        ))
+    }
+
+    fn bind_call(
+        &mut self,
+        callee: Option<Expression>,
+        argument: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_callee = self.bind(callee, holder);
+        let bound_argument = self.bind(argument, holder);
+        let result_type = self.fresh_variable();
+
+        if !self.unify(
+            &bound_callee.get_type(),
+            &BoundType::function(bound_argument.get_type(), result_type.clone()),
+        ) {
+            holder.error(&format!(
+                "Cannot apply argument of type \"{:}\" to type \"{:}\"",
+                self.resolve(&bound_argument.get_type()),
+                self.resolve(&bound_callee.get_type())
+            ))
+        }
+
+        Some(BoundExpression::Call(
+            Box::new(bound_callee),
+            Box::new(bound_argument),
+            result_type,
+        ))
+    }
+
+    fn bind_tuple(
+        &mut self,
+        elements: Vec<Option<Expression>>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_elements = elements
+            .into_iter()
+            .map(|element| self.bind(element, holder))
+            .collect::<Vec<Option<BoundExpression>>>();
+        let tuple_type = BoundType::Tuple(
+            bound_elements
+                .iter()
+                .map(|element| element.get_type())
+                .collect(),
+        );
+
+        Some(BoundExpression::Tuple(bound_elements, tuple_type))
+    }
+
+    fn bind_record_fields(
+        &mut self,
+        fields: Vec<(Token, Option<Expression>)>,
+        holder: &mut DiagnosticHolder,
+    ) -> Vec<(String, Option<BoundExpression>)> {
+        let mut bound_fields: Vec<(String, Option<BoundExpression>)> = vec![];
+
+        for (name, value) in fields {
+            if bound_fields
+                .iter()
+                .any(|(bound_name, _)| *bound_name == name.literal)
+            {
+                holder.error(&format!("Duplicate record field \"{:}\"", name.literal));
+            }
+
+            let bound_value = self.bind(value, holder);
+            bound_fields.push((name.literal, bound_value));
+        }
+
+        bound_fields
+    }
+
+    fn bind_record(
+        &mut self,
+        fields: Vec<(Token, Option<Expression>)>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_fields = self.bind_record_fields(fields, holder);
+        let record_type = BoundType::Record(
+            bound_fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.get_type()))
+                .collect(),
+            None,
+        );
+
+        Some(BoundExpression::Record(bound_fields, record_type))
+    }
+
+    fn bind_record_update(
+        &mut self,
+        record: Option<Expression>,
+        fields: Vec<(Token, Option<Expression>)>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_record = self.bind(record, holder);
+        let bound_fields = self.bind_record_fields(fields, holder);
+        let row = self.fresh_row();
+        let updated_type = BoundType::Record(
+            bound_fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.get_type()))
+                .collect(),
+            row,
+        );
+
+        if !self.unify(&bound_record.get_type(), &updated_type) {
+            holder.error(&format!(
+                "Cannot update type \"{:}\" with fields of type \"{:}\"",
+                self.resolve(&bound_record.get_type()),
+                self.resolve(&updated_type)
+            ))
+        }
+
+        let record_type = bound_record.get_type();
+
+        Some(BoundExpression::RecordUpdate(
+            Box::new(bound_record),
+            bound_fields,
+            record_type,
+        ))
+    }
+
+    fn bind_field(
+        &mut self,
+        record: Option<Expression>,
+        field: &Token,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let bound_record = self.bind(record, holder);
+
+        if field.token_type == Type::Number {
+            return self.bind_tuple_field(bound_record, field, holder);
+        }
+
+        let field_type = self.fresh_variable();
+        let row = self.fresh_row();
+        let expected_type = BoundType::Record(
+            BTreeMap::from([(field.literal.clone(), field_type.clone())]),
+            row,
+        );
+
+        if !self.unify(&bound_record.get_type(), &expected_type) {
+            holder.error(&format!(
+                "Type \"{:}\" has no field \"{:}\"",
+                self.resolve(&bound_record.get_type()),
+                field.literal
+            ))
+        }
+
+        Some(BoundExpression::Field(
+            Box::new(bound_record),
+            field.literal.clone(),
+            field_type,
+        ))
+    }
+
+    fn bind_tuple_field(
+        &mut self,
+        bound_tuple: Option<BoundExpression>,
+        field: &Token,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let position = field.literal.parse::<usize>().ok();
+
+        match (self.prune(&bound_tuple.get_type()), position) {
+            (BoundType::Tuple(elements), Some(position)) if position < elements.len() => {
+                Some(BoundExpression::TupleField(
+                    Box::new(bound_tuple),
+                    position,
+                    elements[position].clone(),
+                ))
+            }
+            (BoundType::Unidentified, _) => None,
+            (BoundType::Variable(_), _) => {
+                holder.error(&format!(
+                    "Cannot infer tuple type to access position {:}",
+                    field.literal
+                ));
+                None
+            }
+            (tuple_type, _) => {
+                holder.error(&format!(
+                    "Type \"{:}\" has no position {:}",
+                    self.resolve(&tuple_type),
+                    field.literal
+                ));
+                None
+            }
+        }
+    }
+
+    fn bind_type_declaration(
+        &mut self,
+        declaration: TypeDeclaration,
+        body: Option<Expression>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        self.register_type(&declaration);
+        self.define_constructors(&declaration, holder);
+
+        self.bind(body, holder)
+    }
+
+    /// Registers a type before resolving any constructor fields so that types may refer to
+    /// themselves or, at the top level, to each other.
+    fn register_type(&mut self, declaration: &TypeDeclaration) {
+        let parameters = declaration
+            .parameters
+            .iter()
+            .map(|_| {
+                self.next_variable += 1;
+                self.next_variable
+            })
+            .collect();
+
+        self.types.insert(
+            format!("{}{}", self.module_prefix, declaration.name.literal),
+            TypeDefinition {
+                parameters,
+                constructors: declaration
+                    .constructors
+                    .iter()
+                    .map(|(name, fields)| {
+                        (
+                            format!("{}{}", self.module_prefix, name.literal),
+                            fields.len(),
+                        )
+                    })
+                    .collect(),
+                definition: self.located(&declaration.name),
+            },
+        );
+    }
+
+    fn define_constructors(
+        &mut self,
+        declaration: &TypeDeclaration,
+        holder: &mut DiagnosticHolder,
+    ) {
+        let type_name = format!("{}{}", self.module_prefix, declaration.name.literal);
+        let parameter_variables = match self.types.get(&type_name) {
+            Some(definition) => definition.parameters.clone(),
+            None => return,
+        };
+        let parameters = declaration
+            .parameters
+            .iter()
+            .zip(&parameter_variables)
+            .map(|(parameter, variable)| {
+                (parameter.literal.clone(), BoundType::Variable(*variable))
+            })
+            .collect::<HashMap<String, BoundType>>();
+
+        for name in std::iter::once(&declaration.name).chain(&declaration.parameters) {
+            let definition = self.located(name);
+            self.refer(name, None, definition);
+        }
+
+        for (name, fields) in &declaration.constructors {
+            for field in fields {
+                self.refer_types(field, &declaration.parameters);
+            }
+
+            let fields = fields
+                .iter()
+                .map(|field| {
+                    self.resolve_type_expression(field, &parameters, holder)
+                        .unwrap_or(BoundType::Unidentified)
+                })
+                .collect();
+            let constructor = ConstructorDefinition {
+                type_name: type_name.clone(),
+                parameters: parameter_variables.clone(),
+                fields,
+                definition: self.located(name),
+            };
+
+            self.refer_binding(name, &constructor.scheme().body);
+            self.constructors.insert(
+                format!("{}{}", self.module_prefix, name.literal),
+                constructor,
+            );
+        }
+    }
+
+    /// Records the types named in a constructor field, see [`Binder::refer`], which are bound
+    /// by the type declaration's `parameters` or by other type declarations.
+    fn refer_types(&mut self, type_expression: &TypeExpression, parameters: &[Token]) {
+        match type_expression {
+            TypeExpression::Named(name, arguments) => {
+                let definition = match parameters
+                    .iter()
+                    .find(|parameter| parameter.literal == name.literal)
+                {
+                    Some(parameter) => self.located(parameter),
+                    None => self
+                        .type_key(canonical(&name.literal))
+                        .and_then(|key| self.types.get(&key))
+                        .and_then(|definition| definition.definition.clone()),
+                };
+
+                self.refer(name, None, definition);
+
+                for argument in arguments {
+                    self.refer_types(argument, parameters);
+                }
+            }
+            TypeExpression::Function(parameter, result) => {
+                self.refer_types(parameter, parameters);
+                self.refer_types(result, parameters);
+            }
+            TypeExpression::Tuple(elements) => {
+                for element in elements {
+                    self.refer_types(element, parameters);
+                }
+            }
+            TypeExpression::Record(fields) => {
+                for (_, field) in fields {
+                    self.refer_types(field, parameters);
+                }
+            }
+        }
+    }
+
+    /// Converts a written type into a bound type, with `parameters` naming the type variables in scope.
+    pub fn resolve_type_expression(
+        &mut self,
+        type_expression: &TypeExpression,
+        parameters: &HashMap<String, BoundType>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundType> {
+        match type_expression {
+            TypeExpression::Named(name, arguments) => {
+                let mut resolved_arguments = vec![];
+
+                for argument in arguments {
+                    resolved_arguments
+                        .push(self.resolve_type_expression(argument, parameters, holder)?);
+                }
+
+                let literal = canonical(&name.literal);
+                let key = self.type_key(literal);
+
+                // Type parameters and user-defined types shadow the built-in type names.
+                let expected_arguments = if parameters.contains_key(literal) {
+                    0
+                } else if let Some(definition) = key.as_ref().and_then(|key| self.types.get(key)) {
+                    definition.parameters.len()
+                } else if matches!(literal, "List" | "列表") {
+                    1
+                } else {
+                    0
+                };
+
+                if resolved_arguments.len() != expected_arguments {
+                    holder.at(Some(name.span.clone()), |holder| {
+                        holder.error(&format!(
+                            "Type \"{:}\" expects {:} type arguments, found {:}",
+                            literal,
+                            expected_arguments,
+                            resolved_arguments.len()
+                        ))
+                    });
+                    return None;
+                }
+
+                if let Some(parameter) = parameters.get(literal) {
+                    return Some(parameter.clone());
+                }
+
+                if let Some(key) = key {
+                    return Some(BoundType::Named(key, resolved_arguments));
+                }
+
+                match literal {
+                    "number" | "數字" => Some(BoundType::Number),
+                    "string" | "字串" => Some(BoundType::String),
+                    "bool" | "布林" => Some(BoundType::Bool),
+                    "List" | "列表" => Some(BoundType::list(resolved_arguments.remove(0))),
+                    _ => {
+                        holder.at(Some(name.span.clone()), |holder| {
+                            holder.error(&format!("Unknown type \"{:}\"", literal))
+                        });
+                        None
+                    }
+                }
+            }
+            TypeExpression::Function(parameter, result) => Some(BoundType::function(
+                self.resolve_type_expression(parameter, parameters, holder)?,
+                self.resolve_type_expression(result, parameters, holder)?,
+            )),
+            TypeExpression::Tuple(elements) => {
+                let mut resolved_elements = vec![];
+
+                for element in elements {
+                    resolved_elements
+                        .push(self.resolve_type_expression(element, parameters, holder)?);
+                }
+
+                Some(BoundType::Tuple(resolved_elements))
+            }
+            TypeExpression::Record(fields) => {
+                let mut resolved_fields = BTreeMap::new();
+
+                for (name, field) in fields {
+                    resolved_fields.insert(
+                        name.literal.clone(),
+                        self.resolve_type_expression(field, parameters, holder)?,
+                    );
+                }
+
+                Some(BoundType::Record(resolved_fields, None))
+            }
+        }
+    }
+
+    fn bind_match(
+        &mut self,
+        scrutinee: Option<Expression>,
+        arms: Vec<(Pattern, Option<Expression>)>,
+        holder: &mut DiagnosticHolder,
+    ) -> Option<BoundExpression> {
+        let errors = holder.error_count();
+        let bound_scrutinee = self.bind(scrutinee, holder);
+        let scrutinee_type = bound_scrutinee.get_type();
+        let result_type = self.fresh_variable();
+        let mut bound_arms = vec![];
+
+        for (pattern, body) in arms {
+            let mut bindings = HashMap::new();
+            let bound_pattern = self.bind_pattern(pattern, &scrutinee_type, &mut bindings, holder);
+
+            self.scopes.push(bindings);
+            let bound_body = self.bind(body, holder);
+            self.scopes.pop();
+
+            if !self.unify(&result_type, &bound_body.get_type()) {
+                holder.error(&format!(
+                    "Match arms must have the same type, found \"{:}\" and \"{:}\"",
+                    self.resolve(&result_type),
+                    self.resolve(&bound_body.get_type())
+                ))
+            }
+
+            bound_arms.push((bound_pattern, bound_body));
+        }
+
+        // Patterns which failed to bind are wildcards, which would hide missing ones.
+        if holder.error_count() == errors {
+            self.check_match(&bound_arms, holder);
+        }
+
+        Some(BoundExpression::Match(
+            Box::new(bound_scrutinee),
+            bound_arms,
+            result_type,
+        ))
+    }
+
+    /// Warns about arms which can never be reached and reports values matched by no arm.
+    fn check_match(
+        &self,
+        arms: &[(BoundPattern, Option<BoundExpression>)],
+        holder: &mut DiagnosticHolder,
+    ) {
+        let mut rows: Vec<Vec<SimplePattern>> = vec![];
+
+        for (pattern, _) in arms {
+            let row = vec![self.simplify_pattern(pattern)];
+
+            if useful(&rows, &row).is_none() {
+                holder.warning(&format!("Unreachable match arm \"{:}\"", pattern));
+            }
+
+            rows.push(row);
+        }
+
+        if let Some(witness) = useful(&rows, &[SimplePattern::Wildcard]) {
+            holder.error(&format!(
+                "Non-exhaustive match, missing pattern \"{:}\"",
+                witness[0]
+            ));
+        }
+    }
+
+    fn simplify_pattern(&self, pattern: &BoundPattern) -> SimplePattern {
+        let literal = |name: &str, siblings: Option<Vec<(String, usize)>>| {
+            SimplePattern::Constructor(
+                Constructor {
+                    name: name.to_string(),
+                    arity: 0,
+                    kind: ConstructorKind::Literal,
+                    siblings,
+                },
+                vec![],
+            )
+        };
+
+        match pattern {
+            BoundPattern::Wildcard | BoundPattern::Variable(_) => SimplePattern::Wildcard,
+            BoundPattern::Number(number) => literal(number, None),
+            BoundPattern::Literal(string) => literal(&format!("{:?}", string), None),
+            BoundPattern::Bool(bool) => literal(
+                bool,
+                Some(vec![("true".to_string(), 0), ("false".to_string(), 0)]),
+            ),
+            BoundPattern::Constructor(name, arguments) => SimplePattern::Constructor(
+                Constructor {
+                    name: name.clone(),
+                    arity: arguments.len(),
+                    kind: ConstructorKind::Named,
+                    siblings: self
+                        .constructors
+                        .get(name)
+                        .and_then(|constructor| self.types.get(&constructor.type_name))
+                        .map(|definition| definition.constructors.clone()),
+                },
+                arguments
+                    .iter()
+                    .map(|argument| self.simplify_pattern(argument))
+                    .collect(),
+            ),
+            BoundPattern::Tuple(elements) => SimplePattern::Constructor(
+                Constructor {
+                    name: "(,)".to_string(),
+                    arity: elements.len(),
+                    kind: ConstructorKind::Tuple,
+                    siblings: Some(vec![("(,)".to_string(), elements.len())]),
+                },
+                elements
+                    .iter()
+                    .map(|element| self.simplify_pattern(element))
+                    .collect(),
+            ),
+            BoundPattern::List(elements) => SimplePattern::Constructor(
+                Constructor {
+                    name: format!("[{}]", elements.len()),
+                    arity: elements.len(),
+                    kind: ConstructorKind::List,
+                    siblings: None,
+                },
+                elements
+                    .iter()
+                    .map(|element| self.simplify_pattern(element))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Binds the variables of the arguments of a constructor pattern which could not be bound as
+    /// unidentified, so that the arm using them is still checked.
+    fn bind_unidentified_patterns(
+        &mut self,
+        arguments: Vec<Pattern>,
+        bindings: &mut HashMap<String, Binding>,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundPattern {
+        for argument in arguments {
+            self.bind_pattern(argument, &BoundType::Unidentified, bindings, holder);
+        }
+
+        BoundPattern::Wildcard
+    }
+
+    /// Binds a pattern, locating the diagnostics about it at its span.
+    fn bind_pattern(
+        &mut self,
+        pattern: Pattern,
+        expected: &BoundType,
+        bindings: &mut HashMap<String, Binding>,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundPattern {
+        holder.at(pattern.span(), |holder| {
+            self.bind_located_pattern(pattern, expected, bindings, holder)
+        })
+    }
+
+    fn bind_located_pattern(
+        &mut self,
+        pattern: Pattern,
+        expected: &BoundType,
+        bindings: &mut HashMap<String, Binding>,
+        holder: &mut DiagnosticHolder,
+    ) -> BoundPattern {
+        match pattern {
+            Pattern::Wildcard => BoundPattern::Wildcard,
+            Pattern::Identifier(token) if self.constructor_key(&token.literal).is_none() => {
+                if bindings.contains_key(&token.literal) {
+                    holder.error(&format!(
+                        "Duplicate pattern variable \"{:}\"",
+                        token.literal
+                    ));
+                }
+
+                self.refer_binding(&token, expected);
+                bindings.insert(
+                    token.literal.clone(),
+                    Binding {
+                        scheme: TypeScheme::monomorphic(expected.clone()),
+                        definition: self.located(&token),
+                    },
+                );
+
+                BoundPattern::Variable(token.literal)
+            }
+            Pattern::Identifier(token) => self.bind_pattern(
+                Pattern::Constructor(token, vec![]),
+                expected,
+                bindings,
+                holder,
+            ),
+            Pattern::Constructor(token, arguments) => {
+                let key = self.constructor_key(&token.literal);
+                let constructor = match key.as_ref().and_then(|key| self.constructors.get(key)) {
+                    Some(constructor) => constructor.clone(),
+                    None => {
+                        holder.error(&format!("Unknown constructor \"{:}\"", token.literal));
+                        return self.bind_unidentified_patterns(arguments, bindings, holder);
+                    }
+                };
+
+                if constructor.fields.len() != arguments.len() {
+                    holder.error(&format!(
+                        "Constructor \"{:}\" expects {:} fields, found {:}",
+                        token.literal,
+                        constructor.fields.len(),
+                        arguments.len()
+                    ));
+                    return self.bind_unidentified_patterns(arguments, bindings, holder);
+                }
+
+                let mapping = constructor
+                    .parameters
+                    .iter()
+                    .map(|parameter| (*parameter, self.fresh_variable()))
+                    .collect::<HashMap<usize, BoundType>>();
+
+                self.refer(
+                    &token,
+                    Some(constructor.scheme().body.substitute(&mapping)),
+                    constructor.definition.clone(),
+                );
+                self.unify_pattern(
+                    expected,
+                    &constructor.result_type().substitute(&mapping),
+                    holder,
+                );
+
+                let bound_arguments = arguments
+                    .into_iter()
+                    .zip(&constructor.fields)
+                    .map(|(argument, field)| {
+                        self.bind_pattern(argument, &field.substitute(&mapping), bindings, holder)
+                    })
+                    .collect();
+
+                BoundPattern::Constructor(key.unwrap_or_default(), bound_arguments)
+            }
+            Pattern::Number(token) => {
+                self.unify_pattern(expected, &BoundType::Number, holder);
+                BoundPattern::Number(token.literal)
+            }
+            Pattern::Literal(token) => {
+                self.unify_pattern(expected, &BoundType::String, holder);
+                BoundPattern::Literal(token.literal)
+            }
+            Pattern::Bool(token) => {
+                self.unify_pattern(expected, &BoundType::Bool, holder);
+                BoundPattern::Bool(token.literal)
+            }
+            Pattern::Tuple(elements) => {
+                let element_types = elements
+                    .iter()
+                    .map(|_| self.fresh_variable())
+                    .collect::<Vec<BoundType>>();
+
+                self.unify_pattern(expected, &BoundType::Tuple(element_types.clone()), holder);
+
+                BoundPattern::Tuple(
+                    elements
+                        .into_iter()
+                        .zip(&element_types)
+                        .map(|(element, element_type)| {
+                            self.bind_pattern(element, element_type, bindings, holder)
+                        })
+                        .collect(),
+                )
+            }
+            Pattern::List(elements) => {
+                let element_type = self.fresh_variable();
+
+                self.unify_pattern(expected, &BoundType::list(element_type.clone()), holder);
+
+                BoundPattern::List(
+                    elements
+                        .into_iter()
+                        .map(|element| self.bind_pattern(element, &element_type, bindings, holder))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    fn unify_pattern(
+        &mut self,
+        expected: &BoundType,
+        pattern_type: &BoundType,
+        holder: &mut DiagnosticHolder,
+    ) {
+        if !self.unify(expected, pattern_type) {
+            holder.error(&format!(
+                "Cannot match type \"{:}\" against pattern of type \"{:}\"",
+                self.resolve(expected),
+                self.resolve(pattern_type)
+            ))
+        }
+    }
+}
+
+/// Collects the names of every identifier used inside an expression.
+fn collect_identifiers(expression: &Option<Expression>, identifiers: &mut Vec<String>) {
+    if let Some(expression) = expression {
+        if let Expression::Identifier(token) = expression {
+            identifiers.push(token.literal.clone());
+        }
+
+        for child in expression.children() {
+            collect_identifiers(&child, identifiers);
+        }
+    }
+}
+
+/// Groups the nodes of a dependency graph into strongly connected components with Tarjan's
+/// algorithm, listing every component after the components it depends on.
+fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        edges: &'a [Vec<usize>],
+        index: usize,
+        indices: Vec<Option<usize>>,
+        low_links: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn connect(state: &mut State, node: usize) {
+        state.indices[node] = Some(state.index);
+        state.low_links[node] = state.index;
+        state.index += 1;
+        state.stack.push(node);
+        state.on_stack[node] = true;
+
+        for &next in &state.edges[node] {
+            match state.indices[next] {
+                None => {
+                    connect(state, next);
+                    state.low_links[node] = state.low_links[node].min(state.low_links[next]);
+                }
+                Some(index) if state.on_stack[next] => {
+                    state.low_links[node] = state.low_links[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.low_links[node]) == state.indices[node] {
+            let mut component = vec![];
+
+            while let Some(member) = state.stack.pop() {
+                state.on_stack[member] = false;
+                component.push(member);
+
+                if member == node {
+                    break;
+                }
+            }
+
+            component.sort_unstable();
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        edges,
+        index: 0,
+        indices: vec![None; edges.len()],
+        low_links: vec![0; edges.len()],
+        stack: vec![],
+        on_stack: vec![false; edges.len()],
+        components: vec![],
+    };
+
+    for node in 0..edges.len() {
+        if state.indices[node].is_none() {
+            connect(&mut state, node);
+        }
+    }
+
+    state.components
+}
+
+/// Collects the names which may stand for type variables, i.e. lowercase names without type
+/// arguments other than the primitive types.
+fn collect_type_variables(type_expression: &TypeExpression, names: &mut Vec<String>) {
+    match type_expression {
+        TypeExpression::Named(name, arguments) => {
+            let is_variable = arguments.is_empty()
+                && name
+                    .literal
+                    .starts_with(|char: char| char.is_ascii_lowercase())
+                && !matches!(name.literal.as_str(), "number" | "string" | "bool");
+
+            if is_variable && !names.contains(&name.literal) {
+                names.push(name.literal.clone());
+            }
+
+            for argument in arguments {
+                collect_type_variables(argument, names);
+            }
+        }
+        TypeExpression::Function(parameter, result) => {
+            collect_type_variables(parameter, names);
+            collect_type_variables(result, names);
+        }
+        TypeExpression::Tuple(elements) => {
+            for element in elements {
+                collect_type_variables(element, names);
+            }
+        }
+        TypeExpression::Record(fields) => {
+            for (_, field) in fields {
+                collect_type_variables(field, names);
+            }
+        }
+    }
+}
+
+/// Whether an expression is a literal zero, possibly negated or in parentheses.
+fn is_zero_literal(expression: &Option<BoundExpression>) -> bool {
+    match expression {
+        Some(BoundExpression::Number(number)) => number.parse::<f64>() == Ok(0.0),
+        Some(
+            BoundExpression::Parenthesis(inner)
+            | BoundExpression::Identity(inner)
+            | BoundExpression::Negation(inner),
+        ) => is_zero_literal(inner),
+        _ => false,
+    }
+}
+
+/// Type signatures of the built-in functions implemented in [`crate::runtime`].
+fn builtin_scheme(builtin: Builtin) -> TypeScheme {
+    use BoundType::{Bool, Number};
+
+    let a = || BoundType::Variable(0);
+    let b = || BoundType::Variable(1);
+    let c = || BoundType::Variable(2);
+    let list = BoundType::list;
+    let function = BoundType::function;
+    let option = |value| BoundType::Named("Option".to_string(), vec![value]);
+    let result = |value, error| BoundType::Named("Result".to_string(), vec![value, error]);
+
+    match builtin {
+        Builtin::Map => TypeScheme::new(
+            vec![0, 1],
+            function(function(a(), b()), function(list(a()), list(b()))),
+        ),
+        Builtin::Filter => TypeScheme::new(
+            vec![0],
+            function(function(a(), Bool), function(list(a()), list(a()))),
+        ),
+        Builtin::Fold => TypeScheme::new(
+            vec![0, 1],
+            function(
+                function(b(), function(a(), b())),
+                function(b(), function(list(a()), b())),
+            ),
+        ),
+        Builtin::Zip => TypeScheme::new(
+            vec![0, 1, 2],
+            function(
+                function(a(), function(b(), c())),
+                function(list(a()), function(list(b()), list(c()))),
+            ),
+        ),
+        Builtin::Range => TypeScheme::new(vec![], function(Number, function(Number, list(Number)))),
+        Builtin::Pow => TypeScheme::new(vec![], function(Number, function(Number, Number))),
+        Builtin::Length => TypeScheme::new(vec![0], function(list(a()), Number)),
+        Builtin::Concat => {
+            TypeScheme::new(vec![0], function(list(a()), function(list(a()), list(a()))))
+        }
+        Builtin::Reverse => TypeScheme::new(vec![0], function(list(a()), list(a()))),
+        Builtin::Sort => TypeScheme::new(vec![0], function(list(a()), list(a()))),
+        Builtin::Get => {
+            TypeScheme::new(vec![0], function(Number, function(list(a()), option(a()))))
+        }
+        Builtin::ParseNumber => TypeScheme::new(
+            vec![],
+            function(BoundType::String, result(Number, BoundType::String)),
+        ),
+        Builtin::Chars => {
+            TypeScheme::new(vec![], function(BoundType::String, list(BoundType::String)))
+        }
+        Builtin::Join => TypeScheme::new(
+            vec![],
+            function(
+                BoundType::String,
+                function(list(BoundType::String), BoundType::String),
+            ),
+        ),
+        Builtin::Split => TypeScheme::new(
+            vec![],
+            function(
+                BoundType::String,
+                function(BoundType::String, list(BoundType::String)),
+            ),
+        ),
+        Builtin::OptionMap => TypeScheme::new(
+            vec![0, 1],
+            function(function(a(), b()), function(option(a()), option(b()))),
+        ),
+        Builtin::OptionAndThen => TypeScheme::new(
+            vec![0, 1],
+            function(
+                function(a(), option(b())),
+                function(option(a()), option(b())),
+            ),
+        ),
+        Builtin::OptionUnwrapOr => {
+            TypeScheme::new(vec![0], function(a(), function(option(a()), a())))
+        }
+        Builtin::ResultMap => TypeScheme::new(
+            vec![0, 1, 2],
+            function(
+                function(a(), b()),
+                function(result(a(), c()), result(b(), c())),
+            ),
+        ),
+        Builtin::ResultAndThen => TypeScheme::new(
+            vec![0, 1, 2],
+            function(
+                function(a(), result(b(), c())),
+                function(result(a(), c()), result(b(), c())),
+            ),
+        ),
+        Builtin::ResultUnwrapOr => {
+            TypeScheme::new(vec![0, 2], function(a(), function(result(a(), c()), a())))
+        }
+    }
+}
+
+/// A type whose listed variables are instantiated freshly on every use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeScheme {
+    pub variables: Vec<usize>,
+    pub body: BoundType,
+}
+
+impl TypeScheme {
+    pub fn new(variables: Vec<usize>, body: BoundType) -> Self {
+        Self { variables, body }
+    }
+
+    pub fn monomorphic(body: BoundType) -> Self {
+        Self::new(vec![], body)
+    }
 }
 
-#[derive(Display, Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
 pub enum BoundType {
-    #[strum(serialize = "unidentified")]
     Unidentified,
-    #[strum(serialize = "string")]
     String,
-    #[strum(serialize = "bool")]
     Bool,
-    #[strum(serialize = "number")]
     Number,
+    List(Box<BoundType>),
+    Function(Box<BoundType>, Box<BoundType>),
+    Tuple(Vec<BoundType>),
+    /// A user-defined sum type applied to its type arguments.
+    Named(String, Vec<BoundType>),
+    /// A record with its fields and, when open, the row variable standing for further fields.
+    Record(BTreeMap<String, BoundType>, Option<usize>),
+    Variable(usize),
+}
+
+impl BoundType {
+    pub fn list(element: BoundType) -> Self {
+        BoundType::List(Box::new(element))
+    }
+
+    /// Collects the type and row variables occurring in this type.
+    pub fn free_variables(&self, variables: &mut BTreeSet<usize>) {
+        match self {
+            BoundType::Variable(variable) => {
+                variables.insert(*variable);
+            }
+            BoundType::List(element) => element.free_variables(variables),
+            BoundType::Function(parameter, result) => {
+                parameter.free_variables(variables);
+                result.free_variables(variables);
+            }
+            BoundType::Tuple(elements) | BoundType::Named(_, elements) => {
+                for element in elements {
+                    element.free_variables(variables);
+                }
+            }
+            BoundType::Record(fields, row) => {
+                for field in fields.values() {
+                    field.free_variables(variables);
+                }
+
+                variables.extend(row);
+            }
+            BoundType::Unidentified | BoundType::String | BoundType::Bool | BoundType::Number => {}
+        }
+    }
+
+    pub fn function(parameter: BoundType, result: BoundType) -> Self {
+        BoundType::Function(Box::new(parameter), Box::new(result))
+    }
+
+    fn substitute(&self, mapping: &HashMap<usize, BoundType>) -> BoundType {
+        match self {
+            BoundType::Variable(variable) => mapping
+                .get(variable)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            BoundType::List(element) => BoundType::list(element.substitute(mapping)),
+            BoundType::Function(parameter, result) => {
+                BoundType::function(parameter.substitute(mapping), result.substitute(mapping))
+            }
+            BoundType::Tuple(elements) => BoundType::Tuple(
+                elements
+                    .iter()
+                    .map(|element| element.substitute(mapping))
+                    .collect(),
+            ),
+            BoundType::Named(name, arguments) => BoundType::Named(
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| argument.substitute(mapping))
+                    .collect(),
+            ),
+            BoundType::Record(fields, row) => BoundType::Record(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), field.substitute(mapping)))
+                    .collect(),
+                row.map(|row| match mapping.get(&row) {
+                    Some(BoundType::Variable(substituted)) => *substituted,
+                    _ => row,
+                }),
+            ),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Display for BoundType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoundType::Unidentified => write!(f, "unidentified"),
+            BoundType::String => write!(f, "string"),
+            BoundType::Bool => write!(f, "bool"),
+            BoundType::Number => write!(f, "number"),
+            BoundType::List(element) => write!(f, "List<{}>", element),
+            BoundType::Function(parameter, result) => match parameter.as_ref() {
+                BoundType::Function(_, _) => write!(f, "({:}) -> {:}", parameter, result),
+                _ => write!(f, "{:} -> {:}", parameter, result),
+            },
+            BoundType::Tuple(elements) => write!(
+                f,
+                "({})",
+                elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            BoundType::Named(name, arguments) if arguments.is_empty() => write!(f, "{}", name),
+            BoundType::Named(name, arguments) => write!(
+                f,
+                "{}<{}>",
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            BoundType::Record(fields, row) => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(name, field)| format!("{}: {}", name, field))
+                    .collect::<Vec<String>>();
+
+                if row.is_some() {
+                    fields.push("..".to_string());
+                }
+
+                if fields.is_empty() {
+                    write!(f, "{{}}")
+                } else {
+                    write!(f, "{{ {} }}", fields.join(", "))
+                }
+            }
+            BoundType::Variable(variable) => {
+                let letter = (b'a' + (variable % 26) as u8) as char;
+
+                match variable / 26 {
+                    0 => write!(f, "'{:}", letter),
+                    suffix => write!(f, "'{:}{:}", letter, suffix),
+                }
+            }
+        }
+    }
 }
 
-trait TypeDestructable {
+pub(crate) trait TypeDestructable {
     fn get_type(&self) -> BoundType;
 }
 
@@ -486,9 +2908,10 @@ impl TypeDestructable for Option<BoundExpression> {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
 pub enum BoundExpression {
     Literal(String),
-    Identifier(String),
+    Identifier(String, BoundType),
     Bool(String),
     Number(String),
     Identity(Box<Option<BoundExpression>>),
@@ -508,13 +2931,119 @@ pub enum BoundExpression {
     Division(Box<Option<BoundExpression>>, Box<Option<BoundExpression>>),
     Remainder(Box<Option<BoundExpression>>, Box<Option<BoundExpression>>),
     Parenthesis(Box<Option<BoundExpression>>),
+    List(Vec<Option<BoundExpression>>, BoundType),
+    Index(
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+        BoundType,
+    ),
+    /// A single parameter lambda, whose body is shared with the closures created from it.
+    Lambda(String, Rc<Option<BoundExpression>>, BoundType),
+    Call(
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+        BoundType,
+    ),
+    Tuple(Vec<Option<BoundExpression>>, BoundType),
+    Record(Vec<(String, Option<BoundExpression>)>, BoundType),
+    RecordUpdate(
+        Box<Option<BoundExpression>>,
+        Vec<(String, Option<BoundExpression>)>,
+        BoundType,
+    ),
+    Field(Box<Option<BoundExpression>>, String, BoundType),
+    TupleField(Box<Option<BoundExpression>>, usize, BoundType),
+    /// A constructor of a sum type together with its number of fields.
+    Constructor(String, usize, BoundType),
+    Match(
+        Box<Option<BoundExpression>>,
+        Vec<(BoundPattern, Option<BoundExpression>)>,
+        BoundType,
+    ),
+    If(
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+    ),
+    Let(
+        String,
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+    ),
+    /// A binding visible inside its own value, which the binder guarantees to be a lambda.
+    LetRec(
+        String,
+        Box<Option<BoundExpression>>,
+        Box<Option<BoundExpression>>,
+    ),
+}
+
+/// A bound program, with definitions ordered so that each only depends on earlier ones or
+/// on definitions of its own recursive group.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub struct BoundProgram {
+    pub definitions: Vec<(String, Option<BoundExpression>)>,
+    pub entry: Option<BoundExpression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub enum BoundPattern {
+    Wildcard,
+    Variable(String),
+    Constructor(String, Vec<BoundPattern>),
+    Number(String),
+    Literal(String),
+    Bool(String),
+    Tuple(Vec<BoundPattern>),
+    List(Vec<BoundPattern>),
+}
+
+impl Display for BoundPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let join = |patterns: &[BoundPattern]| {
+            patterns
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        match self {
+            BoundPattern::Wildcard => write!(f, "_"),
+            BoundPattern::Variable(name) => write!(f, "{}", name),
+            BoundPattern::Constructor(name, arguments) if arguments.is_empty() => {
+                write!(f, "{}", name)
+            }
+            BoundPattern::Constructor(name, arguments) => {
+                write!(f, "{}", name)?;
+
+                for argument in arguments {
+                    match argument {
+                        BoundPattern::Constructor(_, nested) if !nested.is_empty() => {
+                            write!(f, " ({})", argument)?
+                        }
+                        _ => write!(f, " {}", argument)?,
+                    }
+                }
+
+                Ok(())
+            }
+            BoundPattern::Number(number) => write!(f, "{}", number),
+            BoundPattern::Literal(string) => write!(f, "{:?}", string),
+            BoundPattern::Bool(bool) => write!(f, "{}", bool),
+            BoundPattern::Tuple(elements) => write!(f, "({})", join(elements)),
+            BoundPattern::List(elements) => write!(f, "[{}]", join(elements)),
+        }
+    }
 }
 
 impl BoundExpression {
     pub fn get_type(&self) -> BoundType {
         match self {
             BoundExpression::Literal(_) => BoundType::String,
-            BoundExpression::Identifier(_) => BoundType::Unidentified,
+            BoundExpression::Identifier(_, bound_type) => bound_type.clone(),
             BoundExpression::Bool(_) => BoundType::Bool,
             BoundExpression::Number(_) => BoundType::Number,
             BoundExpression::Identity(expression) => expression.get_type(),
@@ -534,6 +3063,141 @@ impl BoundExpression {
             BoundExpression::Division(_, _) => BoundType::Number,
             BoundExpression::Remainder(_, _) => BoundType::Number,
             BoundExpression::Parenthesis(expression) => expression.get_type(),
+            BoundExpression::List(_, bound_type) => bound_type.clone(),
+            BoundExpression::Index(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Lambda(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Call(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Tuple(_, bound_type) => bound_type.clone(),
+            BoundExpression::Record(_, bound_type) => bound_type.clone(),
+            BoundExpression::RecordUpdate(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Field(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::TupleField(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Constructor(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::Match(_, _, bound_type) => bound_type.clone(),
+            BoundExpression::If(_, then_branch, _) => then_branch.get_type(),
+            BoundExpression::Let(_, _, body) => body.get_type(),
+            BoundExpression::LetRec(_, _, body) => body.get_type(),
+        }
+    }
+
+    /// The inferred type stored on this node, if the node carries one.
+    fn type_mut(&mut self) -> Option<&mut BoundType> {
+        match self {
+            BoundExpression::Identifier(_, bound_type)
+            | BoundExpression::List(_, bound_type)
+            | BoundExpression::Index(_, _, bound_type)
+            | BoundExpression::Lambda(_, _, bound_type)
+            | BoundExpression::Call(_, _, bound_type)
+            | BoundExpression::Tuple(_, bound_type)
+            | BoundExpression::Record(_, bound_type)
+            | BoundExpression::RecordUpdate(_, _, bound_type)
+            | BoundExpression::Field(_, _, bound_type)
+            | BoundExpression::TupleField(_, _, bound_type)
+            | BoundExpression::Constructor(_, _, bound_type)
+            | BoundExpression::Match(_, _, bound_type) => Some(bound_type),
+            _ => None,
+        }
+    }
+
+    pub fn children(&self) -> Vec<&Option<BoundExpression>> {
+        match self {
+            BoundExpression::Literal(_)
+            | BoundExpression::Identifier(_, _)
+            | BoundExpression::Bool(_)
+            | BoundExpression::Number(_)
+            | BoundExpression::Constructor(_, _, _) => vec![],
+            BoundExpression::Identity(expression)
+            | BoundExpression::Negation(expression)
+            | BoundExpression::LogicalNot(expression)
+            | BoundExpression::Parenthesis(expression)
+            | BoundExpression::Field(expression, _, _)
+            | BoundExpression::TupleField(expression, _, _) => vec![expression.as_ref()],
+            BoundExpression::LogicalOr(left, right)
+            | BoundExpression::LogicalAnd(left, right)
+            | BoundExpression::NotEqual(left, right)
+            | BoundExpression::Equal(left, right)
+            | BoundExpression::Greater(left, right)
+            | BoundExpression::GreaterEqual(left, right)
+            | BoundExpression::Less(left, right)
+            | BoundExpression::LessEqual(left, right)
+            | BoundExpression::Addition(left, right)
+            | BoundExpression::Subtraction(left, right)
+            | BoundExpression::Multiplication(left, right)
+            | BoundExpression::Division(left, right)
+            | BoundExpression::Remainder(left, right)
+            | BoundExpression::Index(left, right, _)
+            | BoundExpression::Call(left, right, _) => vec![left.as_ref(), right.as_ref()],
+            BoundExpression::Lambda(_, body, _) => vec![body.as_ref()],
+            BoundExpression::List(elements, _) | BoundExpression::Tuple(elements, _) => {
+                elements.iter().collect()
+            }
+            BoundExpression::Record(fields, _) => fields.iter().map(|(_, value)| value).collect(),
+            BoundExpression::RecordUpdate(record, fields, _) => std::iter::once(record.as_ref())
+                .chain(fields.iter().map(|(_, value)| value))
+                .collect(),
+            BoundExpression::Match(scrutinee, arms, _) => std::iter::once(scrutinee.as_ref())
+                .chain(arms.iter().map(|(_, body)| body))
+                .collect(),
+            BoundExpression::If(condition, then_branch, else_branch) => vec![
+                condition.as_ref(),
+                then_branch.as_ref(),
+                else_branch.as_ref(),
+            ],
+            BoundExpression::Let(_, value, body) | BoundExpression::LetRec(_, value, body) => {
+                vec![value.as_ref(), body.as_ref()]
+            }
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Option<BoundExpression>> {
+        match self {
+            BoundExpression::Literal(_)
+            | BoundExpression::Identifier(_, _)
+            | BoundExpression::Bool(_)
+            | BoundExpression::Number(_)
+            | BoundExpression::Constructor(_, _, _) => vec![],
+            BoundExpression::Identity(expression)
+            | BoundExpression::Negation(expression)
+            | BoundExpression::LogicalNot(expression)
+            | BoundExpression::Parenthesis(expression)
+            | BoundExpression::Field(expression, _, _)
+            | BoundExpression::TupleField(expression, _, _) => vec![expression.as_mut()],
+            BoundExpression::LogicalOr(left, right)
+            | BoundExpression::LogicalAnd(left, right)
+            | BoundExpression::NotEqual(left, right)
+            | BoundExpression::Equal(left, right)
+            | BoundExpression::Greater(left, right)
+            | BoundExpression::GreaterEqual(left, right)
+            | BoundExpression::Less(left, right)
+            | BoundExpression::LessEqual(left, right)
+            | BoundExpression::Addition(left, right)
+            | BoundExpression::Subtraction(left, right)
+            | BoundExpression::Multiplication(left, right)
+            | BoundExpression::Division(left, right)
+            | BoundExpression::Remainder(left, right)
+            | BoundExpression::Index(left, right, _)
+            | BoundExpression::Call(left, right, _) => vec![left.as_mut(), right.as_mut()],
+            BoundExpression::Lambda(_, body, _) => vec![Rc::make_mut(body)],
+            BoundExpression::List(elements, _) | BoundExpression::Tuple(elements, _) => {
+                elements.iter_mut().collect()
+            }
+            BoundExpression::Record(fields, _) => {
+                fields.iter_mut().map(|(_, value)| value).collect()
+            }
+            BoundExpression::RecordUpdate(record, fields, _) => std::iter::once(record.as_mut())
+                .chain(fields.iter_mut().map(|(_, value)| value))
+                .collect(),
+            BoundExpression::Match(scrutinee, arms, _) => std::iter::once(scrutinee.as_mut())
+                .chain(arms.iter_mut().map(|(_, body)| body))
+                .collect(),
+            BoundExpression::If(condition, then_branch, else_branch) => vec![
+                condition.as_mut(),
+                then_branch.as_mut(),
+                else_branch.as_mut(),
+            ],
+            BoundExpression::Let(_, value, body) | BoundExpression::LetRec(_, value, body) => {
+                vec![value.as_mut(), body.as_mut()]
+            }
         }
     }
 }
@@ -0,0 +1,684 @@
+use crate::binder::{BoundExpression, BoundPattern, BoundProgram};
+use crate::runtime::Value;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// A single operation of the stack machine in [`crate::vm`]. Operands index into the tables of
+/// the [`CompiledFunction`] holding the instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Pushes a constant.
+    Constant(usize),
+    /// Pushes the local variable in a slot of the current frame.
+    Local(usize),
+    /// Pushes a value captured by the closure being run.
+    Capture(usize),
+    /// Pushes the closure being run, which a `let rec` binding refers to itself by.
+    Current,
+    /// Pushes a top-level definition, host item or built-in function by name.
+    Global(usize),
+    /// Pushes a constructor by name with its number of fields.
+    Constructor(usize, usize),
+    Negate,
+    Not,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    /// Collects the given number of values into a list.
+    List(usize),
+    /// Collects the given number of values into a tuple.
+    Tuple(usize),
+    /// Collects one value per field name into a record.
+    Record(Vec<usize>),
+    /// Replaces fields of the record below the new values.
+    RecordUpdate(Vec<usize>),
+    Field(usize),
+    TupleField(usize),
+    Index,
+    /// Creates a closure of a nested function, capturing values of the current frame.
+    Closure(usize, Vec<Capture>),
+    Call,
+    /// Calls a function in place of the current frame.
+    TailCall,
+    Return,
+    Jump(usize),
+    JumpIfFalse(usize),
+    /// Pops a value and pushes the variables bound by a pattern, or jumps if it does not match.
+    Match(usize, usize),
+    /// Fails as no match arm matches the value in a slot.
+    NoMatch(usize),
+    /// Keeps the top of the stack while dropping the given number of values below it.
+    Slide(usize),
+    /// Fails for an expression the binder could not bind.
+    Missing,
+}
+
+/// Where a closure takes a captured value from when it is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(usize),
+    Capture(usize),
+    Current,
+}
+
+/// The code of a lambda, or of a top-level definition or entry expression without parameter.
+/// The parameter of a lambda is in the first slot of its frame.
+#[derive(Debug, Default)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub names: Vec<String>,
+    pub patterns: Vec<BoundPattern>,
+    pub functions: Vec<Rc<CompiledFunction>>,
+}
+
+/// A compiled program, with definitions in the order they are evaluated.
+#[derive(Debug, Default)]
+pub struct CompiledProgram {
+    pub definitions: Vec<(String, Rc<CompiledFunction>)>,
+    pub entry: Option<Rc<CompiledFunction>>,
+}
+
+/// A function being compiled, with the names of its local variables by slot.
+struct Scope {
+    function: CompiledFunction,
+    locals: Vec<(String, usize)>,
+    captures: Vec<(String, Capture)>,
+    /// The name of a `let rec` binding whose lambda this is.
+    recursive_name: Option<String>,
+    /// Number of values on the stack of the frame at the current instruction.
+    height: usize,
+}
+
+/// Lowers bound trees to bytecode for [`crate::vm::Vm`].
+#[derive(Default)]
+pub struct Compiler {
+    scopes: Vec<Scope>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compile_program(&mut self, program: &BoundProgram) -> CompiledProgram {
+        CompiledProgram {
+            definitions: program
+                .definitions
+                .iter()
+                .map(|(name, value)| {
+                    let (function, _) = self.compile_function(name, None, None, value);
+                    (name.clone(), function)
+                })
+                .collect(),
+            entry: program.entry.as_ref().map(|entry| {
+                let (function, _) =
+                    self.compile_function("<entry>", None, None, &Some(entry.clone()));
+                function
+            }),
+        }
+    }
+
+    fn compile_function(
+        &mut self,
+        name: &str,
+        parameter: Option<&str>,
+        recursive_name: Option<&str>,
+        body: &Option<BoundExpression>,
+    ) -> (Rc<CompiledFunction>, Vec<Capture>) {
+        self.scopes.push(Scope {
+            function: CompiledFunction {
+                name: name.to_string(),
+                ..CompiledFunction::default()
+            },
+            locals: parameter
+                .map(|parameter| vec![(parameter.to_string(), 0)])
+                .unwrap_or_default(),
+            captures: vec![],
+            recursive_name: recursive_name.map(str::to_string),
+            height: usize::from(parameter.is_some()),
+        });
+
+        self.compile(body, true);
+        self.emit(Instruction::Return);
+
+        let scope = self
+            .scopes
+            .pop()
+            .expect("Compilation error: no function is being compiled");
+
+        (
+            Rc::new(scope.function),
+            scope
+                .captures
+                .into_iter()
+                .map(|(_, capture)| capture)
+                .collect(),
+        )
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("Compilation error: no function is being compiled")
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let scope = self.scope();
+        let effect = match &instruction {
+            Instruction::Constant(_)
+            | Instruction::Local(_)
+            | Instruction::Capture(_)
+            | Instruction::Current
+            | Instruction::Global(_)
+            | Instruction::Constructor(_, _)
+            | Instruction::Closure(_, _)
+            | Instruction::Missing => 1,
+            Instruction::Negate
+            | Instruction::Not
+            | Instruction::Field(_)
+            | Instruction::TupleField(_)
+            | Instruction::Jump(_)
+            | Instruction::NoMatch(_)
+            | Instruction::Match(_, _) => 0,
+            Instruction::List(length) | Instruction::Tuple(length) => 1 - *length as isize,
+            Instruction::Record(names) => 1 - names.len() as isize,
+            Instruction::RecordUpdate(names) => -(names.len() as isize),
+            Instruction::Slide(count) => -(*count as isize),
+            _ => -1,
+        };
+
+        scope.height = scope.height.saturating_add_signed(effect);
+        scope.function.code.push(instruction);
+        scope.function.code.len() - 1
+    }
+
+    /// Points the jump at `position` to the next instruction.
+    fn patch(&mut self, position: usize) {
+        let code = &mut self.scope().function.code;
+        let target = code.len();
+
+        match &mut code[position] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) | Instruction::Match(_, to) => {
+                *to = target
+            }
+            instruction => unreachable!("Compilation error: cannot patch {:?}", instruction),
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        let constants = &mut self.scope().function.constants;
+        constants.push(value);
+        constants.len() - 1
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        let names = &mut self.scope().function.names;
+
+        match names.iter().position(|other| other == name) {
+            Some(index) => index,
+            None => {
+                names.push(name.to_string());
+                names.len() - 1
+            }
+        }
+    }
+
+    /// Finds a variable in the function at `depth`, capturing it from enclosing functions if
+    /// needed, or returns `None` for a global name.
+    fn resolve(&mut self, depth: usize, name: &str) -> Option<Capture> {
+        let scope = &self.scopes[depth];
+
+        if let Some((_, slot)) = scope.locals.iter().rev().find(|(local, _)| local == name) {
+            return Some(Capture::Local(*slot));
+        }
+
+        if scope.recursive_name.as_deref() == Some(name) {
+            return Some(Capture::Current);
+        }
+
+        if let Some(index) = scope
+            .captures
+            .iter()
+            .position(|(capture, _)| capture == name)
+        {
+            return Some(Capture::Capture(index));
+        }
+
+        let outer = self.resolve(depth.checked_sub(1)?, name)?;
+        let captures = &mut self.scopes[depth].captures;
+        captures.push((name.to_string(), outer));
+
+        Some(Capture::Capture(captures.len() - 1))
+    }
+
+    fn bind_local(&mut self, name: &str) {
+        let scope = self.scope();
+        let slot = scope.height - 1;
+        scope.locals.push((name.to_string(), slot));
+    }
+
+    fn compile(&mut self, expression: &Option<BoundExpression>, tail: bool) {
+        let Some(expression) = expression else {
+            self.emit(Instruction::Missing);
+            return;
+        };
+
+        match expression {
+            BoundExpression::Literal(string) => {
+                let constant = self.constant(Value::String(string.clone()));
+                self.emit(Instruction::Constant(constant));
+            }
+            BoundExpression::Bool(string) => {
+                let constant = self.constant(Value::Bool(string.parse::<bool>().unwrap()));
+                self.emit(Instruction::Constant(constant));
+            }
+            BoundExpression::Number(string) => {
+                let constant = self.constant(Value::Number(string.parse::<f64>().unwrap()));
+                self.emit(Instruction::Constant(constant));
+            }
+            BoundExpression::Identifier(name, _) => {
+                let depth = self.scopes.len() - 1;
+
+                match self.resolve(depth, name) {
+                    Some(Capture::Local(slot)) => self.emit(Instruction::Local(slot)),
+                    Some(Capture::Capture(index)) => self.emit(Instruction::Capture(index)),
+                    Some(Capture::Current) => self.emit(Instruction::Current),
+                    None => {
+                        let name = self.name(name);
+                        self.emit(Instruction::Global(name))
+                    }
+                };
+            }
+            BoundExpression::Constructor(name, arity, _) => {
+                let name = self.name(name);
+                self.emit(Instruction::Constructor(name, *arity));
+            }
+            BoundExpression::Identity(expression) => self.compile(expression, false),
+            BoundExpression::Parenthesis(expression) => self.compile(expression, tail),
+            BoundExpression::Negation(expression) => {
+                self.compile(expression, false);
+                self.emit(Instruction::Negate);
+            }
+            BoundExpression::LogicalNot(expression) => {
+                self.compile(expression, false);
+                self.emit(Instruction::Not);
+            }
+            BoundExpression::LogicalOr(left, right) => self.short_circuit(left, right, true, tail),
+            BoundExpression::LogicalAnd(left, right) => {
+                self.short_circuit(left, right, false, tail)
+            }
+            BoundExpression::Equal(left, right) => self.binary(left, right, Instruction::Equal),
+            BoundExpression::NotEqual(left, right) => {
+                self.binary(left, right, Instruction::NotEqual)
+            }
+            BoundExpression::Greater(left, right) => self.binary(left, right, Instruction::Greater),
+            BoundExpression::GreaterEqual(left, right) => {
+                self.binary(left, right, Instruction::GreaterEqual)
+            }
+            BoundExpression::Less(left, right) => self.binary(left, right, Instruction::Less),
+            BoundExpression::LessEqual(left, right) => {
+                self.binary(left, right, Instruction::LessEqual)
+            }
+            BoundExpression::Addition(left, right) => self.binary(left, right, Instruction::Add),
+            BoundExpression::Subtraction(left, right) => {
+                self.binary(left, right, Instruction::Subtract)
+            }
+            BoundExpression::Multiplication(left, right) => {
+                self.binary(left, right, Instruction::Multiply)
+            }
+            BoundExpression::Division(left, right) => self.binary(left, right, Instruction::Divide),
+            BoundExpression::Remainder(left, right) => {
+                self.binary(left, right, Instruction::Remainder)
+            }
+            BoundExpression::Index(list, index, _) => self.binary(list, index, Instruction::Index),
+            BoundExpression::List(elements, _) => {
+                for element in elements {
+                    self.compile(element, false);
+                }
+
+                self.emit(Instruction::List(elements.len()));
+            }
+            BoundExpression::Tuple(elements, _) => {
+                for element in elements {
+                    self.compile(element, false);
+                }
+
+                self.emit(Instruction::Tuple(elements.len()));
+            }
+            BoundExpression::Record(fields, _) => {
+                let names = self.fields(fields);
+                self.emit(Instruction::Record(names));
+            }
+            BoundExpression::RecordUpdate(record, fields, _) => {
+                self.compile(record, false);
+                let names = self.fields(fields);
+                self.emit(Instruction::RecordUpdate(names));
+            }
+            BoundExpression::Field(record, field, _) => {
+                self.compile(record, false);
+                let name = self.name(field);
+                self.emit(Instruction::Field(name));
+            }
+            BoundExpression::TupleField(tuple, position, _) => {
+                self.compile(tuple, false);
+                self.emit(Instruction::TupleField(*position));
+            }
+            BoundExpression::Lambda(parameter, body, _) => {
+                self.closure(&format!("fn {}", parameter), parameter, None, body)
+            }
+            BoundExpression::Call(callee, argument, _) => {
+                self.compile(callee, false);
+                self.compile(argument, false);
+                self.emit(if tail {
+                    Instruction::TailCall
+                } else {
+                    Instruction::Call
+                });
+            }
+            BoundExpression::If(condition, then_branch, else_branch) => {
+                self.compile(condition, false);
+                let else_jump = self.emit(Instruction::JumpIfFalse(0));
+                let height = self.scope().height;
+
+                self.compile(then_branch, tail);
+                let end_jump = self.emit(Instruction::Jump(0));
+
+                self.scope().height = height;
+                self.patch(else_jump);
+                self.compile(else_branch, tail);
+                self.patch(end_jump);
+            }
+            BoundExpression::Let(name, value, body) => {
+                self.compile(value, false);
+                self.bind_local(name);
+                self.compile(body, tail);
+                self.scope().locals.pop();
+                self.emit(Instruction::Slide(1));
+            }
+            BoundExpression::LetRec(name, value, body) => {
+                match value.as_ref() {
+                    Some(BoundExpression::Lambda(parameter, lambda_body, _)) => {
+                        self.closure(name, parameter, Some(name), lambda_body)
+                    }
+                    _ => {
+                        self.emit(Instruction::Missing);
+                    }
+                }
+
+                self.bind_local(name);
+                self.compile(body, tail);
+                self.scope().locals.pop();
+                self.emit(Instruction::Slide(1));
+            }
+            BoundExpression::Match(scrutinee, arms, _) => {
+                self.compile(scrutinee, false);
+                let slot = self.scope().height - 1;
+                let mut end_jumps = vec![];
+
+                for (pattern, body) in arms {
+                    self.emit(Instruction::Local(slot));
+
+                    let patterns = &mut self.scope().function.patterns;
+                    patterns.push(pattern.clone());
+                    let pattern_index = patterns.len() - 1;
+
+                    let height = self.scope().height - 1;
+                    let next_arm = self.emit(Instruction::Match(pattern_index, 0));
+                    let mut variables = vec![];
+                    collect_pattern_variables(pattern, &mut variables);
+
+                    self.scope().height = height;
+
+                    for variable in &variables {
+                        self.scope().height += 1;
+                        self.bind_local(variable);
+                    }
+
+                    self.compile(body, tail);
+
+                    let scope = self.scope();
+                    scope.locals.truncate(scope.locals.len() - variables.len());
+                    self.emit(Instruction::Slide(variables.len() + 1));
+                    end_jumps.push(self.emit(Instruction::Jump(0)));
+
+                    self.scope().height = height;
+                    self.patch(next_arm);
+                }
+
+                self.emit(Instruction::NoMatch(slot));
+
+                for end_jump in end_jumps {
+                    self.patch(end_jump);
+                }
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        instruction: Instruction,
+    ) {
+        self.compile(left, false);
+        self.compile(right, false);
+        self.emit(instruction);
+    }
+
+    /// Compiles `left || right`, or `left && right` unless `or`, with conditional jumps so that
+    /// `right` is only evaluated if `left` does not decide the result, in tail position if the
+    /// whole expression is.
+    fn short_circuit(
+        &mut self,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        or: bool,
+        tail: bool,
+    ) {
+        let decided = self.constant(Value::Bool(or));
+
+        self.compile(left, false);
+        let else_jump = self.emit(Instruction::JumpIfFalse(0));
+        let height = self.scope().height;
+
+        if or {
+            self.emit(Instruction::Constant(decided));
+        } else {
+            self.compile(right, tail);
+        }
+
+        let end_jump = self.emit(Instruction::Jump(0));
+
+        self.scope().height = height;
+        self.patch(else_jump);
+
+        if or {
+            self.compile(right, tail);
+        } else {
+            self.emit(Instruction::Constant(decided));
+        }
+
+        self.patch(end_jump);
+    }
+
+    fn fields(&mut self, fields: &[(String, Option<BoundExpression>)]) -> Vec<usize> {
+        fields
+            .iter()
+            .map(|(name, value)| {
+                self.compile(value, false);
+                self.name(name)
+            })
+            .collect()
+    }
+
+    fn closure(
+        &mut self,
+        name: &str,
+        parameter: &str,
+        recursive_name: Option<&str>,
+        body: &Option<BoundExpression>,
+    ) {
+        let (function, captures) =
+            self.compile_function(name, Some(parameter), recursive_name, body);
+        let functions = &mut self.scope().function.functions;
+        functions.push(function);
+        let index = functions.len() - 1;
+
+        self.emit(Instruction::Closure(index, captures));
+    }
+}
+
+/// Collects the variables a pattern binds, in the order the virtual machine pushes them.
+pub fn collect_pattern_variables(pattern: &BoundPattern, variables: &mut Vec<String>) {
+    match pattern {
+        BoundPattern::Variable(name) => variables.push(name.clone()),
+        BoundPattern::Constructor(_, patterns)
+        | BoundPattern::Tuple(patterns)
+        | BoundPattern::List(patterns) => {
+            for pattern in patterns {
+                collect_pattern_variables(pattern, variables);
+            }
+        }
+        BoundPattern::Wildcard
+        | BoundPattern::Number(_)
+        | BoundPattern::Literal(_)
+        | BoundPattern::Bool(_) => {}
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indices = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        match self {
+            Instruction::Constant(index) => write!(f, "CONSTANT {}", index),
+            Instruction::Local(slot) => write!(f, "LOCAL {}", slot),
+            Instruction::Capture(index) => write!(f, "CAPTURE {}", index),
+            Instruction::Current => write!(f, "CURRENT"),
+            Instruction::Global(name) => write!(f, "GLOBAL {}", name),
+            Instruction::Constructor(name, arity) => write!(f, "CONSTRUCTOR {} {}", name, arity),
+            Instruction::Negate => write!(f, "NEGATE"),
+            Instruction::Not => write!(f, "NOT"),
+            Instruction::Equal => write!(f, "EQUAL"),
+            Instruction::NotEqual => write!(f, "NOT_EQUAL"),
+            Instruction::Greater => write!(f, "GREATER"),
+            Instruction::GreaterEqual => write!(f, "GREATER_EQUAL"),
+            Instruction::Less => write!(f, "LESS"),
+            Instruction::LessEqual => write!(f, "LESS_EQUAL"),
+            Instruction::Add => write!(f, "ADD"),
+            Instruction::Subtract => write!(f, "SUBTRACT"),
+            Instruction::Multiply => write!(f, "MULTIPLY"),
+            Instruction::Divide => write!(f, "DIVIDE"),
+            Instruction::Remainder => write!(f, "REMAINDER"),
+            Instruction::List(length) => write!(f, "LIST {}", length),
+            Instruction::Tuple(length) => write!(f, "TUPLE {}", length),
+            Instruction::Record(names) => write!(f, "RECORD {}", indices(names)),
+            Instruction::RecordUpdate(names) => write!(f, "RECORD_UPDATE {}", indices(names)),
+            Instruction::Field(name) => write!(f, "FIELD {}", name),
+            Instruction::TupleField(position) => write!(f, "TUPLE_FIELD {}", position),
+            Instruction::Index => write!(f, "INDEX"),
+            Instruction::Closure(function, captures) => {
+                write!(f, "CLOSURE {}", function)?;
+
+                for capture in captures {
+                    match capture {
+                        Capture::Local(slot) => write!(f, " local {}", slot)?,
+                        Capture::Capture(index) => write!(f, " capture {}", index)?,
+                        Capture::Current => write!(f, " current")?,
+                    }
+                }
+
+                Ok(())
+            }
+            Instruction::Call => write!(f, "CALL"),
+            Instruction::TailCall => write!(f, "TAIL_CALL"),
+            Instruction::Return => write!(f, "RETURN"),
+            Instruction::Jump(target) => write!(f, "JUMP {}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "JUMP_IF_FALSE {}", target),
+            Instruction::Match(pattern, target) => write!(f, "MATCH {} {}", pattern, target),
+            Instruction::NoMatch(slot) => write!(f, "NO_MATCH {}", slot),
+            Instruction::Slide(count) => write!(f, "SLIDE {}", count),
+            Instruction::Missing => write!(f, "MISSING"),
+        }
+    }
+}
+
+/// Lists the instructions of a function and then of its nested functions, with the constant,
+/// name or pattern an operand refers to as a comment.
+impl Display for CompiledFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "== {} ==", self.name)?;
+
+        for (offset, instruction) in self.code.iter().enumerate() {
+            write!(f, "{:04} {}", offset, instruction)?;
+
+            match instruction {
+                Instruction::Constant(index) => {
+                    write!(f, "    ; ")?;
+                    self.constants[*index].fmt_nested(f)?;
+                }
+                Instruction::Global(name)
+                | Instruction::Constructor(name, _)
+                | Instruction::Field(name) => write!(f, "    ; {}", self.names[*name])?,
+                Instruction::Record(names) | Instruction::RecordUpdate(names) => write!(
+                    f,
+                    "    ; {}",
+                    names
+                        .iter()
+                        .map(|name| self.names[*name].as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )?,
+                Instruction::Closure(function, _) => {
+                    write!(f, "    ; {}", self.functions[*function].name)?
+                }
+                Instruction::Match(pattern, _) => write!(f, "    ; {}", self.patterns[*pattern])?,
+                _ => {}
+            }
+
+            writeln!(f)?;
+        }
+
+        for function in &self.functions {
+            write!(f, "\n{}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for CompiledProgram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let functions = self
+            .definitions
+            .iter()
+            .map(|(_, function)| function)
+            .chain(&self.entry);
+
+        for (i, function) in functions.enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{}", function)?;
+        }
+
+        Ok(())
+    }
+}
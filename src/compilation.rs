@@ -1,14 +1,46 @@
-use crate::binder::{Binder, BoundExpression};
-use crate::runtime::Result;
+use crate::binder::{Binder, BoundExpression, BoundProgram, Reference};
+use crate::bytecode::Compiler;
+use crate::host::HostEnvironment;
+use crate::module::{
+    read_source, ChainResolver, FileResolver, ModuleResolver, PRELUDE, STANDARD_LIBRARY,
+};
+use crate::optimizer::{optimize, optimize_program};
+use crate::runtime::{ArithmeticPolicy, Limits, Result, RuntimeError};
 use crate::{
+    cst,
     diagnostic::DiagnosticHolder,
-    lexer::Lexer,
-    parser::{Parser, Tree},
+    formatter::{format_source, Spelling},
+    lexer::{Lexer, Token, KEYWORDS},
+    parser::{Parser, Program, Tree},
     runtime::Evaluator,
+    vm::Vm,
+    wasm::WasmCompiler,
 };
+use std::path::Path;
+
+/// How a compilation runs bound programs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    /// Lowers to bytecode run by [`Vm`].
+    #[default]
+    Bytecode,
+    /// Walks the bound tree with [`Evaluator`], kept as the reference implementation.
+    TreeWalker,
+}
 
 pub struct Compilation {
     source: String,
+    root_module: String,
+    resolver: Box<dyn ModuleResolver>,
+    prelude: bool,
+    optimize: bool,
+    host: HostEnvironment,
+    /// Command-line arguments given to the program as `args`, see [`Compilation::with_arguments`].
+    arguments: Option<Vec<String>>,
+    limits: Limits,
+    arithmetic: ArithmeticPolicy,
+    backend: Backend,
+    runtime_error: Option<RuntimeError>,
     pub holder: DiagnosticHolder,
 }
 
@@ -16,41 +48,476 @@ impl Compilation {
     pub fn new(source: String) -> Self {
         Self {
             source,
+            root_module: "main".to_string(),
+            resolver: Box::new(STANDARD_LIBRARY),
+            prelude: true,
+            optimize: true,
+            host: HostEnvironment::new(),
+            arguments: None,
+            limits: Limits::default(),
+            arithmetic: ArithmeticPolicy::default(),
+            backend: Backend::default(),
+            runtime_error: None,
             holder: DiagnosticHolder::new(),
         }
     }
 
+    /// Compiles the module `root_module` and the modules it imports, all found by `resolver`.
+    pub fn with_resolver(root_module: &str, resolver: Box<dyn ModuleResolver>) -> Self {
+        let mut holder = DiagnosticHolder::new();
+        let source = resolver.resolve(root_module).unwrap_or_else(|| {
+            holder.error(&format!("Cannot find module \"{:}\"", root_module));
+            String::new()
+        });
+
+        Self {
+            root_module: root_module.to_string(),
+            resolver,
+            holder,
+            ..Self::new(source)
+        }
+    }
+
+    /// Compiles a source file, resolving its imports relative to the file's directory and then
+    /// from the standard library.
+    pub fn from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        Self::from_path_in(path.parent().unwrap_or_else(|| Path::new(".")), path)
+    }
+
+    /// Compiles a source file under `root`, naming its module after its path relative to
+    /// `root` and resolving its imports from `root` and then from the standard library.
+    pub fn from_path_in(root: impl AsRef<Path>, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let (root, path) = (root.as_ref(), path.as_ref());
+        let source = read_source(path)?;
+        let root_module = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("::");
+
+        Ok(Self {
+            root_module,
+            resolver: Box::new(ChainResolver::new(vec![
+                Box::new(FileResolver::new(root)),
+                Box::new(STANDARD_LIBRARY),
+            ])),
+            ..Self::new(source)
+        })
+    }
+
+    /// Compiles without the prelude, so that only the built-in functions and the program's own
+    /// imports are available, e.g. for sandboxes and tests.
+    pub fn without_prelude(mut self) -> Self {
+        self.prelude = false;
+        self
+    }
+
+    /// Runs bound trees as written, skipping the constant folding of [`optimize`], e.g. to
+    /// compare against optimised evaluation.
+    pub fn without_optimization(mut self) -> Self {
+        self.optimize = false;
+        self
+    }
+
+    /// Makes the functions and constants of `host` available to every module.
+    pub fn with_host(mut self, host: HostEnvironment) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// Makes `arguments` available to the program as `args`, a list of strings, e.g. the
+    /// command-line arguments following the source file.
+    pub fn with_arguments(mut self, arguments: Vec<String>) -> Self {
+        self.arguments = Some(arguments);
+        self
+    }
+
+    /// Evaluates within the given budgets, see [`Limits`].
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Evaluates division by zero and NaN results as given by `arithmetic`, see
+    /// [`ArithmeticPolicy`].
+    pub fn with_arithmetic(mut self, arithmetic: ArithmeticPolicy) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// The error which stopped the last evaluation, e.g. to tell an exhausted budget apart from
+    /// other failures.
+    pub fn runtime_error(&self) -> Option<&RuntimeError> {
+        self.runtime_error.as_ref()
+    }
+
+    /// Compiles the source as a program and runs its entry expression.
     pub fn eval(&mut self) -> Box<dyn Result> {
-        let tree = self.lex_parse();
-        let binder = Binder::new();
-        let bound_expression = binder.bind_expression(tree.root_expression, &mut self.holder);
-        let evaluator = Evaluator::new(bound_expression.unwrap());
+        let program = self.lex_parse_program();
+        let bound_program = self.bind_program(program);
+
+        self.eval_program(bound_program)
+    }
+
+    /// Lexes, parses and binds the source as a program without running it, returning whether
+    /// it has no errors.
+    pub fn check(&mut self) -> bool {
+        let program = self.lex_parse_program();
+        self.bind_program(program);
+
+        self.holder.success()
+    }
+
+    /// Binds the source as a program and returns its entry expression, whose type is resolved.
+    pub fn bind_entry(&mut self) -> Option<BoundExpression> {
+        let program = self.lex_parse_program();
+
+        self.bind_program(program).entry
+    }
+
+    /// Binds a program together with every module it imports.
+    pub fn bind_program(&mut self, program: Program) -> BoundProgram {
+        self.bind_with_binder(program).1
+    }
+
+    /// Binds the source as a program and lists the names its entry expression can use without
+    /// qualification, see [`Binder::names`], together with its own definitions.
+    pub fn names(&mut self) -> Vec<String> {
+        let program = self.lex_parse_program();
+        let (binder, bound_program) = self.bind_with_binder(program);
+        let mut names = binder.names();
+
+        names.extend(
+            bound_program
+                .definitions
+                .into_iter()
+                .map(|(name, _)| name)
+                .filter(|name| !name.contains("::")),
+        );
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Binds the source as a program and lists where its names are bound and used, see
+    /// [`Binder::references`].
+    pub fn references(&mut self) -> Vec<Reference> {
+        let program = self.lex_parse_program();
+
+        self.bind_with_binder(program).0.references()
+    }
+
+    /// Names to complete in the source: the keywords together with [`Compilation::names`], in
+    /// order.
+    pub fn completions(&mut self) -> Vec<String> {
+        with_keywords(self.names())
+    }
+
+    /// Creates a binder for the inputs of a REPL session, see [`Compilation::bind_input`].
+    pub fn session_binder(&mut self) -> Binder {
+        let mut binder = self.binder();
+
+        if self.prelude {
+            binder.set_prelude(PRELUDE);
+        }
+
+        binder
+    }
+
+    /// Binds the source as the next input of a REPL session, seeing the inputs `binder` bound
+    /// before, see [`Binder::bind_modules`]. Only the modules it has not bound yet, e.g. the
+    /// prelude for the first input, are loaded, and their definitions come first in the
+    /// returned program.
+    pub fn bind_input(&mut self, binder: &mut Binder) -> BoundProgram {
+        let program = self.lex_parse_program();
+        let modules = self.load_unbound_modules(&program, &|module| binder.is_bound(module));
+
+        if !self.holder.success() {
+            return BoundProgram::default();
+        }
+
+        let mut bound_program = binder.bind_modules(modules, program, &mut self.holder);
+
+        if self.optimize && self.holder.success() {
+            optimize_program(&mut bound_program, &mut self.holder);
+        }
+
+        bound_program
+    }
+
+    fn bind_with_binder(&mut self, program: Program) -> (Binder, BoundProgram) {
+        let modules = self.load_modules(&program);
+        let mut binder = self.binder();
+
+        if !self.holder.success() {
+            return (binder, BoundProgram::default());
+        }
+
+        if self.prelude {
+            binder.set_prelude(PRELUDE);
+        }
+
+        let mut bound_program = binder.bind_modules(modules, program, &mut self.holder);
+
+        if self.optimize && self.holder.success() {
+            optimize_program(&mut bound_program, &mut self.holder);
+        }
 
-        evaluator.eval(&self.holder)
+        (binder, bound_program)
+    }
+
+    /// Parses every module imported directly or indirectly by `program`, each one listed after
+    /// the modules it imports.
+    pub fn load_modules(&mut self, program: &Program) -> Vec<(String, Program)> {
+        self.load_unbound_modules(program, &|_| false)
+    }
+
+    /// Like [`Compilation::load_modules`], leaving out the modules which are `bound` already
+    /// together with their own imports.
+    fn load_unbound_modules(
+        &mut self,
+        program: &Program,
+        bound: &dyn Fn(&str) -> bool,
+    ) -> Vec<(String, Program)> {
+        let mut loaded = vec![];
+        let mut importers = vec![self.root_module.clone()];
+
+        // The prelude and the modules it imports come from the standard library, whatever the
+        // resolver.
+        if self.prelude {
+            load_module(
+                &STANDARD_LIBRARY,
+                &mut self.holder,
+                PRELUDE,
+                &mut vec![],
+                &mut loaded,
+                bound,
+            );
+        }
+
+        for import in program.imports() {
+            load_module(
+                self.resolver.as_ref(),
+                &mut self.holder,
+                &import,
+                &mut importers,
+                &mut loaded,
+                bound,
+            );
+        }
+
+        loaded
+    }
+
+    pub fn eval_program(&mut self, bound_program: BoundProgram) -> Box<dyn Result> {
+        if self.holder.success() {
+            self.run(bound_program)
+        } else {
+            Box::new("<Error>".to_string())
+        }
     }
 
     pub fn bind_tree(&mut self, tree: Tree) -> Option<BoundExpression> {
-        let binder = Binder::new();
+        let mut binder = self.binder();
 
-        binder.bind_expression(tree.root_expression, &mut self.holder)
+        let mut bound_expression = binder.bind_expression(tree.root_expression, &mut self.holder);
+
+        if self.optimize && self.holder.success() {
+            optimize(&mut bound_expression, &mut self.holder);
+        }
+
+        bound_expression
     }
 
+    /// Evaluates a bound expression, reporting runtime errors through the diagnostic holder.
     pub fn eval_expression(
         &mut self,
         bound_expression: Option<BoundExpression>,
     ) -> Box<dyn Result> {
-        let evaluator = Evaluator::new(bound_expression.unwrap());
+        match bound_expression {
+            Some(bound_expression) if self.holder.success() => self.run(BoundProgram {
+                definitions: vec![],
+                entry: Some(bound_expression),
+            }),
+            _ => Box::new("<Error>".to_string()),
+        }
+    }
+
+    /// The host environment together with `args` when arguments are given, which binding and
+    /// evaluation both see.
+    fn host(&self) -> HostEnvironment {
+        let mut host = self.host.clone();
+
+        if let Some(arguments) = &self.arguments {
+            host.register_constant("args", "List string", arguments.clone());
+        }
+
+        host
+    }
+
+    /// Creates a binder knowing the declared types of the host functions and constants.
+    fn binder(&mut self) -> Binder {
+        let mut binder = Binder::new();
+
+        let host = self.host();
+
+        // Declared types are sources of their own, so their diagnostics are not located.
+        self.holder.elsewhere(|holder| {
+            for item in host.items() {
+                let tokens = cst::lex(&item.declared_type, holder);
+                let type_expression = Parser::new(tokens).parse_type(holder);
+
+                binder.declare_host(&item.name, &type_expression, item.arity, holder);
+            }
+        });
+
+        binder
+    }
+
+    /// Lays out the source canonically, see [`format_source`], or `None` if it has lexing or
+    /// parsing errors.
+    pub fn format(&mut self, spelling: Option<Spelling>) -> Option<String> {
+        format_source(&self.source, spelling, &mut self.holder)
+    }
+
+    /// Compiles the source as a program and lists its bytecode, or `None` if it has errors.
+    pub fn disassemble(&mut self) -> Option<String> {
+        let program = self.lex_parse_program();
+        let bound_program = self.bind_program(program);
+
+        self.holder
+            .success()
+            .then(|| Compiler::new().compile_program(&bound_program).to_string())
+    }
 
-        evaluator.eval(&self.holder)
+    /// Compiles the source as a program to a WebAssembly module exporting its entry expression,
+    /// see [`WasmCompiler`], or `None` if it has errors or values WebAssembly cannot represent.
+    pub fn emit_wasm(&mut self) -> Option<Vec<u8>> {
+        let program = self.lex_parse_program();
+        let bound_program = self.bind_program(program);
+
+        if !self.holder.success() {
+            return None;
+        }
+
+        let module = WasmCompiler::new().compile_program(&bound_program, &mut self.holder);
+
+        self.holder.success().then_some(module)
+    }
+
+    fn run(&mut self, program: BoundProgram) -> Box<dyn Result> {
+        let host = self.host();
+        let result = match self.backend {
+            Backend::Bytecode => Vm::new(Compiler::new().compile_program(&program))
+                .with_host(&host)
+                .with_limits(self.limits)
+                .with_arithmetic(self.arithmetic)
+                .evaluate(),
+            Backend::TreeWalker => Evaluator::from_program(program)
+                .with_host(&host)
+                .with_limits(self.limits)
+                .with_arithmetic(self.arithmetic)
+                .evaluate(),
+        };
+
+        match result {
+            Ok(value) => value.into_result(),
+            Err(error) => {
+                self.holder.error(&format!("Runtime error: {:}", error));
+                self.runtime_error = Some(error);
+                Box::new("<Error>".to_string())
+            }
+        }
     }
 
     pub fn lex_parse(&mut self) -> Tree {
-        let source = self.source.clone();
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.lex(&mut self.holder);
+        let tokens = cst::lex(&self.source, &mut self.holder);
 
         let mut parser = Parser::new(tokens);
 
         parser.parse(&mut self.holder)
     }
+
+    pub fn lex(&mut self) -> Vec<Token> {
+        Lexer::new(self.source.clone()).lex(&mut self.holder)
+    }
+
+    pub fn lex_parse_program(&mut self) -> Program {
+        self.parse_program(self.source.clone())
+    }
+
+    fn parse_program(&mut self, source: String) -> Program {
+        let tokens = cst::lex(&source, &mut self.holder);
+
+        let mut parser = Parser::new(tokens);
+
+        parser.parse_program(&mut self.holder)
+    }
+}
+
+fn load_module(
+    resolver: &dyn ModuleResolver,
+    holder: &mut DiagnosticHolder,
+    name: &str,
+    importers: &mut Vec<String>,
+    loaded: &mut Vec<(String, Program)>,
+    bound: &dyn Fn(&str) -> bool,
+) {
+    if let Some(position) = importers.iter().position(|importer| importer == name) {
+        let mut cycle = importers[position..].to_vec();
+        cycle.push(name.to_string());
+
+        holder.error(&format!("Import cycle detected: {:}", cycle.join(" -> ")));
+        return;
+    }
+
+    if bound(name) || loaded.iter().any(|(module, _)| module == name) {
+        return;
+    }
+
+    let source = match resolver.resolve(name) {
+        Some(source) => source,
+        None => {
+            holder.error(&format!("Cannot find module \"{:}\"", name));
+            return;
+        }
+    };
+    let program = parse_module(&source, holder);
+
+    importers.push(name.to_string());
+
+    for import in program.imports() {
+        load_module(resolver, holder, &import, importers, loaded, bound);
+    }
+
+    importers.pop();
+    loaded.push((name.to_string(), program));
+}
+
+/// Parses an imported module, whose diagnostics are not located since it is not the compiled
+/// source.
+fn parse_module(source: &str, holder: &mut DiagnosticHolder) -> Program {
+    let tokens = cst::lex(source, holder);
+
+    holder.elsewhere(|holder| Parser::new(tokens).parse_program(holder))
+}
+
+/// Adds the keywords to the given names, in order, to complete them, see
+/// [`Compilation::completions`].
+pub fn with_keywords(mut names: Vec<String>) -> Vec<String> {
+    names.extend(KEYWORDS.iter().map(|(keyword, _)| keyword.to_string()));
+    names.extend(["true", "false"].map(String::from));
+    names.sort();
+    names.dedup();
+    names
 }
@@ -0,0 +1,742 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::{
+    diagnostic::DiagnosticHolder,
+    lexer::{Lexer, Token, Type},
+    parser::{Declaration, Expression, Parser, Pattern, Program, TypeDeclaration, TypeExpression},
+};
+
+/// Kinds of the nodes of a concrete syntax tree, one for each construct of the grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A source file, made of top-level items and the separators between them.
+    Program,
+    /// An expression or type parsed on its own, see [`Parser::parse`].
+    Root,
+    Import,
+    Export,
+    /// `name parameters = value`.
+    Definition,
+    /// `type Name parameters = Constructor fields | ...`.
+    TypeDeclaration,
+    Constructor,
+    /// A type declaration scoped to an expression, `type ... in body`.
+    TypeDeclarationExpression,
+    /// An identifier, possibly qualified by modules, e.g. `math::abs`.
+    Path,
+    /// A number, string or boolean.
+    Literal,
+    Unary,
+    Binary,
+    Call,
+    Field,
+    Index,
+    Parenthesis,
+    Tuple,
+    List,
+    Record,
+    RecordUpdate,
+    /// `name: value` in a record or record update.
+    RecordField,
+    Match,
+    /// `pattern -> body` in a `match`.
+    MatchArm,
+    If,
+    Let,
+    Lambda,
+    WildcardPattern,
+    LiteralPattern,
+    ConstructorPattern,
+    ParenthesizedPattern,
+    TuplePattern,
+    ListPattern,
+    /// A type name with arguments written in angle brackets, e.g. `List<a>`.
+    NamedType,
+    /// A type name applied to arguments by juxtaposition, e.g. `Maybe number`.
+    TypeApplication,
+    FunctionType,
+    ParenthesizedType,
+    TupleType,
+    RecordType,
+    /// `name: type` in a record type.
+    RecordFieldType,
+    /// Tokens which do not form what was expected, or nothing where something was expected.
+    Error,
+}
+
+/// A token of a green tree together with the source code it was read from. Whitespace and
+/// text the lexer could not read are kept as trivia without a token, comments as trivia with
+/// one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenToken {
+    pub token: Option<Token>,
+    pub text: String,
+}
+
+impl GreenToken {
+    pub fn is_trivia(&self) -> bool {
+        self.token
+            .as_ref()
+            .is_none_or(|token| token.token_type == Type::Comment)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GreenElement {
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len(),
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An immutable node of a concrete syntax tree, which only knows its kind, its children and
+/// the length of their text, so that unchanged subtrees can be shared between versions of a
+/// tree. See [`Node`] for the position of a node in its tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreenNode {
+    kind: NodeKind,
+    children: Vec<GreenElement>,
+    len: usize,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::len).sum();
+
+        Self {
+            kind,
+            children,
+            len,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// Length in bytes of the source code of the node.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The source code of the node, exactly as it was read.
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.len);
+        self.push_text(&mut text);
+        text
+    }
+
+    fn push_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.push_text(text),
+                GreenElement::Token(token) => text.push_str(&token.text),
+            }
+        }
+    }
+}
+
+/// A node of a concrete syntax tree at its position in the tree: its byte offset in the source
+/// and its ancestors, computed while walking down from the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    green: Rc<GreenNode>,
+    offset: usize,
+    /// The parent and the index of this node among its children.
+    parent: Option<(Rc<Node>, usize)>,
+}
+
+/// A token of a concrete syntax tree, see [`GreenToken`], at its position in the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaf {
+    green: Rc<GreenToken>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Leaf(Leaf),
+}
+
+impl Node {
+    /// The root of a tree.
+    pub fn new(green: Rc<GreenNode>) -> Self {
+        Self {
+            green,
+            offset: 0,
+            parent: None,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.green.kind
+    }
+
+    pub fn green(&self) -> &Rc<GreenNode> {
+        &self.green
+    }
+
+    /// Byte range of the source code of the node.
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.len
+    }
+
+    pub fn text(&self) -> String {
+        self.green.text()
+    }
+
+    pub fn parent(&self) -> Option<&Node> {
+        self.parent.as_ref().map(|(parent, _)| parent.as_ref())
+    }
+
+    /// The parent of this node, its parent and so on up to the root.
+    pub fn ancestors(&self) -> Vec<Node> {
+        let mut ancestors = vec![];
+        let mut node = self.parent();
+
+        while let Some(parent) = node {
+            ancestors.push(parent.clone());
+            node = parent.parent();
+        }
+
+        ancestors
+    }
+
+    pub fn children(&self) -> Vec<Element> {
+        let parent = Rc::new(self.clone());
+        let mut offset = self.offset;
+
+        self.green
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, child)| {
+                let element = match child {
+                    GreenElement::Node(node) => Element::Node(Node {
+                        green: node.clone(),
+                        offset,
+                        parent: Some((parent.clone(), index)),
+                    }),
+                    GreenElement::Token(token) => Element::Leaf(Leaf {
+                        green: token.clone(),
+                        offset,
+                    }),
+                };
+
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    /// The child nodes of this node, in order.
+    pub fn nodes(&self) -> Vec<Node> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                Element::Node(node) => Some(node),
+                Element::Leaf(_) => None,
+            })
+            .collect()
+    }
+
+    /// The tokens directly inside this node, in order, leaving out trivia, each with its byte
+    /// range in the source.
+    pub fn tokens(&self) -> Vec<Token> {
+        self.children()
+            .into_iter()
+            .filter_map(|child| match child {
+                Element::Leaf(leaf) if !leaf.green.is_trivia() => leaf
+                    .token()
+                    .map(|token| token.clone().with_span(leaf.range())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every token of the subtree, trivia included, in order.
+    pub fn leaves(&self) -> Vec<Leaf> {
+        self.children()
+            .into_iter()
+            .flat_map(|child| match child {
+                Element::Node(node) => node.leaves(),
+                Element::Leaf(leaf) => vec![leaf],
+            })
+            .collect()
+    }
+
+    /// The innermost node of the subtree whose source code contains `offset`.
+    pub fn node_at(&self, offset: usize) -> Option<Node> {
+        if !self.range().contains(&offset) {
+            return None;
+        }
+
+        self.nodes()
+            .iter()
+            .find_map(|node| node.node_at(offset))
+            .or_else(|| Some(self.clone()))
+    }
+
+    /// Replaces this node by `green`, returning the root of the changed tree. Only the nodes
+    /// from here up to the root are rebuilt, the rest is shared with this tree.
+    pub fn replace_with(&self, green: GreenNode) -> Rc<GreenNode> {
+        let green = Rc::new(green);
+
+        match &self.parent {
+            Some((parent, index)) => {
+                let mut children = parent.green.children.clone();
+                children[*index] = GreenElement::Node(green);
+
+                parent.replace_with(GreenNode::new(parent.kind(), children))
+            }
+            None => green,
+        }
+    }
+}
+
+impl Leaf {
+    /// The lexed token, or `None` for whitespace and unreadable text.
+    pub fn token(&self) -> Option<&Token> {
+        self.green.token.as_ref()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+}
+
+/// How the parser read its tokens: as nodes starting, tokens belonging to the innermost open
+/// node, and nodes finishing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Start(NodeKind),
+    Token,
+    Finish,
+}
+
+/// Builds a tree of `kind` from parser events and the tokens they consumed, which are
+/// interleaved with trivia. Trivia belongs to the innermost node open before the next token,
+/// and tokens left over by the parser to the root.
+pub fn build(kind: NodeKind, events: Vec<Event>, tokens: Vec<GreenToken>) -> Rc<GreenNode> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut stack = vec![(kind, vec![])];
+
+    fn push_trivia(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<GreenToken>>,
+        children: &mut Vec<GreenElement>,
+    ) {
+        while let Some(trivia) = tokens.next_if(GreenToken::is_trivia) {
+            children.push(GreenElement::Token(Rc::new(trivia)));
+        }
+    }
+
+    for event in events {
+        match event {
+            Event::Start(kind) => {
+                let (_, children) = stack.last_mut().unwrap();
+                push_trivia(&mut tokens, children);
+                stack.push((kind, vec![]));
+            }
+            Event::Token => {
+                let (_, children) = stack.last_mut().unwrap();
+                push_trivia(&mut tokens, children);
+
+                if let Some(token) = tokens.next() {
+                    children.push(GreenElement::Token(Rc::new(token)));
+                }
+            }
+            Event::Finish => {
+                let (kind, children) = stack.pop().unwrap();
+                let (_, parent) = stack.last_mut().unwrap();
+
+                parent.push(GreenElement::Node(Rc::new(GreenNode::new(kind, children))));
+            }
+        }
+    }
+
+    let (kind, mut children) = stack.pop().unwrap();
+    children.extend(tokens.map(|token| GreenElement::Token(Rc::new(token))));
+
+    Rc::new(GreenNode::new(kind, children))
+}
+
+/// Lexes source code into the tokens of a green tree, with the trivia between them, so that
+/// their texts make up the whole source.
+pub fn lex(source: &str, holder: &mut DiagnosticHolder) -> Vec<GreenToken> {
+    green_tokens(source, Lexer::new(source.to_string()).lex_spans(holder))
+}
+
+/// Pairs tokens lexed from `source`, see [`Lexer::lex_spans`], with their source code and fills
+/// the gaps between them with trivia.
+pub fn green_tokens(source: &str, spans: Vec<(Token, Range<usize>)>) -> Vec<GreenToken> {
+    let mut tokens = vec![];
+    let mut position = 0;
+
+    for (token, span) in spans {
+        if position < span.start {
+            tokens.push(GreenToken {
+                token: None,
+                text: source[position..span.start].to_string(),
+            });
+        }
+
+        tokens.push(GreenToken {
+            token: Some(token),
+            text: source[span.clone()].to_string(),
+        });
+        position = span.end;
+    }
+
+    if position < source.len() {
+        tokens.push(GreenToken {
+            token: None,
+            text: source[position..].to_string(),
+        });
+    }
+
+    tokens
+}
+
+/// Parses source code into a lossless concrete syntax tree: its text is exactly `source`,
+/// including whitespace and code with errors, which are reported to `holder`.
+pub fn parse(source: &str, holder: &mut DiagnosticHolder) -> Node {
+    Parser::new(lex(source, holder)).parse_syntax(holder)
+}
+
+/// Derives the abstract syntax of a program from its concrete syntax tree.
+pub fn lower_program(node: &Node) -> Program {
+    let mut declarations = vec![];
+    let mut entry = None;
+
+    for child in node.nodes() {
+        if let Some(declaration) = lower_declaration(&child) {
+            declarations.push(declaration);
+        } else if entry.is_none() {
+            entry = lower_expression(&child);
+        }
+    }
+
+    Program {
+        declarations,
+        entry,
+    }
+}
+
+fn lower_declaration(node: &Node) -> Option<Declaration> {
+    match node.kind() {
+        NodeKind::Import => {
+            let path = node.nodes().into_iter().next()?;
+
+            Some(Declaration::Import(lower_path(&path)))
+        }
+        NodeKind::Export => {
+            let declaration = node.nodes().into_iter().next()?;
+
+            lower_declaration(&declaration)
+                .map(|declaration| Declaration::Export(Box::new(declaration)))
+        }
+        NodeKind::Definition => {
+            let mut identifiers = node
+                .tokens()
+                .into_iter()
+                .filter(|token| token.token_type == Type::Identifier);
+            let name = identifiers.next()?;
+            let parameters = identifiers.collect::<Vec<Token>>();
+            let value = node.nodes().first().and_then(lower_expression);
+
+            if parameters.is_empty() {
+                Some(Declaration::Definition(name, value))
+            } else {
+                Some(Declaration::Definition(
+                    name,
+                    Some(Expression::Lambda(parameters, Box::new(value))),
+                ))
+            }
+        }
+        NodeKind::TypeDeclaration => Some(Declaration::Type(lower_type_declaration(node))),
+        _ => None,
+    }
+}
+
+/// The name and parameters of a `let`, a definition or a type, with an empty name if it is
+/// missing.
+fn names(node: &Node) -> (Token, Vec<Token>) {
+    let mut identifiers = node
+        .tokens()
+        .into_iter()
+        .filter(|token| token.token_type == Type::Identifier);
+    let name = identifiers.next().unwrap_or_else(|| {
+        Token::new("", Type::Identifier).with_span(node.range().end..node.range().end)
+    });
+
+    (name, identifiers.collect())
+}
+
+fn lower_type_declaration(node: &Node) -> TypeDeclaration {
+    let (name, parameters) = names(node);
+    let constructors =
+        node.nodes()
+            .iter()
+            .filter(|child| child.kind() == NodeKind::Constructor)
+            .map(|constructor| {
+                let name = constructor.tokens().into_iter().next().unwrap_or_else(|| {
+                    Token::new("", Type::Identifier).with_span(constructor.range())
+                });
+                let fields = constructor.nodes().iter().map(lower_type).collect();
+
+                (name, fields)
+            })
+            .collect();
+
+    TypeDeclaration {
+        name,
+        parameters,
+        constructors,
+    }
+}
+
+/// The identifier named by a path, with its segments joined by `::`.
+fn lower_path(node: &Node) -> Token {
+    let segments = node
+        .tokens()
+        .into_iter()
+        .filter(|token| token.token_type == Type::Identifier)
+        .collect::<Vec<Token>>();
+    let literal = segments
+        .iter()
+        .map(|token| token.literal.as_str())
+        .collect::<Vec<&str>>()
+        .join("::");
+    let span = match (segments.first(), segments.last()) {
+        (Some(first), Some(last)) => first.span.start..last.span.end,
+        _ => node.range(),
+    };
+
+    Token::new(&literal, Type::Identifier).with_span(span)
+}
+
+/// Derives the abstract syntax of an expression, or `None` for an erroneous one.
+pub fn lower_expression(node: &Node) -> Option<Expression> {
+    let nodes = node.nodes();
+    let expression = |index: usize| Box::new(nodes.get(index).and_then(lower_expression));
+    let tokens = node.tokens();
+    let first_token = || tokens.first().cloned();
+
+    let expression = match node.kind() {
+        NodeKind::Path => Expression::Identifier(Box::new(lower_path(node))),
+        NodeKind::Literal => {
+            let token = first_token()?;
+
+            match token.token_type {
+                Type::Number => Expression::Number(Box::new(token)),
+                Type::Literal => Expression::Literal(Box::new(token)),
+                _ => Expression::Bool(Box::new(token)),
+            }
+        }
+        NodeKind::Unary => match first_token()?.token_type {
+            Type::Plus => Expression::Positive(expression(0)),
+            Type::Minus => Expression::Negative(expression(0)),
+            _ => Expression::NOT(expression(0)),
+        },
+        NodeKind::Binary => {
+            let (left, right) = (expression(0), expression(1));
+
+            match first_token()?.token_type {
+                Type::DoubleAmpersand => Expression::AND(left, right),
+                Type::DoublePipe => Expression::OR(left, right),
+                Type::BangEqual => Expression::BangEqual(left, right),
+                Type::DoubleEqual => Expression::Equal(left, right),
+                Type::GreaterThan => Expression::Greater(left, right),
+                Type::GreaterEqualThan => Expression::GreaterEqual(left, right),
+                Type::LessThan => Expression::Less(left, right),
+                Type::LessEqualThan => Expression::LessEqual(left, right),
+                Type::Plus => Expression::Addition(left, right),
+                Type::Minus => Expression::Subtraction(left, right),
+                Type::Star => Expression::Multiplication(left, right),
+                Type::Slash => Expression::Division(left, right),
+                _ => Expression::Remainder(left, right),
+            }
+        }
+        NodeKind::Call => Expression::Call(expression(0), expression(1)),
+        // A field missing its name stands for the expression before the dot.
+        NodeKind::Field => match tokens
+            .iter()
+            .find(|token| matches!(token.token_type, Type::Identifier | Type::Number))
+        {
+            Some(field) => Expression::Field(expression(0), Box::new(field.clone())),
+            None => return *expression(0),
+        },
+        NodeKind::Index => Expression::Index(expression(0), expression(1)),
+        NodeKind::Parenthesis => Expression::Parenthesis(expression(0)),
+        NodeKind::Tuple => Expression::Tuple(nodes.iter().map(lower_expression).collect()),
+        NodeKind::List => Expression::List(nodes.iter().map(lower_expression).collect()),
+        NodeKind::Record => Expression::Record(lower_fields(&nodes)),
+        NodeKind::RecordUpdate => Expression::RecordUpdate(expression(0), lower_fields(&nodes)),
+        NodeKind::Match => Expression::Match(
+            expression(0),
+            nodes
+                .iter()
+                .filter(|child| child.kind() == NodeKind::MatchArm)
+                .map(|arm| {
+                    let nodes = arm.nodes();
+
+                    (
+                        nodes.first().map_or(Pattern::Wildcard, lower_pattern),
+                        nodes.get(1).and_then(lower_expression),
+                    )
+                })
+                .collect(),
+        ),
+        NodeKind::If => Expression::If(expression(0), expression(1), expression(2)),
+        NodeKind::Let => {
+            let (name, parameters) = names(node);
+            let value = if parameters.is_empty() {
+                expression(0)
+            } else {
+                Box::new(Some(Expression::Lambda(parameters, expression(0))))
+            };
+
+            if tokens
+                .iter()
+                .any(|token| token.token_type == Type::RecKeyword)
+            {
+                Expression::LetRec(Box::new(name), value, expression(1))
+            } else {
+                Expression::Let(Box::new(name), value, expression(1))
+            }
+        }
+        NodeKind::Lambda => {
+            let parameters = tokens
+                .iter()
+                .filter(|token| token.token_type == Type::Identifier)
+                .cloned()
+                .collect();
+
+            Expression::Lambda(parameters, expression(0))
+        }
+        NodeKind::TypeDeclarationExpression => Expression::TypeDeclaration(
+            Box::new(lower_type_declaration(nodes.first()?)),
+            expression(1),
+        ),
+        _ => return None,
+    };
+
+    Some(expression)
+}
+
+fn lower_fields(nodes: &[Node]) -> Vec<(Token, Option<Expression>)> {
+    nodes
+        .iter()
+        .filter(|child| child.kind() == NodeKind::RecordField)
+        .filter_map(|field| {
+            let name = field.tokens().into_iter().next()?;
+
+            Some((name, field.nodes().first().and_then(lower_expression)))
+        })
+        .collect()
+}
+
+/// Derives the abstract syntax of a pattern, where parentheses are left out and erroneous
+/// patterns match anything.
+pub fn lower_pattern(node: &Node) -> Pattern {
+    let nodes = node.nodes();
+
+    match node.kind() {
+        NodeKind::Path => Pattern::Identifier(lower_path(node)),
+        NodeKind::LiteralPattern => match node.tokens().as_slice() {
+            [minus, number] if minus.token_type == Type::Minus => Pattern::Number(
+                Token::new(&format!("-{}", number.literal), Type::Number)
+                    .with_span(minus.span.start..number.span.end),
+            ),
+            [token] if token.token_type == Type::Number => Pattern::Number(token.clone()),
+            [token] if token.token_type == Type::Literal => Pattern::Literal(token.clone()),
+            [token] => Pattern::Bool(token.clone()),
+            _ => Pattern::Wildcard,
+        },
+        NodeKind::ConstructorPattern => match nodes.first().map(lower_pattern) {
+            Some(Pattern::Identifier(constructor)) => {
+                Pattern::Constructor(constructor, nodes[1..].iter().map(lower_pattern).collect())
+            }
+            _ => Pattern::Wildcard,
+        },
+        NodeKind::ParenthesizedPattern => nodes.first().map_or(Pattern::Wildcard, lower_pattern),
+        NodeKind::TuplePattern => Pattern::Tuple(nodes.iter().map(lower_pattern).collect()),
+        NodeKind::ListPattern => Pattern::List(nodes.iter().map(lower_pattern).collect()),
+        _ => Pattern::Wildcard,
+    }
+}
+
+/// Derives the abstract syntax of a type, where parentheses are left out and erroneous types
+/// are named by an empty name.
+pub fn lower_type(node: &Node) -> TypeExpression {
+    let nodes = node.nodes();
+    let error = || {
+        TypeExpression::Named(
+            Token::new("", Type::Identifier).with_span(node.range()),
+            vec![],
+        )
+    };
+
+    match node.kind() {
+        NodeKind::NamedType => match nodes.split_first() {
+            Some((name, arguments)) => {
+                TypeExpression::Named(lower_path(name), arguments.iter().map(lower_type).collect())
+            }
+            None => error(),
+        },
+        NodeKind::TypeApplication => match nodes.split_first() {
+            Some((name, arguments)) => match lower_type(name) {
+                TypeExpression::Named(name, _) => {
+                    TypeExpression::Named(name, arguments.iter().map(lower_type).collect())
+                }
+                other => other,
+            },
+            None => error(),
+        },
+        NodeKind::FunctionType => match nodes.as_slice() {
+            [parameter, result] => TypeExpression::Function(
+                Box::new(lower_type(parameter)),
+                Box::new(lower_type(result)),
+            ),
+            _ => error(),
+        },
+        NodeKind::ParenthesizedType => nodes.first().map_or_else(error, lower_type),
+        NodeKind::TupleType => TypeExpression::Tuple(nodes.iter().map(lower_type).collect()),
+        NodeKind::RecordType => TypeExpression::Record(
+            nodes
+                .iter()
+                .filter_map(|field| {
+                    let name = field.tokens().into_iter().next()?;
+
+                    Some((name, field.nodes().first().map_or_else(error, lower_type)))
+                })
+                .collect(),
+        ),
+        _ => error(),
+    }
+}
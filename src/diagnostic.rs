@@ -1,39 +1,113 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
 pub struct DiagnosticHolder {
     pub diagonistic_units: Vec<Unit>,
+    /// Where diagnostics reported now are located, see [`DiagnosticHolder::at`].
+    location: Option<Range<usize>>,
+    /// Whether diagnostics reported now are about another source than the compiled one, see
+    /// [`DiagnosticHolder::elsewhere`].
+    elsewhere: bool,
+}
+
+impl Default for DiagnosticHolder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DiagnosticHolder {
     pub fn new() -> Self {
         Self {
             diagonistic_units: vec![],
+            location: None,
+            elsewhere: false,
         }
     }
 
+    /// Whether no errors were reported; warnings do not prevent evaluation.
     pub fn success(&self) -> bool {
-        self.diagonistic_units.is_empty()
+        self.error_count() == 0
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagonistic_units
+            .iter()
+            .filter(|unit| matches!(unit, Unit::Error(..)))
+            .count()
     }
 
     pub fn warning(&mut self, message: &str) {
         self.diagonistic_units
-            .push(Unit::Warning(message.to_string()))
+            .push(Unit::Warning(message.to_string(), self.location.clone()))
     }
 
     pub fn error(&mut self, message: &str) {
         self.diagonistic_units
-            .push(Unit::Error(message.to_string()))
+            .push(Unit::Error(message.to_string(), self.location.clone()))
+    }
+
+    /// Locates the diagnostics reported by `report` at `span`, a byte range of the compiled
+    /// source, unless a nested call locates them more precisely. They keep the enclosing
+    /// location if `span` is `None`.
+    pub fn at<T>(&mut self, span: Option<Range<usize>>, report: impl FnOnce(&mut Self) -> T) -> T {
+        let Some(span) = span.filter(|_| !self.elsewhere) else {
+            return report(self);
+        };
+
+        let outer = self.location.replace(span);
+        let result = report(self);
+        self.location = outer;
+
+        result
+    }
+
+    /// Leaves the diagnostics reported by `report` unlocated, e.g. those of imported modules,
+    /// whose spans refer to their own sources.
+    pub fn elsewhere<T>(&mut self, report: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = (self.location.take(), self.elsewhere);
+        self.elsewhere = true;
+        let result = report(self);
+        (self.location, self.elsewhere) = outer;
+
+        result
     }
 }
 
+/// A diagnostic with its message and, if known, the byte range of the source it is about.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Unit {
-    Warning(String),
-    Error(String),
+    Warning(String, Option<Range<usize>>),
+    Error(String, Option<Range<usize>>),
 }
 
-impl ToString for Unit {
-    fn to_string(&self) -> String {
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Warning(message, _) => write!(f, "Warning: {}", message),
+            Unit::Error(message, _) => write!(f, "Error: {}", message),
+        }
+    }
+}
+
+impl Unit {
+    pub fn message(&self) -> &str {
+        match self {
+            Unit::Warning(message, _) | Unit::Error(message, _) => message,
+        }
+    }
+
+    pub fn span(&self) -> Option<&Range<usize>> {
+        match self {
+            Unit::Warning(_, span) | Unit::Error(_, span) => span.as_ref(),
+        }
+    }
+
+    /// Either `"warning"` or `"error"`.
+    pub fn severity(&self) -> &'static str {
         match self {
-            Unit::Warning(message) => format!("Warning: {}", message),
-            Unit::Error(message) => format!("Error: {}", message),
+            Unit::Warning(..) => "warning",
+            Unit::Error(..) => "error",
         }
     }
 }
@@ -0,0 +1,199 @@
+use std::fmt::{Display, Formatter};
+
+/// A pattern reduced to constructors and wildcards, as used by the usefulness algorithm.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimplePattern {
+    Wildcard,
+    Constructor(Constructor, Vec<SimplePattern>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constructor {
+    pub name: String,
+    pub arity: usize,
+    pub kind: ConstructorKind,
+    /// Every constructor of the matched type with its arity, or `None` when there are infinitely many.
+    pub siblings: Option<Vec<(String, usize)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstructorKind {
+    Named,
+    Tuple,
+    List,
+    Literal,
+}
+
+impl Constructor {
+    fn sibling(&self, name: &str, arity: usize) -> Constructor {
+        Constructor {
+            name: name.to_string(),
+            arity,
+            kind: self.kind,
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
+impl Display for SimplePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimplePattern::Wildcard => write!(f, "_"),
+            SimplePattern::Constructor(constructor, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.to_string())
+                    .collect::<Vec<String>>();
+
+                match constructor.kind {
+                    ConstructorKind::Tuple => write!(f, "({})", arguments.join(", ")),
+                    ConstructorKind::List => write!(f, "[{}]", arguments.join(", ")),
+                    ConstructorKind::Literal => write!(f, "{}", constructor.name),
+                    ConstructorKind::Named if arguments.is_empty() => {
+                        write!(f, "{}", constructor.name)
+                    }
+                    ConstructorKind::Named => write!(
+                        f,
+                        "{} {}",
+                        constructor.name,
+                        self.arguments()
+                            .iter()
+                            .map(|argument| argument.nested())
+                            .collect::<Vec<String>>()
+                            .join(" ")
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl SimplePattern {
+    fn arguments(&self) -> &[SimplePattern] {
+        match self {
+            SimplePattern::Constructor(_, arguments) => arguments,
+            SimplePattern::Wildcard => &[],
+        }
+    }
+
+    fn nested(&self) -> String {
+        match self {
+            SimplePattern::Constructor(constructor, arguments)
+                if constructor.kind == ConstructorKind::Named && !arguments.is_empty() =>
+            {
+                format!("({})", self)
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Checks whether `vector` matches any value not matched by the rows of `matrix`.
+///
+/// Returns an example of such values, with one pattern per column, when it does.
+pub fn useful(
+    matrix: &[Vec<SimplePattern>],
+    vector: &[SimplePattern],
+) -> Option<Vec<SimplePattern>> {
+    let (head, rest) = match vector.split_first() {
+        Some(split) => split,
+        None => {
+            return if matrix.is_empty() {
+                Some(vec![])
+            } else {
+                None
+            }
+        }
+    };
+
+    match head {
+        SimplePattern::Constructor(constructor, arguments) => {
+            let specialized_vector = [arguments.as_slice(), rest].concat();
+
+            useful(&specialize(matrix, constructor), &specialized_vector)
+                .map(|witness| rebuild(constructor, witness))
+        }
+        SimplePattern::Wildcard => {
+            let used = matrix
+                .iter()
+                .filter_map(|row| match &row[0] {
+                    SimplePattern::Constructor(constructor, _) => Some(constructor),
+                    SimplePattern::Wildcard => None,
+                })
+                .collect::<Vec<&Constructor>>();
+            let siblings = used
+                .first()
+                .and_then(|constructor| constructor.siblings.clone());
+
+            match siblings {
+                Some(siblings)
+                    if siblings
+                        .iter()
+                        .all(|(name, _)| used.iter().any(|used| used.name == *name)) =>
+                {
+                    siblings.iter().find_map(|(name, arity)| {
+                        let constructor = used[0].sibling(name, *arity);
+                        let specialized_vector =
+                            [vec![SimplePattern::Wildcard; *arity].as_slice(), rest].concat();
+
+                        useful(&specialize(matrix, &constructor), &specialized_vector)
+                            .map(|witness| rebuild(&constructor, witness))
+                    })
+                }
+                siblings => {
+                    let default = matrix
+                        .iter()
+                        .filter(|row| row[0] == SimplePattern::Wildcard)
+                        .map(|row| row[1..].to_vec())
+                        .collect::<Vec<Vec<SimplePattern>>>();
+
+                    useful(&default, rest).map(|witness| {
+                        let missing = siblings.and_then(|siblings| {
+                            siblings
+                                .into_iter()
+                                .find(|(name, _)| !used.iter().any(|used| used.name == *name))
+                        });
+                        let head = match missing {
+                            Some((name, arity)) => SimplePattern::Constructor(
+                                used[0].sibling(&name, arity),
+                                vec![SimplePattern::Wildcard; arity],
+                            ),
+                            None => SimplePattern::Wildcard,
+                        };
+
+                        [vec![head], witness].concat()
+                    })
+                }
+            }
+        }
+    }
+}
+
+fn specialize(matrix: &[Vec<SimplePattern>], constructor: &Constructor) -> Vec<Vec<SimplePattern>> {
+    matrix
+        .iter()
+        .filter_map(|row| match &row[0] {
+            SimplePattern::Constructor(other, arguments) if other.name == constructor.name => {
+                Some([arguments.as_slice(), &row[1..]].concat())
+            }
+            SimplePattern::Constructor(_, _) => None,
+            SimplePattern::Wildcard => Some(
+                [
+                    vec![SimplePattern::Wildcard; constructor.arity].as_slice(),
+                    &row[1..],
+                ]
+                .concat(),
+            ),
+        })
+        .collect()
+}
+
+fn rebuild(constructor: &Constructor, mut witness: Vec<SimplePattern>) -> Vec<SimplePattern> {
+    let rest = witness.split_off(constructor.arity);
+
+    [
+        vec![SimplePattern::Constructor(constructor.clone(), witness)],
+        rest,
+    ]
+    .concat()
+}
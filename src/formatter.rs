@@ -0,0 +1,644 @@
+use crate::cst::{self, Node, NodeKind};
+use crate::diagnostic::DiagnosticHolder;
+use crate::lexer::{Lexer, Type, KEYWORDS};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// How keywords and punctuation are spelled, as Collage accepts both ASCII and Mandarin ones,
+/// e.g. `fn` and `函數` or `,` and `，`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spelling {
+    Ascii,
+    Mandarin,
+}
+
+impl Spelling {
+    /// The spelling used by most keywords and punctuation of the source, ASCII on a tie.
+    pub fn detect(source: &str) -> Self {
+        let spans = Lexer::new(source.to_string()).lex_spans(&mut DiagnosticHolder::new());
+        let (mandarin, ascii): (Vec<&str>, Vec<&str>) = spans
+            .iter()
+            .filter(|(token, _)| {
+                Type::keyword(&token.literal).is_some()
+                    || matches!(
+                        token.token_type,
+                        Type::Comma | Type::Colon | Type::VerticalBar
+                    )
+            })
+            .map(|(_, span)| &source[span.clone()])
+            .partition(|text| !text.is_ascii());
+
+        if mandarin.len() > ascii.len() {
+            Spelling::Mandarin
+        } else {
+            Spelling::Ascii
+        }
+    }
+
+    fn keyword(self, token_type: Type) -> &'static str {
+        let mut spellings = KEYWORDS
+            .iter()
+            .filter(|(_, keyword_type)| *keyword_type == token_type)
+            .map(|(spelling, _)| *spelling);
+        let ascii = spellings.next().unwrap_or_default();
+
+        match self {
+            Spelling::Ascii => ascii,
+            Spelling::Mandarin => spellings.next().unwrap_or(ascii),
+        }
+    }
+
+    fn comma(self) -> &'static str {
+        match self {
+            Spelling::Ascii => ", ",
+            Spelling::Mandarin => "，",
+        }
+    }
+
+    fn colon(self) -> &'static str {
+        match self {
+            Spelling::Ascii => ": ",
+            Spelling::Mandarin => "：",
+        }
+    }
+
+    fn bar(self) -> &'static str {
+        match self {
+            Spelling::Ascii => "|",
+            Spelling::Mandarin => "｜",
+        }
+    }
+}
+
+/// Lays out source code canonically from its concrete syntax tree: one top-level item per
+/// line, keeping the blank lines between items, single spaces around operators, and the arms of
+/// `match` expressions on lines of their own, indented below the line starting the `match`.
+/// Comments are kept before the code following them, on a line of their own if they were, else
+/// at the end of the line. Keywords and punctuation are spelled as given, or as most of the source
+/// spells them. Returns `None` if the source has errors, which are reported to `holder`.
+pub fn format_source(
+    source: &str,
+    spelling: Option<Spelling>,
+    holder: &mut DiagnosticHolder,
+) -> Option<String> {
+    let tree = cst::parse(source, holder);
+
+    if !holder.success() {
+        return None;
+    }
+
+    let mut formatter = Formatter {
+        source,
+        spelling: spelling.unwrap_or_else(|| Spelling::detect(source)),
+        output: String::new(),
+        indent: 0,
+        comments: tree
+            .leaves()
+            .into_iter()
+            .filter(|leaf| {
+                leaf.token()
+                    .is_some_and(|token| token.token_type == Type::Comment)
+            })
+            .map(|leaf| leaf.range())
+            .collect(),
+        previous_end: 0,
+    };
+
+    for item in tree.nodes() {
+        formatter.top_level_comments(item.range().start);
+        formatter.separate(item.range().start);
+        formatter.indent = 0;
+        formatter.item(&item);
+        formatter.comments(item.range().end, 2);
+        formatter.previous_end = item.range().end;
+    }
+
+    formatter.top_level_comments(source.len());
+
+    if !formatter.output.is_empty() {
+        formatter.new_line(0);
+    }
+
+    Some(formatter.output)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    spelling: Spelling,
+    output: String,
+    /// Indentation of the current line.
+    indent: usize,
+    /// Byte ranges of the comments not written yet.
+    comments: VecDeque<Range<usize>>,
+    /// Where the source code of the last written top-level item or comment ends.
+    previous_end: usize,
+}
+
+impl Formatter<'_> {
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn keyword(&mut self, token_type: Type) {
+        let keyword = self.spelling.keyword(token_type);
+        self.push(keyword);
+    }
+
+    /// Starts a new line, or reuses the current one if it is still empty, e.g. after a comment.
+    fn new_line(&mut self, indent: usize) {
+        self.indent = indent;
+        self.output
+            .truncate(self.output.trim_end_matches(' ').len());
+
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+
+        self.output.push_str(&" ".repeat(indent));
+    }
+
+    /// Starts the line of a top-level item or comment beginning at `start`, after a blank line if
+    /// the source has one before it.
+    fn separate(&mut self, start: usize) {
+        if self.output.is_empty() {
+            return;
+        }
+
+        self.new_line(0);
+
+        if self.has_blank_line(self.previous_end..start) {
+            self.output.push('\n');
+        }
+    }
+
+    /// Writes the comments between top-level items which start before `offset`.
+    fn top_level_comments(&mut self, offset: usize) {
+        while let Some(comment) = self.next_comment(offset) {
+            if self.is_own_line(&comment) {
+                self.separate(comment.start);
+            } else {
+                self.space();
+            }
+
+            self.push(self.source[comment.clone()].trim_end());
+            self.previous_end = comment.end;
+        }
+    }
+
+    /// Writes the comments inside an item which start before `offset`, each followed by a new
+    /// line with the given indentation.
+    fn comments(&mut self, offset: usize, indent: usize) {
+        while let Some(comment) = self.next_comment(offset) {
+            if self.is_own_line(&comment) {
+                self.new_line(indent);
+            } else {
+                self.space();
+            }
+
+            self.push(self.source[comment].trim_end());
+            self.new_line(indent);
+        }
+    }
+
+    fn next_comment(&mut self, offset: usize) -> Option<Range<usize>> {
+        self.comments.pop_front_if(|comment| comment.start < offset)
+    }
+
+    /// Whether a comment is alone on its line of the source, rather than following code.
+    fn is_own_line(&self, comment: &Range<usize>) -> bool {
+        let line_start = self.source[..comment.start]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+
+        self.source[line_start..comment.start].trim().is_empty()
+    }
+
+    /// Separates what follows from the code on the current line by a space, unless nothing is on
+    /// it yet, e.g. for a comment following code.
+    fn space(&mut self) {
+        let line_start = self.output.rfind('\n').map_or(0, |index| index + 1);
+
+        if !self.output[line_start..].trim().is_empty() {
+            self.output
+                .truncate(self.output.trim_end_matches(' ').len());
+            self.push(" ");
+        }
+    }
+
+    /// Whether the source code in `range` has a blank line, i.e. one other than those of the
+    /// code around it.
+    fn has_blank_line(&self, range: Range<usize>) -> bool {
+        let lines = self.source[range].split('\n').collect::<Vec<&str>>();
+
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|line| line.trim().is_empty())
+    }
+
+    fn item(&mut self, item: &Node) {
+        match item.kind() {
+            NodeKind::Definition => self.definition(item),
+            NodeKind::TypeDeclaration => self.type_declaration(item),
+            NodeKind::Import => {
+                self.keyword(Type::ImportKeyword);
+                self.push(" ");
+                self.nodes(item, Self::path);
+            }
+            NodeKind::Export => {
+                self.keyword(Type::ExportKeyword);
+                self.push(" ");
+                self.nodes(item, Self::item);
+            }
+            _ => self.expression(item),
+        }
+    }
+
+    /// Writes each child node of `node`.
+    fn nodes(&mut self, node: &Node, mut write: impl FnMut(&mut Self, &Node)) {
+        for child in node.nodes() {
+            write(self, &child);
+        }
+    }
+
+    fn path(&mut self, path: &Node) {
+        for token in path.tokens() {
+            self.push(&token.literal);
+        }
+    }
+
+    fn definition(&mut self, definition: &Node) {
+        let (name, parameters) = names(definition);
+        let value = definition.nodes().remove(0);
+
+        self.push(&name);
+
+        let body = self.parameters(&parameters, value);
+        self.push(" =");
+
+        // A `let` spanning several lines starts on a line of its own.
+        if body.kind() == NodeKind::Let && is_multiline(&body) {
+            self.new_line(2);
+        } else {
+            self.push(" ");
+        }
+
+        self.expression(&body);
+    }
+
+    /// Writes the parameters of a function as those of a definition, e.g. `f x = x` rather than
+    /// `f = fn x -> x`, and returns its body.
+    fn parameters(&mut self, parameters: &[String], value: Node) -> Node {
+        let (parameters, body) = if parameters.is_empty() && value.kind() == NodeKind::Lambda {
+            (identifiers(&value), value.nodes().remove(0))
+        } else {
+            (parameters.to_vec(), value)
+        };
+
+        for parameter in parameters {
+            self.push(" ");
+            self.push(&parameter);
+        }
+
+        body
+    }
+
+    fn type_declaration(&mut self, declaration: &Node) {
+        let (name, parameters) = names(declaration);
+
+        self.keyword(Type::TypeKeyword);
+        self.push(" ");
+        self.push(&name);
+
+        for parameter in parameters {
+            self.push(" ");
+            self.push(&parameter);
+        }
+
+        self.push(" =");
+
+        for (index, constructor) in declaration.nodes().iter().enumerate() {
+            if index > 0 {
+                self.push(" ");
+                self.push(self.spelling.bar());
+            }
+
+            self.push(" ");
+            self.push(&names(constructor).0);
+
+            for field in constructor.nodes() {
+                self.push(" ");
+                self.type_expression(&field);
+            }
+        }
+    }
+
+    fn type_expression(&mut self, type_expression: &Node) {
+        let nodes = type_expression.nodes();
+        self.comments(type_expression.range().start, self.indent.max(2));
+
+        match type_expression.kind() {
+            NodeKind::NamedType => {
+                self.path(&nodes[0]);
+
+                if nodes.len() > 1 {
+                    self.push("<");
+                    self.separated(&nodes[1..], Self::type_expression);
+                    self.push(">");
+                }
+            }
+            NodeKind::TypeApplication => {
+                self.type_expression(&nodes[0]);
+
+                for argument in &nodes[1..] {
+                    self.push(" ");
+                    self.type_expression(argument);
+                }
+            }
+            NodeKind::FunctionType => {
+                self.type_expression(&nodes[0]);
+                self.push(" -> ");
+                self.type_expression(&nodes[1]);
+            }
+            NodeKind::ParenthesizedType | NodeKind::TupleType => {
+                self.push("(");
+                self.separated(&nodes, Self::type_expression);
+                self.push(")");
+            }
+            NodeKind::RecordType => {
+                self.push("{");
+
+                for (index, field) in nodes.iter().enumerate() {
+                    self.push(if index > 0 {
+                        self.spelling.comma()
+                    } else {
+                        " "
+                    });
+                    self.push(&names(field).0);
+                    self.push(self.spelling.colon());
+                    self.nodes(field, Self::type_expression);
+                }
+
+                self.push(if nodes.is_empty() { "}" } else { " }" });
+            }
+            _ => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Node) {
+        let nodes = pattern.nodes();
+        self.comments(pattern.range().start, self.indent.max(2));
+
+        match pattern.kind() {
+            NodeKind::WildcardPattern => self.push("_"),
+            NodeKind::Path => self.path(pattern),
+            NodeKind::LiteralPattern => self.literal(pattern),
+            NodeKind::ConstructorPattern => {
+                self.pattern(&nodes[0]);
+
+                for argument in &nodes[1..] {
+                    self.push(" ");
+                    self.pattern(argument);
+                }
+            }
+            NodeKind::ParenthesizedPattern | NodeKind::TuplePattern => {
+                self.push("(");
+                self.separated(&nodes, Self::pattern);
+                self.push(")");
+            }
+            NodeKind::ListPattern => {
+                self.push("[");
+                self.separated(&nodes, Self::pattern);
+                self.push("]");
+            }
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &Node) {
+        let nodes = expression.nodes();
+        let operator = || {
+            expression
+                .tokens()
+                .first()
+                .map(|token| token.literal.clone())
+                .unwrap_or_default()
+        };
+        self.comments(expression.range().start, self.indent.max(2));
+
+        match expression.kind() {
+            NodeKind::Path => self.path(expression),
+            NodeKind::Literal => self.literal(expression),
+            NodeKind::Unary => {
+                self.push(&operator());
+                self.expression(&nodes[0]);
+            }
+            NodeKind::Binary => {
+                self.expression(&nodes[0]);
+                self.push(" ");
+                self.push(&operator());
+                self.push(" ");
+                self.expression(&nodes[1]);
+            }
+            NodeKind::Parenthesis | NodeKind::Tuple => {
+                self.push("(");
+                self.separated(&nodes, Self::expression);
+                self.push(")");
+            }
+            NodeKind::List => {
+                self.push("[");
+                self.separated(&nodes, Self::expression);
+                self.push("]");
+            }
+            NodeKind::Index => {
+                self.expression(&nodes[0]);
+                self.push(".[");
+                self.expression(&nodes[1]);
+                self.push("]");
+            }
+            NodeKind::Field => {
+                self.expression(&nodes[0]);
+                self.push(".");
+                self.push(&field_name(expression));
+            }
+            NodeKind::Lambda => {
+                self.keyword(Type::FnKeyword);
+
+                for parameter in identifiers(expression) {
+                    self.push(" ");
+                    self.push(&parameter);
+                }
+
+                self.push(" -> ");
+                self.expression(&nodes[0]);
+            }
+            NodeKind::Call => {
+                self.expression(&nodes[0]);
+                self.push(" ");
+                self.expression(&nodes[1]);
+            }
+            NodeKind::Record => {
+                self.push("{");
+                self.fields(&nodes);
+                self.push(if nodes.is_empty() { "}" } else { " }" });
+            }
+            NodeKind::RecordUpdate => {
+                self.push("{ ");
+                self.expression(&nodes[0]);
+                self.push(" ");
+                self.keyword(Type::WithKeyword);
+                self.fields(&nodes[1..]);
+                self.push(" }");
+            }
+            NodeKind::TypeDeclarationExpression => {
+                self.type_declaration(&nodes[0]);
+                self.push(" ");
+                self.keyword(Type::InKeyword);
+                self.push(" ");
+                self.expression(&nodes[1]);
+            }
+            NodeKind::Match => {
+                let indent = self.indent;
+
+                self.keyword(Type::MatchKeyword);
+                self.push(" ");
+                self.expression(&nodes[0]);
+                self.push(" ");
+                self.keyword(Type::WithKeyword);
+
+                for arm in &nodes[1..] {
+                    self.comments(arm.range().start, indent + 2);
+
+                    let arm = arm.nodes();
+
+                    self.new_line(indent + 2);
+                    self.push(self.spelling.bar());
+                    self.push(" ");
+                    self.pattern(&arm[0]);
+                    self.push(" -> ");
+                    self.expression(&arm[1]);
+                }
+            }
+            NodeKind::If => {
+                self.keyword(Type::IfKeyword);
+                self.push(" ");
+                self.expression(&nodes[0]);
+                self.push(" ");
+                self.keyword(Type::ThenKeyword);
+                self.push(" ");
+                self.expression(&nodes[1]);
+                self.push(" ");
+                self.keyword(Type::ElseKeyword);
+                self.push(" ");
+                self.expression(&nodes[2]);
+            }
+            NodeKind::Let => {
+                let indent = self.indent;
+                let (name, parameters) = names(expression);
+
+                self.keyword(Type::LetKeyword);
+                self.push(" ");
+
+                if expression
+                    .tokens()
+                    .iter()
+                    .any(|token| token.token_type == Type::RecKeyword)
+                {
+                    self.keyword(Type::RecKeyword);
+                    self.push(" ");
+                }
+
+                self.push(&name);
+
+                let value = self.parameters(&parameters, nodes[0].clone());
+                self.push(" = ");
+                self.expression(&value);
+
+                self.comments(nodes[1].range().start, indent.max(2));
+
+                // Continuation lines are indented, as unindented ones start a new item.
+                if is_multiline(&value) || is_multiline(&nodes[1]) {
+                    self.new_line(indent.max(2));
+                } else {
+                    self.space();
+                }
+
+                self.keyword(Type::InKeyword);
+                self.push(" ");
+                self.expression(&nodes[1]);
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes a number, string or boolean.
+    fn literal(&mut self, literal: &Node) {
+        for token in literal.tokens() {
+            if token.token_type == Type::Literal {
+                self.push("\"");
+                self.push(&token.literal);
+                self.push("\"");
+            } else {
+                self.push(&token.literal);
+            }
+        }
+    }
+
+    fn fields(&mut self, fields: &[Node]) {
+        for (index, field) in fields.iter().enumerate() {
+            self.push(if index > 0 {
+                self.spelling.comma()
+            } else {
+                " "
+            });
+            self.push(&names(field).0);
+            self.push(self.spelling.colon());
+            self.nodes(field, Self::expression);
+        }
+    }
+
+    fn separated(&mut self, nodes: &[Node], mut write: impl FnMut(&mut Self, &Node)) {
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0 {
+                self.push(self.spelling.comma());
+            }
+
+            write(self, node);
+        }
+    }
+}
+
+/// The identifiers directly inside a node, e.g. the parameters of a lambda.
+fn identifiers(node: &Node) -> Vec<String> {
+    node.tokens()
+        .into_iter()
+        .filter(|token| token.token_type == Type::Identifier)
+        .map(|token| token.literal)
+        .collect()
+}
+
+/// The first identifier directly inside a node, which names it, and the following ones, which
+/// are its parameters.
+fn names(node: &Node) -> (String, Vec<String>) {
+    let mut identifiers = identifiers(node).into_iter();
+
+    (
+        identifiers.next().unwrap_or_default(),
+        identifiers.collect(),
+    )
+}
+
+/// The name of a field, or position in a tuple, following a dot.
+fn field_name(field: &Node) -> String {
+    field
+        .tokens()
+        .into_iter()
+        .find(|token| matches!(token.token_type, Type::Identifier | Type::Number))
+        .map(|token| token.literal)
+        .unwrap_or_default()
+}
+
+/// Whether an expression is laid out over several lines, i.e. contains a `match`.
+fn is_multiline(expression: &Node) -> bool {
+    expression.kind() == NodeKind::Match || expression.nodes().iter().any(is_multiline)
+}
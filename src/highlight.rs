@@ -0,0 +1,122 @@
+use crate::diagnostic::DiagnosticHolder;
+use crate::lexer::{Lexer, Type};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[38;5;9m";
+const GREEN: &str = "\x1b[38;5;10m";
+const YELLOW: &str = "\x1b[38;5;11m";
+const BLUE: &str = "\x1b[38;5;12m";
+const MAGENTA: &str = "\x1b[38;5;13m";
+const CYAN: &str = "\x1b[38;5;14m";
+const DARK_GREY: &str = "\x1b[38;5;8m";
+
+/// Colours source code for the terminal by the types of its tokens, see [`Lexer::lex_spans`]:
+/// keywords, numbers and booleans, strings, operators, constructors and comments each get a colour, and
+/// code which is not part of any token, e.g. an unterminated string, is shown as an error.
+pub fn highlight(source: &str) -> String {
+    let spans = Lexer::new(source.to_string()).lex_spans(&mut DiagnosticHolder::new());
+    let mut highlighted = String::new();
+    let mut position = 0;
+
+    for (token, span) in spans {
+        push_gap(&mut highlighted, &source[position..span.start]);
+
+        match colour(&token.token_type, &token.literal) {
+            Some(colour) => {
+                highlighted.push_str(colour);
+                highlighted.push_str(&source[span.clone()]);
+                highlighted.push_str(RESET);
+            }
+            None => highlighted.push_str(&source[span.clone()]),
+        }
+
+        position = span.end;
+    }
+
+    push_gap(&mut highlighted, &source[position..]);
+    highlighted
+}
+
+/// Colours the text of a node of a syntax tree, e.g. `Number(1)`, by its kind.
+pub fn highlight_node(node: &str) -> String {
+    let (kind, rest) = node.split_at(node.find('(').unwrap_or(node.len()));
+
+    format!("{:}{:}{:}{:}", BLUE, kind, RESET, rest)
+}
+
+/// Colours the lines and markers drawing a syntax tree.
+pub fn highlight_branch(branch: &str) -> String {
+    format!("{:}{:}{:}", DARK_GREY, branch, RESET)
+}
+
+/// Source code between tokens, which is whitespace unless the lexer skipped over an error.
+fn push_gap(highlighted: &mut String, gap: &str) {
+    let error = gap.trim_start();
+
+    highlighted.push_str(&gap[..gap.len() - error.len()]);
+
+    if !error.is_empty() {
+        highlighted.push_str(RED);
+        highlighted.push_str(error);
+        highlighted.push_str(RESET);
+    }
+}
+
+/// What a token is highlighted as, shared by the terminal and by editors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenClass {
+    Keyword,
+    Number,
+    Bool,
+    String,
+    Constructor,
+    Operator,
+    Comment,
+}
+
+/// Classifies a token by its type, or `None` for plain identifiers and punctuation.
+pub fn classify(token_type: &Type, literal: &str) -> Option<TokenClass> {
+    match token_type {
+        Type::Identifier if matches!(literal, "true" | "false") => Some(TokenClass::Bool),
+        Type::Identifier if literal.starts_with(char::is_uppercase) => {
+            Some(TokenClass::Constructor)
+        }
+        Type::Identifier => None,
+        Type::Number => Some(TokenClass::Number),
+        Type::Literal => Some(TokenClass::String),
+        Type::Comment => Some(TokenClass::Comment),
+        Type::FnKeyword
+        | Type::WithKeyword
+        | Type::TypeKeyword
+        | Type::InKeyword
+        | Type::MatchKeyword
+        | Type::IfKeyword
+        | Type::ThenKeyword
+        | Type::ElseKeyword
+        | Type::LetKeyword
+        | Type::RecKeyword
+        | Type::ImportKeyword
+        | Type::ExportKeyword => Some(TokenClass::Keyword),
+        Type::OpenParenthesis
+        | Type::CloseParenthesis
+        | Type::OpenBracket
+        | Type::CloseBracket
+        | Type::OpenBrace
+        | Type::CloseBrace
+        | Type::Comma
+        | Type::Semicolon
+        | Type::Newline => None,
+        _ => Some(TokenClass::Operator),
+    }
+}
+
+fn colour(token_type: &Type, literal: &str) -> Option<&'static str> {
+    classify(token_type, literal).map(|class| match class {
+        TokenClass::Keyword => MAGENTA,
+        TokenClass::Number | TokenClass::Bool => CYAN,
+        TokenClass::String => GREEN,
+        TokenClass::Constructor => YELLOW,
+        TokenClass::Operator => BLUE,
+        TokenClass::Comment => DARK_GREY,
+    })
+}
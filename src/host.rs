@@ -0,0 +1,359 @@
+use crate::runtime::{EvalResult, Function, RuntimeError, Value};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+type NativeFunction = Box<dyn Fn(&[Value]) -> EvalResult>;
+
+/// A Rust function callable from Collage once it has received all of its arguments.
+pub struct HostFunction {
+    pub name: String,
+    pub arity: usize,
+    function: NativeFunction,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> EvalResult + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> EvalResult {
+        (self.function)(arguments)
+    }
+}
+
+impl Debug for HostFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostFunction({:}/{:})", self.name, self.arity)
+    }
+}
+
+/// A function or constant provided by the embedder, with its type written in Collage syntax,
+/// e.g. `number -> List a -> a`. Lowercase names which are not types are type variables.
+#[derive(Debug, Clone)]
+pub struct HostItem {
+    pub name: String,
+    pub declared_type: String,
+    /// Number of arguments the host function takes, `None` for constants.
+    pub arity: Option<usize>,
+    pub value: Value,
+}
+
+/// Native functions and constants made visible to every module of a compilation.
+#[derive(Debug, Clone, Default)]
+pub struct HostEnvironment {
+    items: Vec<HostItem>,
+}
+
+impl HostEnvironment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a Rust closure taking between one and four arguments, each converted from its
+    /// Collage value with [`FromValue`] and its result converted back with [`IntoValue`].
+    pub fn register_function<Arguments>(
+        &mut self,
+        name: &str,
+        declared_type: &str,
+        function: impl IntoHostFunction<Arguments>,
+    ) -> &mut Self {
+        self.insert(name, declared_type, function.into_host_function(name))
+    }
+
+    /// Registers a function working on the raw argument values, which may fail with its own
+    /// runtime error, e.g. [`RuntimeError::Host`].
+    pub fn register_raw_function(
+        &mut self,
+        name: &str,
+        declared_type: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> EvalResult + 'static,
+    ) -> &mut Self {
+        self.insert(
+            name,
+            declared_type,
+            HostFunction::new(name, arity, function),
+        )
+    }
+
+    pub fn register_constant(
+        &mut self,
+        name: &str,
+        declared_type: &str,
+        value: impl IntoValue,
+    ) -> &mut Self {
+        self.items.retain(|item| item.name != name);
+        self.items.push(HostItem {
+            name: name.to_string(),
+            declared_type: declared_type.to_string(),
+            arity: None,
+            value: value.into_value(),
+        });
+        self
+    }
+
+    pub fn items(&self) -> &[HostItem] {
+        &self.items
+    }
+
+    fn insert(&mut self, name: &str, declared_type: &str, function: HostFunction) -> &mut Self {
+        self.items.retain(|item| item.name != name);
+        self.items.push(HostItem {
+            name: name.to_string(),
+            declared_type: declared_type.to_string(),
+            arity: Some(function.arity),
+            value: Value::Function(Rc::new(Function::Host {
+                function: Rc::new(function),
+                arguments: vec![],
+            })),
+        });
+        self
+    }
+}
+
+/// Converts a Collage value into a Rust value, failing when the value has another shape.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// Converts a Rust value into a Collage value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+/// Only accepts whole numbers within range.
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) if number.fract() == 0.0 && number.abs() <= i64::MAX as f64 => {
+                Some(*number as i64)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Number(self as f64)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(string) => Some(string.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(elements) => elements.iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+/// Maps to the built-in `Option` type.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Data(name, values) if name == "None" && values.is_empty() => Some(None),
+            Value::Data(name, values) if name == "Some" && values.len() == 1 => {
+                T::from_value(&values[0]).map(Some)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => Value::some(value.into_value()),
+            None => Value::none(),
+        }
+    }
+}
+
+/// Maps to the built-in `Result` type.
+impl<T: FromValue, E: FromValue> FromValue for Result<T, E> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Data(name, values) if name == "Ok" && values.len() == 1 => {
+                T::from_value(&values[0]).map(Ok)
+            }
+            Value::Data(name, values) if name == "Err" && values.len() == 1 => {
+                E::from_value(&values[0]).map(Err)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue, E: IntoValue> IntoValue for Result<T, E> {
+    fn into_value(self) -> Value {
+        match self {
+            Ok(value) => Value::ok(value.into_value()),
+            Err(error) => Value::err(error.into_value()),
+        }
+    }
+}
+
+/// Maps to a record type with fields of a single type.
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Record(fields) => fields
+                .iter()
+                .map(|(name, field)| T::from_value(field).map(|field| (name.clone(), field)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for BTreeMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Record(
+            self.into_iter()
+                .map(|(name, field)| (name, field.into_value()))
+                .collect(),
+        )
+    }
+}
+
+macro_rules! impl_tuple_conversion {
+    ($length:literal; $($element:ident $index:tt),+) => {
+        impl<$($element: FromValue),+> FromValue for ($($element,)+) {
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::Tuple(elements) if elements.len() == $length => {
+                        Some(($($element::from_value(&elements[$index])?,)+))
+                    }
+                    _ => None,
+                }
+            }
+        }
+
+        impl<$($element: IntoValue),+> IntoValue for ($($element,)+) {
+            fn into_value(self) -> Value {
+                Value::Tuple(vec![$(self.$index.into_value()),+])
+            }
+        }
+    };
+}
+
+impl_tuple_conversion!(2; A 0, B 1);
+impl_tuple_conversion!(3; A 0, B 1, C 2);
+impl_tuple_conversion!(4; A 0, B 1, C 2, D 3);
+
+/// A Rust closure which can be registered with [`HostEnvironment::register_function`], with
+/// `Arguments` being the tuple of its argument types.
+pub trait IntoHostFunction<Arguments> {
+    fn into_host_function(self, name: &str) -> HostFunction;
+}
+
+fn argument<T: FromValue>(function: &str, value: &Value) -> Result<T, RuntimeError> {
+    T::from_value(value).ok_or_else(|| RuntimeError::HostArgument {
+        function: function.to_string(),
+        value: value.to_string(),
+    })
+}
+
+macro_rules! impl_into_host_function {
+    ($arity:literal; $($argument:ident $index:tt),+) => {
+        impl<F, R, $($argument),+> IntoHostFunction<($($argument,)+)> for F
+        where
+            F: Fn($($argument),+) -> R + 'static,
+            R: IntoValue,
+            $($argument: FromValue,)+
+        {
+            fn into_host_function(self, name: &str) -> HostFunction {
+                let function_name = name.to_string();
+
+                HostFunction::new(name, $arity, move |arguments| {
+                    Ok(self($(argument::<$argument>(&function_name, &arguments[$index])?),+)
+                        .into_value())
+                })
+            }
+        }
+    };
+}
+
+impl_into_host_function!(1; A 0);
+impl_into_host_function!(2; A 0, B 1);
+impl_into_host_function!(3; A 0, B 1, C 2);
+impl_into_host_function!(4; A 0, B 1, C 2, D 3);
@@ -1,9 +1,16 @@
 use crate::diagnostic::DiagnosticHolder;
 use crate::parser::SyntaxNode;
+use std::ops::Range;
 
 pub struct Lexer {
     position: usize,
     source: String,
+    /// Grapheme positions of the separators between top-level items, see [`Lexer::split_items`].
+    separators: Vec<usize>,
+    /// Grapheme ranges of the tokens, see [`Lexer::lex_spans`].
+    spans: Vec<Range<usize>>,
+    /// Whether the source ended inside a string literal.
+    incomplete: bool,
 }
 
 impl Lexer {
@@ -11,11 +18,68 @@ impl Lexer {
         Self {
             position: 0,
             source,
+            separators: vec![],
+            spans: vec![],
+            incomplete: false,
         }
     }
 
-    fn offset(&self, segmented_source: &Vec<&str>, offset: usize) -> String {
-        segmented_source[self.position + offset].to_string()
+    /// Whether the source ended inside a string literal, so more input could complete it.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// Splits the source into the source code of its top-level items, wherever [`Lexer::lex`]
+    /// separates them, leaving out blank items.
+    pub fn split_items(&mut self, holder: &mut DiagnosticHolder) -> Vec<String> {
+        self.item_spans(holder)
+            .into_iter()
+            .map(|span| self.source[span].to_string())
+            .collect()
+    }
+
+    /// Byte ranges of the top-level items of the source without their surrounding whitespace,
+    /// see [`Lexer::split_items`].
+    pub fn item_spans(&mut self, holder: &mut DiagnosticHolder) -> Vec<Range<usize>> {
+        self.lex(holder);
+
+        let offsets = self.byte_offsets();
+        let mut items = vec![];
+        let mut start = 0;
+
+        for end in self
+            .separators
+            .iter()
+            .copied()
+            .chain(std::iter::once(offsets.len() - 1))
+        {
+            let item = &self.source[offsets[start]..offsets[end]];
+            let item_start = offsets[start] + (item.len() - item.trim_start().len());
+
+            if !item.trim().is_empty() {
+                items.push(item_start..item_start + item.trim().len());
+            }
+
+            start = end + 1;
+        }
+
+        items
+    }
+
+    /// Byte offsets of the graphemes of the source, followed by its length.
+    fn byte_offsets(&self) -> Vec<usize> {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        UnicodeSegmentation::grapheme_indices(self.source.as_str(), true)
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(self.source.len()))
+            .collect()
+    }
+
+    fn offset(&self, segmented_source: &[&str], offset: usize) -> String {
+        segmented_source
+            .get(self.position + offset)
+            .map_or_else(String::new, |grapheme| grapheme.to_string())
     }
 
     pub fn lex(&mut self, holder: &mut DiagnosticHolder) -> Vec<Token> {
@@ -24,8 +88,12 @@ impl Lexer {
         let segmented_source =
             UnicodeSegmentation::graphemes(self.source.as_str(), true).collect::<Vec<&str>>();
         let mut tokens = Vec::<Token>::new();
+        // Nesting of parentheses, brackets and braces, inside which newlines never separate items.
+        let mut depth = 0usize;
 
         while self.position < segmented_source.len() {
+            let start = self.position;
+            let token_count = tokens.len();
             let char = &segmented_source[self.position];
 
             match *char {
@@ -59,7 +127,7 @@ impl Lexer {
                         tokens.push(Token::new("==", Type::DoubleEqual));
                         self.position += 2;
                     } else {
-                        holder.error(&*format!("Unexpected character ="));
+                        tokens.push(Token::new("=", Type::Equal));
                         self.position += 1;
                     }
                 }
@@ -77,7 +145,7 @@ impl Lexer {
                         tokens.push(Token::new("&&", Type::DoubleAmpersand));
                         self.position += 2;
                     } else {
-                        holder.error(&*format!("Unexpected character &"));
+                        holder.error("Unexpected character &");
                         self.position += 1;
                     }
                 }
@@ -86,10 +154,14 @@ impl Lexer {
                         tokens.push(Token::new("||", Type::DoublePipe));
                         self.position += 2;
                     } else {
-                        holder.error(&*format!("Unexpected character |"));
+                        tokens.push(Token::new("|", Type::VerticalBar));
                         self.position += 1;
                     }
                 }
+                "｜" => {
+                    tokens.push(Token::new("|", Type::VerticalBar));
+                    self.position += 1;
+                }
                 ">" => {
                     if self.offset(&segmented_source, 1) == "=" {
                         tokens.push(Token::new(">=", Type::GreaterEqualThan));
@@ -109,22 +181,56 @@ impl Lexer {
                     }
                 }
                 "(" => {
+                    depth += 1;
                     tokens.push(Token::new("(", Type::OpenParenthesis));
                     self.position += 1;
                 }
                 ")" => {
+                    depth = depth.saturating_sub(1);
                     tokens.push(Token::new(")", Type::CloseParenthesis));
                     self.position += 1;
                 }
+                "[" => {
+                    depth += 1;
+                    tokens.push(Token::new("[", Type::OpenBracket));
+                    self.position += 1;
+                }
+                "]" => {
+                    depth = depth.saturating_sub(1);
+                    tokens.push(Token::new("]", Type::CloseBracket));
+                    self.position += 1;
+                }
+                "," | "，" => {
+                    tokens.push(Token::new(",", Type::Comma));
+                    self.position += 1;
+                }
+                "." => {
+                    tokens.push(Token::new(".", Type::Dot));
+                    self.position += 1;
+                }
                 ":" => {
                     if self.offset(&segmented_source, 1) == ":" {
                         tokens.push(Token::new("::", Type::DoubleColon));
                         self.position += 2;
                     } else {
-                        holder.error(&*format!("Unexpected character :"));
+                        tokens.push(Token::new(":", Type::Colon));
                         self.position += 1;
                     }
                 }
+                "：" => {
+                    tokens.push(Token::new(":", Type::Colon));
+                    self.position += 1;
+                }
+                "{" => {
+                    depth += 1;
+                    tokens.push(Token::new("{", Type::OpenBrace));
+                    self.position += 1;
+                }
+                "}" => {
+                    depth = depth.saturating_sub(1);
+                    tokens.push(Token::new("}", Type::CloseBrace));
+                    self.position += 1;
+                }
                 "~" => {
                     tokens.push(Token::new("~", Type::Tilde));
                     self.position += 1;
@@ -132,7 +238,7 @@ impl Lexer {
                 "\"" => {
                     self.position += 1;
 
-                    let start = *&self.position;
+                    let start = self.position;
 
                     while self.position < segmented_source.len()
                         && segmented_source[self.position] != "\""
@@ -140,15 +246,21 @@ impl Lexer {
                         self.position += 1;
                     }
 
-                    let string_literal = &segmented_source[start..self.position].join("");
-                    tokens.push(Token::new(&string_literal, Type::Literal));
+                    if self.position < segmented_source.len() {
+                        let string_literal = &segmented_source[start..self.position].join("");
+                        tokens.push(Token::new(string_literal, Type::Literal));
+                        self.position += 1;
+                    } else {
+                        holder.error("Unterminated string literal.");
+                        self.incomplete = true;
+                    }
                 }
-                _ if ("0".."9").contains(char) => {
+                _ if ("0"..="9").contains(char) => {
                     let mut float = false;
-                    let start = *&self.position;
+                    let start = self.position;
 
                     while self.position < segmented_source.len()
-                        && (("0".."9").contains(&segmented_source[self.position])
+                        && (("0"..="9").contains(&segmented_source[self.position])
                             || segmented_source[self.position] == ".")
                     {
                         if segmented_source[self.position] == "." {
@@ -163,34 +275,167 @@ impl Lexer {
                     }
 
                     let number = &segmented_source[start..self.position].join("");
-                    tokens.push(Token::new(&number, Type::Number));
+                    tokens.push(Token::new(number, Type::Number));
                 }
-                " " | "\t" | "\n" | "\r" => {
+                ";" | "；" => {
+                    tokens.push(Token::new(";", Type::Semicolon));
+                    self.separators.push(self.position);
                     self.position += 1;
                 }
+                "\n" | "\r\n" => {
+                    // A line starting without indentation begins a new top-level item,
+                    // indented lines continue the previous one.
+                    let next = self.offset(&segmented_source, 1);
+                    let continues = next.is_empty() || next.trim().is_empty();
+                    let separated = tokens
+                        .iter()
+                        .rfind(|token| token.token_type != Type::Comment)
+                        .is_none_or(|token| {
+                            matches!(token.token_type, Type::Newline | Type::Semicolon)
+                        });
+
+                    if depth == 0 && !continues && !separated {
+                        tokens.push(Token::new("\n", Type::Newline));
+                        self.separators.push(self.position);
+                    }
+
+                    self.position += 1;
+                }
+                " " | "\t" | "\r" => {
+                    self.position += 1;
+                }
+                "#" | "＃" => {
+                    // A comment runs to the end of its line.
+                    let start = self.position;
+
+                    while self.position < segmented_source.len()
+                        && !matches!(segmented_source[self.position], "\n" | "\r\n")
+                    {
+                        self.position += 1;
+                    }
+
+                    let comment = segmented_source[start..self.position].join("");
+                    tokens.push(Token::new(&comment, Type::Comment));
+                }
                 _ => {
-                    let start = *&self.position;
+                    let start = self.position;
 
                     while self.position < segmented_source.len()
-                        && !segmented_source[self.position].trim().is_empty()
+                        && !is_delimiter(segmented_source[self.position])
                     {
                         self.position += 1;
                     }
 
                     let identifier = &segmented_source[start..self.position].join("");
-                    tokens.push(Token::new(&identifier, Type::Identifier));
+                    let token_type = Type::keyword(identifier).unwrap_or(Type::Identifier);
+                    tokens.push(Token::new(identifier, token_type));
                 }
             }
+
+            if tokens.len() > token_count {
+                self.spans.push(start..self.position);
+            }
         }
 
         tokens
     }
+
+    /// Lexes the source and pairs every token with the byte range of the source code it was
+    /// read from, e.g. to highlight it.
+    pub fn lex_spans(&mut self, holder: &mut DiagnosticHolder) -> Vec<(Token, Range<usize>)> {
+        let tokens = self.lex(holder);
+        let offsets = self.byte_offsets();
+
+        tokens
+            .into_iter()
+            .zip(&self.spans)
+            .map(|(token, span)| (token, offsets[span.start]..offsets[span.end]))
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Whether `text` is a number literal as lexed above, i.e. ASCII digits with at most one dot,
+/// starting with a digit.
+pub fn is_number_literal(text: &str) -> bool {
+    text.starts_with(|char: char| char.is_ascii_digit())
+        && text
+            .chars()
+            .all(|char| char.is_ascii_digit() || char == '.')
+        && text.matches('.').count() <= 1
+}
+
+/// Graphemes which terminate an identifier, i.e. whitespaces and the start of any other token.
+fn is_delimiter(grapheme: &str) -> bool {
+    grapheme.trim().is_empty()
+        || matches!(
+            grapheme,
+            "+" | "-"
+                | "*"
+                | "/"
+                | "%"
+                | "="
+                | "!"
+                | "&"
+                | "|"
+                | "｜"
+                | ">"
+                | "<"
+                | "("
+                | ")"
+                | "["
+                | "]"
+                | ","
+                | "，"
+                | "."
+                | ":"
+                | "："
+                | "{"
+                | "}"
+                | "~"
+                | "\""
+                | ";"
+                | "；"
+                | "#"
+                | "＃"
+        )
+}
+
+/// ASCII and Mandarin spellings of every keyword, each ASCII spelling first.
+pub const KEYWORDS: &[(&str, Type)] = &[
+    ("fn", Type::FnKeyword),
+    ("函數", Type::FnKeyword),
+    ("with", Type::WithKeyword),
+    ("帶", Type::WithKeyword),
+    ("type", Type::TypeKeyword),
+    ("型別", Type::TypeKeyword),
+    ("in", Type::InKeyword),
+    ("在", Type::InKeyword),
+    ("match", Type::MatchKeyword),
+    ("匹配", Type::MatchKeyword),
+    ("if", Type::IfKeyword),
+    ("如果", Type::IfKeyword),
+    ("then", Type::ThenKeyword),
+    ("那麼", Type::ThenKeyword),
+    ("else", Type::ElseKeyword),
+    ("否則", Type::ElseKeyword),
+    ("let", Type::LetKeyword),
+    ("令", Type::LetKeyword),
+    ("rec", Type::RecKeyword),
+    ("遞迴", Type::RecKeyword),
+    ("import", Type::ImportKeyword),
+    ("引入", Type::ImportKeyword),
+    ("export", Type::ExportKeyword),
+    ("匯出", Type::ExportKeyword),
+];
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
 pub struct Token {
     pub literal: String,
     pub token_type: Type,
+    /// Byte range of the token in its source, known once it is read from a syntax tree, see
+    /// [`crate::cst::Node::tokens`], and empty otherwise.
+    pub span: Range<usize>,
 }
 
 impl Token {
@@ -198,8 +443,22 @@ impl Token {
         Self {
             literal: literal.to_string(),
             token_type,
+            span: 0..0,
         }
     }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+/// Tokens are equal if they read the same, wherever they are, so that syntax trees of different
+/// layouts of the same code are equal.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.literal == other.literal && self.token_type == other.token_type
+    }
 }
 
 impl SyntaxNode<Token> for Token {
@@ -215,6 +474,7 @@ impl SyntaxNode<Token> for Token {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
 pub enum Type {
     Identifier,
     Literal,
@@ -228,6 +488,7 @@ pub enum Type {
     DoubleAmpersand,
     DoublePipe,
     BangEqual,
+    Equal,
     DoubleEqual,
     GreaterThan,
     GreaterEqualThan,
@@ -235,13 +496,64 @@ pub enum Type {
     LessEqualThan,
     OpenParenthesis,
     CloseParenthesis,
+    OpenBracket,
+    CloseBracket,
+    OpenBrace,
+    CloseBrace,
+    Comma,
+    Dot,
+    Colon,
     Arrow,
     Tilde,
     VerticalBar,
     DoubleColon,
+    Semicolon,
+    Newline,
+    Comment,
+    FnKeyword,
+    WithKeyword,
+    TypeKeyword,
+    InKeyword,
+    MatchKeyword,
+    IfKeyword,
+    ThenKeyword,
+    ElseKeyword,
+    LetKeyword,
+    RecKeyword,
+    ImportKeyword,
+    ExportKeyword,
 }
 
 impl Type {
+    /// Resolves both ASCII and Mandarin spellings of a keyword.
+    pub fn keyword(literal: &str) -> Option<Type> {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == literal)
+            .map(|(_, token_type)| token_type.clone())
+    }
+
+    /// Whether a token of this type can begin an argument of a function application.
+    pub fn is_argument_start(&self) -> bool {
+        matches!(
+            self,
+            Type::Identifier
+                | Type::Literal
+                | Type::Number
+                | Type::OpenParenthesis
+                | Type::OpenBracket
+                | Type::OpenBrace
+        )
+    }
+
+    /// Whether a token of this type can begin a type atom.
+    pub fn is_type_start(&self) -> bool {
+        matches!(
+            self,
+            Type::Identifier | Type::OpenParenthesis | Type::OpenBrace
+        )
+    }
+
     pub fn unary_precedence(&self) -> usize {
         match self {
             Type::Plus | Type::Minus | Type::Bang => 7,
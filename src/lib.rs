@@ -1,9 +1,24 @@
 mod tests;
 
 pub mod binder;
+pub mod bytecode;
 pub mod compilation;
+pub mod cst;
 pub mod diagnostic;
+pub mod exhaustiveness;
+pub mod formatter;
+pub mod highlight;
+pub mod host;
 pub mod lexer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod lsp;
+pub mod module;
+pub mod optimizer;
 pub mod parser;
+#[cfg(feature = "playground")]
+pub mod playground;
 pub mod runtime;
+pub mod session;
 pub mod utils;
+pub mod vm;
+pub mod wasm;
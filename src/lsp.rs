@@ -0,0 +1,459 @@
+use crate::binder::Reference;
+use crate::compilation::Compilation;
+use crate::diagnostic::{DiagnosticHolder, Unit};
+use crate::formatter::format_source;
+use crate::highlight::{classify, TokenClass};
+use crate::lexer::{Lexer, KEYWORDS};
+use crate::module::{
+    ChainResolver, FileResolver, MemoryResolver, ModuleResolver, STANDARD_LIBRARY,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Semantic token types reported to editors, indexed by [`semantic_type`].
+const SEMANTIC_TOKEN_TYPES: [&str; 6] = [
+    "keyword",
+    "number",
+    "string",
+    "enumMember",
+    "operator",
+    "comment",
+];
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Runs a language server over `input` and `output`, usually stdin and stdout, until the client
+/// sends `exit` or closes `input`. Messages are JSON-RPC framed by `Content-Length` headers.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    let mut server = LanguageServer::new();
+
+    while let Some(content) = read_message(&mut input)? {
+        let replies = match serde_json::from_str::<Value>(&content) {
+            Ok(message) if message["method"] == "exit" => break,
+            Ok(message) => server.handle(&message),
+            Err(error) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": error.to_string() },
+            })],
+        };
+
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the content of a message, or `None` at the end of `input`.
+pub fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+
+    String::from_utf8(content)
+        .map(Some)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+}
+
+/// Writes `message` with its `Content-Length` header.
+pub fn write_message(output: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let content = message.to_string();
+
+    write!(
+        output,
+        "Content-Length: {:}\r\n\r\n{:}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+/// The open documents of an editor, answering its requests about them: diagnostics when they
+/// change, hover with the type of an identifier, go-to-definition, completion, semantic tokens
+/// and formatting.
+#[derive(Debug, Default)]
+pub struct LanguageServer {
+    documents: HashMap<String, String>,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles a request or notification and returns the messages to send back: the response
+    /// to a request and the diagnostics of changed documents.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let Some(id) = message.get("id") else {
+            return self.notify(method, uri, params);
+        };
+
+        let result = match method {
+            "initialize" => capabilities(),
+            "shutdown" => Value::Null,
+            "textDocument/hover" => self.with_document(uri, |text| {
+                hover(uri, text, offset(text, &params["position"]))
+            }),
+            "textDocument/definition" => self.with_document(uri, |text| {
+                definition(uri, text, offset(text, &params["position"]))
+            }),
+            "textDocument/completion" => self.with_document(uri, |text| completion(uri, text)),
+            "textDocument/semanticTokens/full" => self.with_document(uri, semantic_tokens),
+            "textDocument/formatting" => self.with_document(uri, formatting),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("Unknown method \"{:}\"", method),
+                    },
+                })]
+            }
+        };
+
+        vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })]
+    }
+
+    fn notify(&mut self, method: &str, uri: &str, params: &Value) -> Vec<Value> {
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            // Documents are synchronized in full, so the last change holds the whole text.
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+
+                match changes.and_then(|changes| changes.last()) {
+                    Some(change) => {
+                        let text = change["text"].as_str().unwrap_or_default();
+                        self.documents.insert(uri.to_string(), text.to_string());
+                    }
+                    None => return vec![],
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+
+                return vec![publish_diagnostics(uri, "", vec![])];
+            }
+            _ => return vec![],
+        }
+
+        let text = &self.documents[uri];
+        let units = compile(uri, text).1.diagonistic_units;
+
+        vec![publish_diagnostics(uri, text, units)]
+    }
+
+    fn with_document(&self, uri: &str, answer: impl FnOnce(&str) -> Value) -> Value {
+        self.documents
+            .get(uri)
+            .map_or(Value::Null, |text| answer(text))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "completionProvider": {},
+            "semanticTokensProvider": {
+                "legend": { "tokenTypes": SEMANTIC_TOKEN_TYPES, "tokenModifiers": [] },
+                "full": true,
+            },
+            "documentFormattingProvider": true,
+        },
+        "serverInfo": { "name": "collage", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Compiles a document without optimizing it, so that its bound tree follows the source,
+/// resolving its imports next to it and then from the standard library.
+fn compilation(uri: &str, text: &str) -> Compilation {
+    let mut resolvers: Vec<Box<dyn ModuleResolver>> =
+        vec![Box::new(MemoryResolver::new().with("main", text))];
+
+    if let Some(directory) = uri_path(uri).and_then(|path| path.parent().map(PathBuf::from)) {
+        resolvers.push(Box::new(FileResolver::new(directory)));
+    }
+
+    resolvers.push(Box::new(STANDARD_LIBRARY));
+
+    Compilation::with_resolver("main", Box::new(ChainResolver::new(resolvers)))
+        .without_optimization()
+}
+
+fn compile(uri: &str, text: &str) -> (Vec<Reference>, DiagnosticHolder) {
+    let mut compilation = compilation(uri, text);
+    let references = compilation.references();
+
+    (references, compilation.holder)
+}
+
+/// Diagnostics of a document, at the source they are about or else at its start.
+fn publish_diagnostics(uri: &str, text: &str, units: Vec<Unit>) -> Value {
+    let diagnostics = units
+        .iter()
+        .map(|unit| {
+            let severity = match unit {
+                Unit::Error(..) => 1,
+                Unit::Warning(..) => 2,
+            };
+
+            json!({
+                "range": range(text, unit.span().unwrap_or(&(0..0))),
+                "severity": severity,
+                "source": "collage",
+                "message": unit.message(),
+            })
+        })
+        .collect::<Vec<Value>>();
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// The type of the name bound or used at `offset`, as inferred in its scope.
+fn hover(uri: &str, text: &str, offset: usize) -> Value {
+    let Some(reference) = reference_at(uri, text, offset) else {
+        return Value::Null;
+    };
+
+    match reference.bound_type {
+        Some(bound_type) => json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```collage\n{:} : {:}\n```", reference.name, bound_type),
+            },
+            "range": range(text, &reference.span),
+        }),
+        None => Value::Null,
+    }
+}
+
+/// Where the name at `offset` is bound in the document, e.g. by a parameter, a `let`, a pattern
+/// variable or a top-level definition, type or constructor.
+fn definition(uri: &str, text: &str, offset: usize) -> Value {
+    match reference_at(uri, text, offset).and_then(|reference| reference.definition) {
+        Some(definition) => json!({ "uri": uri, "range": range(text, &definition) }),
+        None => Value::Null,
+    }
+}
+
+/// The name bound or used at `offset`, see [`Compilation::references`].
+fn reference_at(uri: &str, text: &str, offset: usize) -> Option<Reference> {
+    let references = compile(uri, text).0;
+
+    // A cursor right after a name, e.g. at the end of a word, still points at it.
+    references
+        .iter()
+        .find(|reference| reference.span.contains(&offset))
+        .or_else(|| {
+            references
+                .iter()
+                .find(|reference| reference.span.end == offset)
+        })
+        .cloned()
+}
+
+fn completion(uri: &str, text: &str) -> Value {
+    let keywords = KEYWORDS
+        .iter()
+        .map(|(keyword, _)| *keyword)
+        .collect::<Vec<&str>>();
+
+    compilation(uri, text)
+        .completions()
+        .iter()
+        .map(|name| {
+            // Keywords, constructors and other names, as LSP completion item kinds.
+            let kind = if keywords.contains(&name.as_str()) {
+                14
+            } else if name.starts_with(char::is_uppercase) {
+                20
+            } else {
+                6
+            };
+
+            json!({ "label": name, "kind": kind })
+        })
+        .collect()
+}
+
+fn semantic_tokens(text: &str) -> Value {
+    let spans = Lexer::new(text.to_string()).lex_spans(&mut DiagnosticHolder::new());
+    let mut data = vec![];
+    let (mut previous_line, mut previous_character) = (0, 0);
+
+    for (token, span) in spans {
+        let Some(class) = classify(&token.token_type, &token.literal) else {
+            continue;
+        };
+
+        if text[span.clone()].contains('\n') {
+            continue;
+        }
+
+        let (line, character) = line_character(text, span.start);
+        let length = text[span].encode_utf16().count();
+
+        if line != previous_line {
+            previous_character = 0;
+        }
+
+        data.extend([
+            line - previous_line,
+            character - previous_character,
+            length,
+            semantic_type(class),
+            0,
+        ]);
+        previous_line = line;
+        previous_character = character;
+    }
+
+    json!({ "data": data })
+}
+
+fn semantic_type(class: TokenClass) -> usize {
+    match class {
+        TokenClass::Keyword | TokenClass::Bool => 0,
+        TokenClass::Number => 1,
+        TokenClass::String => 2,
+        TokenClass::Constructor => 3,
+        TokenClass::Operator => 4,
+        TokenClass::Comment => 5,
+    }
+}
+
+/// Replaces the whole document by its formatted source, see [`format_source`], or answers
+/// `null` if it has errors.
+fn formatting(text: &str) -> Value {
+    match format_source(text, None, &mut DiagnosticHolder::new()) {
+        Some(formatted) if formatted == text => json!([]),
+        Some(formatted) => {
+            json!([{ "range": range(text, &(0..text.len())), "newText": formatted }])
+        }
+        None => Value::Null,
+    }
+}
+
+/// The byte offset of an LSP position, whose character counts UTF-16 code units.
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    let start = text
+        .split_inclusive('\n')
+        .take(line)
+        .map(str::len)
+        .sum::<usize>();
+    let mut units = 0;
+
+    for (index, char) in text[start..].char_indices() {
+        if char == '\n' || units >= character {
+            return start + index;
+        }
+
+        units += char.len_utf16();
+    }
+
+    text.len()
+}
+
+fn line_character(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    (
+        before.matches('\n').count(),
+        before[line_start..].encode_utf16().count(),
+    )
+}
+
+fn range(text: &str, span: &Range<usize>) -> Value {
+    let (start_line, start_character) = line_character(text, span.start);
+    let (end_line, end_character) = line_character(text, span.end);
+
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+/// The path of a `file:` URI, decoding escaped characters.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| path.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    let path = String::from_utf8(decoded).ok()?;
+
+    // Windows paths are written `file:///C:/...`.
+    match path.strip_prefix('/') {
+        Some(windows) if windows.get(1..2) == Some(":") => Some(PathBuf::from(windows)),
+        _ => Some(PathBuf::from(path)),
+    }
+}
@@ -2,33 +2,600 @@
 extern crate colour;
 
 use collage::compilation::Compilation;
+use collage::diagnostic::{DiagnosticHolder, Unit};
+use collage::formatter::Spelling;
+use collage::highlight::highlight;
+use collage::module::{read_source, source_files};
+use collage::runtime::Result;
+use collage::session::{is_incomplete, Session};
+use collage::utils::print_syntax_tree;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::json;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const COMMANDS: [&str; 11] = [
+    ":tokens", ":ast", ":bound", ":type", ":time", ":env", ":reset", ":save", ":load", ":cls",
+    ":exit",
+];
+
+/// REPL commands followed by source code, which may span several lines like other inputs.
+const SOURCE_COMMANDS: [&str; 5] = [":tokens", ":ast", ":bound", ":type", ":time"];
+
+const USAGE: &str = "Usage: collage [run <source> [arguments...] \
+    | check [--deny-warnings] [--format text|json] <paths...> \
+    | fmt [--check] [--keywords ascii|mandarin] <paths...> \
+    | --emit wasm <source> [-o <output>]]";
 
 fn main() {
-    loop {
-        magenta!("> ");
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
 
-        let mut input_source_code = String::new();
-        std::io::stdin()
-            .read_line(&mut input_source_code)
-            .expect("Unexpected reading error.");
+    let success = match arguments.first().map(String::as_str) {
+        None => {
+            repl();
+            true
+        }
+        Some("run") => run(&arguments[1..]),
+        Some("check") => check(&arguments[1..]),
+        Some("fmt") => fmt(&arguments[1..]),
+        Some("--emit") => emit(&arguments),
+        Some(command) => {
+            e_red_ln!("Unknown command \"{:}\"", command);
+            e_red_ln!("{:}", USAGE);
+            false
+        }
+    };
+
+    if !success {
+        std::process::exit(1);
+    }
+}
+
+fn repl() {
+    let mut session = Session::new();
+    let mut editor = match Editor::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            e_red_ln!("Cannot start the line editor: {:}", error);
+            return;
+        }
+    };
+    let history = history_path();
 
-        input_source_code = input_source_code.trim().to_string();
+    editor.set_helper(Some(ReplHelper {
+        names: session.completions(),
+    }));
 
-        match &*input_source_code {
+    if let Some(history) = &history {
+        // The history file does not exist before the first session.
+        let _ = editor.load_history(history);
+    }
+
+    while let Some(input_source_code) = read_input(&mut editor) {
+        let (command, source) = match input_source_code.split_once(char::is_whitespace) {
+            Some((command, source)) if command.starts_with(':') => (command, source.trim()),
+            _ => (&*input_source_code, ""),
+        };
+
+        if !input_source_code.is_empty() {
+            let _ = editor.add_history_entry(&input_source_code);
+        }
+
+        match command {
             ":exit" => break,
             ":cls" => print!("{esc}[2J{esc}[1;1H", esc = 27 as char),
-            _ => {
-                let mut compilation = Compilation::new(input_source_code);
-                let expression = compilation.lex_parse();
-                let bound_expression = compilation.bind_tree(expression);
+            ":reset" => session = Session::new(),
+            ":env" => {
+                for (name, bound_type) in session.bindings() {
+                    yellow_ln!("{:} : {:}", name, bound_type);
+                }
+            }
+            ":save" => {
+                if let Err(error) = std::fs::write(source, session.source()) {
+                    e_red_ln!("Cannot write \"{:}\": {:}", source, error);
+                }
+            }
+            ":load" => match read_source(source) {
+                Ok(saved) => {
+                    let mut loaded = Session::new();
+                    let (holder, result) = loaded.eval(&saved);
+
+                    print_result(&holder, result);
+
+                    if holder.success() {
+                        session = loaded;
+                    }
+                }
+                Err(error) => {
+                    e_red_ln!("Cannot read \"{:}\": {:}", source, error);
+                }
+            },
+            ":tokens" => {
+                let mut compilation = Compilation::new(source.to_string());
+                let tokens = compilation.lex();
+
+                for token in tokens {
+                    println!("{:?} {:?}", token.token_type, token.literal);
+                }
+
+                print_diagnostics(&compilation.holder);
+            }
+            ":ast" => {
+                let mut compilation = Compilation::new(source.to_string());
+                let tree = compilation.lex_parse();
+
+                print_syntax_tree(&tree.root_expression, String::new(), true);
+                print_diagnostics(&compilation.holder);
+            }
+            // Stages after parsing see the session's definitions.
+            ":bound" => {
+                let (compilation, bound_program) = session.compile(source);
+
+                if let Some(bound_expression) = bound_program.entry {
+                    println!("{:#?}", bound_expression);
+                }
+
+                print_diagnostics(&compilation.holder);
+            }
+            ":type" => {
+                let (compilation, bound_program) = session.compile(source);
+
+                if let Some(bound_expression) = bound_program.entry {
+                    yellow_ln!("{:} : {:}", source, bound_expression.get_type());
+                }
+
+                print_diagnostics(&compilation.holder);
+            }
+            ":time" => {
+                let start = Instant::now();
+                let (mut compilation, bound_program) = session.compile(source);
+                let compiled = start.elapsed();
+                let result = compilation.eval_program(bound_program);
+                let evaluated = start.elapsed() - compiled;
+
+                print_diagnostics(&compilation.holder);
 
                 if compilation.holder.success() {
-                    yellow_ln!("{:}", compilation.eval_expression(bound_expression));
-                } else {
-                    for i in compilation.holder.diagonistic_units {
-                        red_ln!("{:}", i.to_string());
+                    println!("{:}", highlight(&result.to_string()));
+                }
+
+                println!("Compiled in {:?}, evaluated in {:?}", compiled, evaluated);
+            }
+            _ if command.starts_with(':') => {
+                e_red_ln!(
+                    "Unknown command \"{:}\", expected {:}",
+                    command,
+                    COMMANDS.join(", ")
+                );
+            }
+            _ => {
+                let (holder, result) = session.eval(&input_source_code);
+
+                print_result(&holder, result);
+            }
+        }
+
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = session.completions();
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Err(error) = editor.save_history(history) {
+            e_red_ln!("Cannot write \"{:}\": {:}", history.display(), error);
+        }
+    }
+}
+
+/// The file keeping the REPL history between sessions, in the home directory.
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".collage_history"))
+}
+
+/// Completes the keyword, command or name before the cursor in the REPL.
+struct ReplHelper {
+    /// Keywords and names visible to the next input, see [`Session::completions`].
+    names: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        position: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..position]
+            .char_indices()
+            .rev()
+            .take_while(|(_, char)| char.is_alphanumeric() || matches!(char, '_' | ':'))
+            .last()
+            .map_or(position, |(index, _)| index);
+        let prefix = &line[start..position];
+        let candidates = if prefix.starts_with(':') && start == 0 {
+            COMMANDS.iter().map(|command| command.to_string()).collect()
+        } else {
+            self.names.clone()
+        };
+
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .filter(|candidate| !prefix.is_empty() && candidate.starts_with(prefix))
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize, _: bool) -> bool {
+        true
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _: bool) -> Cow<'b, str> {
+        Cow::Owned(format!("\x1b[95m{:}\x1b[0m", prompt))
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Reads a REPL input, prompting for another line while it is incomplete, see
+/// [`is_incomplete`], or while its last line is indented, until an empty line. Interrupting
+/// discards the input, and `None` means the input was closed.
+fn read_input(editor: &mut Editor<ReplHelper, DefaultHistory>) -> Option<String> {
+    let mut input = String::new();
+    let mut prompt = "> ";
+
+    loop {
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => return Some(String::new()),
+            Err(ReadlineError::Eof) if input.is_empty() => return None,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                e_red_ln!("Unexpected reading error: {:}", error);
+                return None;
+            }
+        };
+
+        let continued = !input.is_empty();
+        let blank = line.trim().is_empty();
+        let indented = !blank && line.starts_with(char::is_whitespace);
+
+        input.push_str(&line);
+        input.push('\n');
+
+        let trimmed = input.trim();
+        let source = match trimmed.split_once(char::is_whitespace) {
+            Some((command, source)) if SOURCE_COMMANDS.contains(&command) => source,
+            _ if trimmed.starts_with(':') => break,
+            _ => trimmed,
+        };
+
+        if continued && blank || !(is_incomplete(source) || continued && indented) {
+            break;
+        }
+
+        prompt = "... ";
+    }
+
+    Some(input.trim().to_string())
+}
+
+/// Prints the diagnostics of a REPL input, and its value if it has an entry expression and
+/// succeeded.
+fn print_result(holder: &DiagnosticHolder, result: Option<Box<dyn Result>>) {
+    print_diagnostics(holder);
+
+    if let Some(result) = result.filter(|_| holder.success()) {
+        println!("{:}", highlight(&result.to_string()));
+    }
+}
+
+/// Runs a source file, passing the arguments after it to the program as `args`, and prints
+/// the value of its entry expression. Returns whether it succeeded.
+fn run(arguments: &[String]) -> bool {
+    let Some((source, arguments)) = arguments.split_first() else {
+        e_red_ln!("{:}", USAGE);
+        return false;
+    };
+
+    let mut compilation = match Compilation::from_path(source) {
+        Ok(compilation) => compilation.with_arguments(arguments.to_vec()),
+        Err(error) => {
+            e_red_ln!("Cannot read \"{:}\": {:}", source, error);
+            return false;
+        }
+    };
+    let result = compilation.eval();
+
+    print_diagnostics(&compilation.holder);
+
+    if compilation.holder.success() {
+        println!("{:}", result);
+    }
+
+    compilation.holder.success()
+}
+
+/// Type-checks the given source files and every source file under the given directories
+/// without running them, printing their diagnostics as text or as JSON. Returns whether there
+/// were no errors, nor warnings with `--deny-warnings`.
+fn check(arguments: &[String]) -> bool {
+    let mut json = false;
+    let mut deny_warnings = false;
+    let mut paths = vec![];
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--deny-warnings" => deny_warnings = true,
+            "--format" => match arguments.next().map(String::as_str) {
+                Some("text") => json = false,
+                Some("json") => json = true,
+                _ => {
+                    e_red_ln!("{:}", USAGE);
+                    return false;
+                }
+            },
+            _ => paths.push(Path::new(argument)),
+        }
+    }
+
+    if paths.is_empty() {
+        e_red_ln!("{:}", USAGE);
+        return false;
+    }
+
+    let mut reports = vec![];
+
+    for path in paths {
+        let root = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or_else(|| Path::new("."))
+        };
+
+        match source_files(path) {
+            Ok(files) => {
+                for file in files {
+                    let units = match Compilation::from_path_in(root, &file) {
+                        Ok(mut compilation) => {
+                            compilation.check();
+                            compilation.holder.diagonistic_units
+                        }
+                        Err(error) => vec![Unit::Error(
+                            format!("Cannot read \"{:}\": {:}", file.display(), error),
+                            None,
+                        )],
+                    };
+
+                    reports.push((file, units));
+                }
+            }
+            Err(error) => reports.push((
+                path.to_path_buf(),
+                vec![Unit::Error(
+                    format!("Cannot read \"{:}\": {:}", path.display(), error),
+                    None,
+                )],
+            )),
+        }
+    }
+
+    let units = reports.iter().flat_map(|(_, units)| units);
+    let errors = units
+        .clone()
+        .filter(|unit| matches!(unit, Unit::Error(..)))
+        .count();
+    let warnings = units
+        .filter(|unit| matches!(unit, Unit::Warning(..)))
+        .count();
+
+    if json {
+        let files = reports
+            .iter()
+            .map(|(file, units)| {
+                json!({
+                    "file": file.display().to_string(),
+                    "diagnostics": units
+                        .iter()
+                        .map(|unit| json!({ "severity": unit.severity(), "message": unit.message() }))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        println!("{:}", json!(files));
+    } else {
+        for (file, units) in &reports {
+            for unit in units {
+                match unit {
+                    Unit::Warning(..) => {
+                        e_dark_yellow_ln!("{:}: {:}", file.display(), unit);
                     }
+                    Unit::Error(..) => {
+                        e_red_ln!("{:}: {:}", file.display(), unit);
+                    }
+                }
+            }
+        }
+
+        eprintln!(
+            "Checked {:} files: {:} errors, {:} warnings",
+            reports.len(),
+            errors,
+            warnings
+        );
+    }
+
+    errors == 0 && !(deny_warnings && warnings > 0)
+}
+
+/// Formats the given source files and every source file under the given directories in place,
+/// spelling keywords and punctuation as `--keywords` asks. With `--check`, lists the files
+/// which are not formatted instead. Returns whether all files could be formatted and, with
+/// `--check`, were already formatted.
+fn fmt(arguments: &[String]) -> bool {
+    let mut check = false;
+    let mut spelling = None;
+    let mut paths = vec![];
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--check" => check = true,
+            "--keywords" => match arguments.next().map(String::as_str) {
+                Some("ascii") => spelling = Some(Spelling::Ascii),
+                Some("mandarin") => spelling = Some(Spelling::Mandarin),
+                _ => {
+                    e_red_ln!("{:}", USAGE);
+                    return false;
                 }
+            },
+            _ => paths.push(Path::new(argument)),
+        }
+    }
+
+    if paths.is_empty() {
+        e_red_ln!("{:}", USAGE);
+        return false;
+    }
+
+    let mut success = true;
+
+    for path in paths {
+        let files = match source_files(path) {
+            Ok(files) => files,
+            Err(error) => {
+                e_red_ln!("Cannot read \"{:}\": {:}", path.display(), error);
+                success = false;
+                continue;
+            }
+        };
+
+        for file in files {
+            let source = match read_source(&file) {
+                Ok(source) => source,
+                Err(error) => {
+                    e_red_ln!("Cannot read \"{:}\": {:}", file.display(), error);
+                    success = false;
+                    continue;
+                }
+            };
+            let mut compilation = Compilation::new(source.clone());
+
+            let Some(formatted) = compilation.format(spelling) else {
+                for unit in &compilation.holder.diagonistic_units {
+                    e_red_ln!("{:}: {:}", file.display(), unit);
+                }
+
+                success = false;
+                continue;
+            };
+
+            if formatted == source {
+                continue;
+            }
+
+            if check {
+                println!("{:}", file.display());
+                success = false;
+            } else if let Err(error) = std::fs::write(&file, formatted) {
+                e_red_ln!("Cannot write \"{:}\": {:}", file.display(), error);
+                success = false;
+            }
+        }
+    }
+
+    success
+}
+
+/// Compiles a source file to the format given by `--emit`, written next to the source unless
+/// `-o` names the output. Returns whether it succeeded.
+fn emit(arguments: &[String]) -> bool {
+    let mut format = None;
+    let mut source = None;
+    let mut output = None;
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--emit" => format = arguments.next(),
+            "-o" => output = arguments.next().map(PathBuf::from),
+            _ if source.is_none() => source = Some(PathBuf::from(argument)),
+            _ => {
+                e_red_ln!("{:}", USAGE);
+                return false;
+            }
+        }
+    }
+
+    let (Some(format), Some(source)) = (format, source) else {
+        e_red_ln!("{:}", USAGE);
+        return false;
+    };
+
+    if format != "wasm" {
+        e_red_ln!("Unknown emit format \"{:}\", expected \"wasm\"", format);
+        return false;
+    }
+
+    let mut compilation = match Compilation::from_path(&source) {
+        Ok(compilation) => compilation,
+        Err(error) => {
+            e_red_ln!("Cannot read \"{:}\": {:}", source.display(), error);
+            return false;
+        }
+    };
+    let module = compilation.emit_wasm();
+
+    print_diagnostics(&compilation.holder);
+
+    let Some(module) = module else {
+        return false;
+    };
+    let output = output.unwrap_or_else(|| source.with_extension("wasm"));
+
+    match std::fs::write(&output, module) {
+        Ok(()) => true,
+        Err(error) => {
+            e_red_ln!("Cannot write \"{:}\": {:}", output.display(), error);
+            false
+        }
+    }
+}
+
+fn print_diagnostics(holder: &DiagnosticHolder) {
+    for i in &holder.diagonistic_units {
+        match i {
+            Unit::Warning(..) => {
+                e_dark_yellow_ln!("{:}", i);
+            }
+            Unit::Error(..) => {
+                e_red_ln!("{:}", i);
             }
         }
     }
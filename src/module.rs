@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File extension of Collage source files.
+pub const EXTENSION: &str = "clg";
+
+/// Module imported implicitly into every program, its exports usable without qualification.
+pub const PRELUDE: &str = "std::prelude";
+
+/// Modules shipped with the compiler, available to every program.
+pub const STANDARD_LIBRARY: EmbeddedResolver = EmbeddedResolver::new(&[
+    (PRELUDE, include_str!("std/prelude.clg")),
+    ("std::math", include_str!("std/math.clg")),
+]);
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Reads a UTF-8 source file, ignoring a leading byte order mark.
+pub fn read_source(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let source = std::fs::read_to_string(path)?;
+
+    match source.strip_prefix(BYTE_ORDER_MARK) {
+        Some(stripped) => Ok(stripped.to_string()),
+        None => Ok(source),
+    }
+}
+
+/// The source files to check for `path`: the file itself, or every file with the [`EXTENSION`]
+/// under a directory, in order of their paths.
+pub fn source_files(path: impl AsRef<Path>) -> std::io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = vec![];
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?.path();
+
+        if entry.is_dir() {
+            files.extend(source_files(&entry)?);
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+        {
+            files.push(entry);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Finds the source code of a module by its name, e.g. `std::math`.
+pub trait ModuleResolver {
+    fn resolve(&self, name: &str) -> Option<String>;
+}
+
+/// Resolves module `a::b` to the file `a/b.clg` under a root directory.
+pub struct FileResolver {
+    root: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ModuleResolver for FileResolver {
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut path = self.root.clone();
+        path.extend(name.split("::"));
+        path.set_extension(EXTENSION);
+
+        read_source(path).ok()
+    }
+}
+
+/// Modules held in memory, e.g. for tests or an editor without a file system.
+#[derive(Default)]
+pub struct MemoryResolver {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, name: &str, source: &str) {
+        self.modules.insert(name.to_string(), source.to_string());
+    }
+
+    pub fn with(mut self, name: &str, source: &str) -> Self {
+        self.insert(name, source);
+        self
+    }
+}
+
+impl ModuleResolver for MemoryResolver {
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.modules.get(name).cloned()
+    }
+}
+
+/// Modules compiled into the binary with `include_str!`.
+#[derive(Clone, Copy)]
+pub struct EmbeddedResolver {
+    modules: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedResolver {
+    pub const fn new(modules: &'static [(&'static str, &'static str)]) -> Self {
+        Self { modules }
+    }
+}
+
+impl ModuleResolver for EmbeddedResolver {
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.modules
+            .iter()
+            .find(|(module, _)| *module == name)
+            .map(|(_, source)| source.to_string())
+    }
+}
+
+/// Tries each resolver in turn, returning the first module found.
+pub struct ChainResolver {
+    resolvers: Vec<Box<dyn ModuleResolver>>,
+}
+
+impl ChainResolver {
+    pub fn new(resolvers: Vec<Box<dyn ModuleResolver>>) -> Self {
+        Self { resolvers }
+    }
+}
+
+impl ModuleResolver for ChainResolver {
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.resolvers
+            .iter()
+            .find_map(|resolver| resolver.resolve(name))
+    }
+}
@@ -0,0 +1,152 @@
+use crate::binder::{BoundExpression, BoundProgram};
+use crate::diagnostic::DiagnosticHolder;
+
+/// Simplifies every definition and the entry expression of `program`, see [`optimize`].
+pub fn optimize_program(program: &mut BoundProgram, holder: &mut DiagnosticHolder) {
+    for (_, definition) in &mut program.definitions {
+        optimize(definition, holder);
+    }
+
+    let mut entry = program.entry.take();
+    optimize(&mut entry, holder);
+    program.entry = entry;
+}
+
+/// Simplifies a bound tree bottom up without changing what it evaluates to: operators over
+/// literals are folded, parentheses and unary plus are dropped, `true &&`, `false ||` and
+/// double negations are removed, `false && x` and `true || x` are folded without `x`, which is
+/// never evaluated, and `if` expressions with a constant condition are replaced by the branch
+/// taken, with a warning. Division by zero and NaN results are left to the runtime and its
+/// [`crate::runtime::ArithmeticPolicy`].
+pub fn optimize(expression: &mut Option<BoundExpression>, holder: &mut DiagnosticHolder) {
+    let Some(inner) = expression else {
+        return;
+    };
+
+    for child in inner.children_mut() {
+        optimize(child, holder);
+    }
+
+    if let Some(simplified) = simplify(inner, holder) {
+        *expression = simplified;
+    }
+}
+
+/// The simplified form of a node whose children are already simplified, if any.
+fn simplify(
+    expression: &mut BoundExpression,
+    holder: &mut DiagnosticHolder,
+) -> Option<Option<BoundExpression>> {
+    match expression {
+        BoundExpression::Parenthesis(inner) | BoundExpression::Identity(inner) => {
+            Some(inner.take())
+        }
+        BoundExpression::Negation(inner) => number(inner).map(|value| number_literal(-value)),
+        BoundExpression::LogicalNot(inner) => match inner.as_mut() {
+            Some(BoundExpression::LogicalNot(double)) => Some(double.take()),
+            _ => bool(inner).map(|value| bool_literal(!value)),
+        },
+        BoundExpression::LogicalAnd(left, right) => match (bool(left), bool(right)) {
+            (Some(left), Some(right)) => Some(bool_literal(left && right)),
+            (Some(false), None) => Some(bool_literal(false)),
+            (Some(true), None) => Some(right.take()),
+            (None, Some(true)) => Some(left.take()),
+            _ => None,
+        },
+        BoundExpression::LogicalOr(left, right) => match (bool(left), bool(right)) {
+            (Some(left), Some(right)) => Some(bool_literal(left || right)),
+            (Some(true), None) => Some(bool_literal(true)),
+            (Some(false), None) => Some(right.take()),
+            (None, Some(false)) => Some(left.take()),
+            _ => None,
+        },
+        BoundExpression::Equal(left, right) => equal(left, right).map(bool_literal),
+        BoundExpression::NotEqual(left, right) => {
+            equal(left, right).map(|equal| bool_literal(!equal))
+        }
+        BoundExpression::Greater(left, right) => compare(left, right, |left, right| left > right),
+        BoundExpression::GreaterEqual(left, right) => {
+            compare(left, right, |left, right| left >= right)
+        }
+        BoundExpression::Less(left, right) => compare(left, right, |left, right| left < right),
+        BoundExpression::LessEqual(left, right) => {
+            compare(left, right, |left, right| left <= right)
+        }
+        BoundExpression::Addition(left, right) => fold(left, right, |left, right| left + right),
+        BoundExpression::Subtraction(left, right) => fold(left, right, |left, right| left - right),
+        BoundExpression::Multiplication(left, right) => {
+            fold(left, right, |left, right| left * right)
+        }
+        BoundExpression::Division(left, right) if number(right) != Some(0.0) => {
+            fold(left, right, |left, right| left / right)
+        }
+        BoundExpression::Remainder(left, right) if number(right) != Some(0.0) => {
+            fold(left, right, |left, right| left % right)
+        }
+        BoundExpression::If(condition, then_branch, else_branch) => {
+            bool(condition).map(|condition| {
+                holder.warning(&format!("Condition is always {:}", condition));
+
+                if condition {
+                    then_branch.take()
+                } else {
+                    else_branch.take()
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
+fn number(expression: &Option<BoundExpression>) -> Option<f64> {
+    match expression {
+        Some(BoundExpression::Number(number)) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+fn bool(expression: &Option<BoundExpression>) -> Option<bool> {
+    match expression {
+        Some(BoundExpression::Bool(bool)) => bool.parse().ok(),
+        _ => None,
+    }
+}
+
+fn number_literal(value: f64) -> Option<BoundExpression> {
+    Some(BoundExpression::Number(value.to_string()))
+}
+
+fn bool_literal(value: bool) -> Option<BoundExpression> {
+    Some(BoundExpression::Bool(value.to_string()))
+}
+
+/// Whether two literals are equal, or `None` unless both are literals.
+fn equal(left: &Option<BoundExpression>, right: &Option<BoundExpression>) -> Option<bool> {
+    match (left, right) {
+        (Some(BoundExpression::Literal(left)), Some(BoundExpression::Literal(right))) => {
+            Some(left == right)
+        }
+        _ => match (number(left), number(right)) {
+            (Some(left), Some(right)) => Some(left == right),
+            _ => Some(bool(left)? == bool(right)?),
+        },
+    }
+}
+
+fn compare(
+    left: &Option<BoundExpression>,
+    right: &Option<BoundExpression>,
+    comparison: impl Fn(f64, f64) -> bool,
+) -> Option<Option<BoundExpression>> {
+    Some(bool_literal(comparison(number(left)?, number(right)?)))
+}
+
+fn fold(
+    left: &Option<BoundExpression>,
+    right: &Option<BoundExpression>,
+    operation: impl Fn(f64, f64) -> f64,
+) -> Option<Option<BoundExpression>> {
+    let result = operation(number(left)?, number(right)?);
+
+    (!result.is_nan()).then(|| number_literal(result))
+}
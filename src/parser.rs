@@ -1,202 +1,1003 @@
 use std::fmt::Debug;
+use std::mem::take;
+use std::ops::Range;
 
-use strum_macros::ToString;
+use strum_macros::Display;
 
 use crate::{
+    cst::{self, Event, GreenToken, Node, NodeKind},
     diagnostic::DiagnosticHolder,
     lexer::{Token, Type},
 };
 
+/// Parses tokens into a concrete syntax tree, see [`crate::cst`], from which the abstract
+/// syntax is derived.
 pub struct Parser {
     position: usize,
     tokens: Vec<Token>,
+    /// The tokens with the trivia between them, see [`cst::lex`].
+    green_tokens: Vec<GreenToken>,
+    /// Byte length of the source, where errors about missing tokens are located.
+    end: usize,
+    /// Whether an error was reported at the end of the tokens, see [`Parser::is_incomplete`].
+    incomplete: bool,
+    /// The nodes and tokens read so far, see [`cst::build`].
+    events: Vec<Event>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    /// Creates a parser of the tokens of a source, see [`cst::lex`].
+    pub fn new(green_tokens: Vec<GreenToken>) -> Self {
+        let mut tokens = vec![];
+        let mut offset = 0;
+
+        for green_token in &green_tokens {
+            let span = offset..offset + green_token.text.len();
+
+            match &green_token.token {
+                Some(token) if !green_token.is_trivia() => {
+                    tokens.push(token.clone().with_span(span.clone()))
+                }
+                _ => {}
+            }
+
+            offset = span.end;
+        }
+
         Self {
             position: 0,
             tokens,
+            end: offset,
+            green_tokens,
+            incomplete: false,
+            events: vec![],
         }
     }
 
+    /// Whether parsing failed because the tokens ended in the middle of an item, e.g. after an
+    /// operator, inside brackets or before the `in` of a `let`, so more input could complete it.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// Reports an error at the current token, or at the end of the source after the last one.
+    fn error(&mut self, holder: &mut DiagnosticHolder, message: &str) {
+        let span = self
+            .peek(0)
+            .map_or(self.end..self.end, |token| token.span.clone());
+
+        self.incomplete |= self.peek(0).is_none();
+        holder.at(Some(span), |holder| holder.error(message));
+    }
+
+    /// Byte range of the tokens read since the token at `start`.
+    fn span_since(&self, start: usize) -> Range<usize> {
+        let end = self.tokens[..self.position.min(self.tokens.len())]
+            .last()
+            .map_or(self.end, |token| token.span.end);
+
+        self.tokens
+            .get(start)
+            .map_or(end..end, |token| token.span.start..end)
+    }
+
     fn peek(&self, offset: usize) -> Option<&Token> {
-        self.tokens.get(self.position.clone() + offset)
+        self.tokens.get(self.position + offset)
     }
 
-    fn assert(&mut self, token_type: Type) -> Option<&Token> {
-        let current = self.peek(0);
+    fn peek_type(&self, offset: usize) -> Option<&Type> {
+        self.peek(offset).map(|token| &token.token_type)
+    }
 
-        if let Some(token) = current {
-            if token.token_type == token_type {
-                let token = &self.tokens[self.position];
-                self.position += 1;
-                return Some(token);
-            }
+    fn advance(&mut self) {
+        if self.position < self.tokens.len() {
+            self.events.push(Event::Token);
+        }
+
+        self.position += 1;
+    }
+
+    fn assert(&mut self, token_type: Type) -> Option<&Token> {
+        if self.peek_type(0) == Some(&token_type) {
+            self.advance();
+            return Some(&self.tokens[self.position - 1]);
         }
 
         None
     }
 
+    /// Marks where a node may start, so it can be wrapped once its kind is known.
+    fn mark(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Makes everything read since `mark` a node of `kind`.
+    fn wrap(&mut self, mark: usize, kind: NodeKind) {
+        self.events.insert(mark, Event::Start(kind));
+        self.events.push(Event::Finish);
+    }
+
+    /// Builds the tree read so far, which holds all of the source code.
+    fn syntax(&mut self, kind: NodeKind) -> Node {
+        Node::new(cst::build(
+            kind,
+            take(&mut self.events),
+            take(&mut self.green_tokens),
+        ))
+    }
+
     pub fn parse(&mut self, holder: &mut DiagnosticHolder) -> Tree {
+        self.parse_expression(0, holder);
+
+        if let Some(token) = self.peek(0) {
+            let message = format!(
+                "Unexpected parsing error: Unexpected token {:}",
+                token.literal
+            );
+
+            self.error(holder, &message);
+        }
+
+        let root = self.syntax(NodeKind::Root);
+
         Tree {
-            root_expression: self.parse_expression(0, holder),
+            root_expression: root.nodes().first().and_then(cst::lower_expression),
+        }
+    }
+
+    /// Parses a type on its own, e.g. the declared type of a host function.
+    pub fn parse_type(&mut self, holder: &mut DiagnosticHolder) -> TypeExpression {
+        self.parse_type_expression(holder);
+
+        if let Some(token) = self.peek(0) {
+            let message = format!(
+                "Unexpected parsing error: Unexpected token {:}",
+                token.literal
+            );
+
+            self.error(holder, &message);
+        }
+
+        let root = self.syntax(NodeKind::Root);
+
+        cst::lower_type(&root.nodes()[0])
+    }
+
+    /// Parses top-level items separated by `;`, `；` or a newline followed by an unindented line.
+    pub fn parse_program(&mut self, holder: &mut DiagnosticHolder) -> Program {
+        cst::lower_program(&self.parse_syntax(holder))
+    }
+
+    /// Parses top-level items into a concrete syntax tree, see [`cst::parse`].
+    pub fn parse_syntax(&mut self, holder: &mut DiagnosticHolder) -> Node {
+        self.parse_items(holder);
+
+        self.syntax(NodeKind::Program)
+    }
+
+    fn parse_items(&mut self, holder: &mut DiagnosticHolder) {
+        let mut entry = false;
+
+        loop {
+            while self.assert(Type::Semicolon).is_some() || self.assert(Type::Newline).is_some() {}
+
+            let token_type = match self.peek_type(0) {
+                Some(token_type) => token_type.clone(),
+                None => break,
+            };
+            let mark = self.mark();
+            let start = self.position;
+
+            match token_type {
+                Type::ImportKeyword => {
+                    self.advance();
+
+                    let path = self.mark();
+
+                    match self.assert(Type::Identifier) {
+                        Some(_) => self.qualify(path),
+                        None => {
+                            self.error(holder, "Unexpected parsing error: Expected module name.")
+                        }
+                    }
+
+                    self.wrap(mark, NodeKind::Import);
+                }
+                Type::ExportKeyword => {
+                    self.advance();
+
+                    if self.peek_type(0) == Some(&Type::TypeKeyword) {
+                        self.parse_type_declaration(holder);
+                    } else if self.peek_type(0) == Some(&Type::Identifier) && self.is_definition() {
+                        self.parse_definition(holder);
+                    } else {
+                        self.error(
+                            holder,
+                            "Unexpected parsing error: Expected definition after export.",
+                        );
+                    }
+
+                    self.wrap(mark, NodeKind::Export);
+                }
+                Type::TypeKeyword => {
+                    self.parse_type_declaration(holder);
+
+                    if self.assert(Type::InKeyword).is_some() {
+                        self.parse_expression(0, holder);
+                        self.wrap(mark, NodeKind::TypeDeclarationExpression);
+                        self.set_entry(&mut entry, start, holder);
+                    }
+                }
+                Type::Identifier if self.is_definition() => self.parse_definition(holder),
+                _ => {
+                    if self.parse_expression(0, holder) {
+                        self.set_entry(&mut entry, start, holder);
+                    }
+                }
+            }
+
+            if let Some(token) = self.peek(0) {
+                if !matches!(token.token_type, Type::Semicolon | Type::Newline) {
+                    let message = format!(
+                        "Unexpected parsing error: Unexpected token {:}",
+                        token.literal
+                    );
+
+                    self.error(holder, &message);
+
+                    let skipped = self.mark();
+
+                    while self.peek_type(0).is_some_and(|token_type| {
+                        !matches!(token_type, Type::Semicolon | Type::Newline)
+                    }) {
+                        self.advance();
+                    }
+
+                    self.wrap(skipped, NodeKind::Error);
+                }
+            }
+        }
+    }
+
+    /// Extends an identifier read since `mark` with the `::` separated segments following it,
+    /// e.g. `math::abs`, into a path.
+    fn qualify(&mut self, mark: usize) {
+        while self.peek_type(0) == Some(&Type::DoubleColon)
+            && self.peek_type(1) == Some(&Type::Identifier)
+        {
+            self.advance();
+            self.advance();
         }
+
+        self.wrap(mark, NodeKind::Path);
     }
 
+    /// Marks the item read since the token at `start` as the entry expression, reporting it if
+    /// there already is one.
+    fn set_entry(&self, entry: &mut bool, start: usize, holder: &mut DiagnosticHolder) {
+        if *entry {
+            holder.at(Some(self.span_since(start)), |holder| {
+                holder.error(
+                    "Unexpected parsing error: A program can only have one entry expression.",
+                )
+            });
+        } else {
+            *entry = true;
+        }
+    }
+
+    /// Whether the upcoming tokens are `name parameters =`, i.e. a top-level definition.
+    fn is_definition(&self) -> bool {
+        let mut offset = 1;
+
+        while self.peek_type(offset) == Some(&Type::Identifier) {
+            offset += 1;
+        }
+
+        self.peek_type(offset) == Some(&Type::Equal)
+    }
+
+    fn parse_definition(&mut self, holder: &mut DiagnosticHolder) {
+        let mark = self.mark();
+        self.advance();
+
+        while self.assert(Type::Identifier).is_some() {}
+
+        let _ = self.assert(Type::Equal);
+        self.parse_expression(0, holder);
+        self.wrap(mark, NodeKind::Definition);
+    }
+
+    // Each of the expression parsers below reads exactly one node, which is an error node when
+    // it returns false, so the parts of an expression can be told apart by their order.
+
     fn parse_expression(
         &mut self,
         parent_precedence: usize,
         holder: &mut DiagnosticHolder,
-    ) -> Option<Expression> {
-        let mut left: Option<Expression> = None;
-        if let Some(precedence_token) = self.tokens.get(self.position) {
-            let precedence = precedence_token.token_type.unary_precedence();
-
-            left = if precedence != 0 && precedence >= parent_precedence {
-                self.position += 1;
-
-                match precedence_token.token_type {
-                    Type::Plus => Some(Expression::Positive(Box::new(
-                        self.parse_expression(precedence, holder),
-                    ))),
-                    Type::Minus => Some(Expression::Negative(Box::new(
-                        self.parse_expression(precedence, holder),
-                    ))),
-                    Type::Bang => Some(Expression::NOT(Box::new(
-                        self.parse_expression(precedence, holder),
-                    ))),
-                    _ => None,
-                }
+    ) -> bool {
+        let mark = self.mark();
+        let mut parsed = false;
+
+        if let Some(token_type) = self.peek_type(0) {
+            let precedence = token_type.unary_precedence();
+
+            parsed = if precedence != 0 && precedence >= parent_precedence {
+                self.advance();
+                self.parse_expression(precedence, holder);
+                self.wrap(mark, NodeKind::Unary);
+                true
             } else {
-                self.parse_literal_expression(holder)
+                self.parse_application_expression(holder)
             }
+        } else {
+            self.error(holder, "Unexpected parsing error: Unexpected end of input.");
+            self.wrap(mark, NodeKind::Error);
         }
 
-        loop {
-            if let Some(precedence_token) = self.tokens.get(self.position) {
-                let precedence = precedence_token.token_type.binary_precedence();
+        while let Some(token_type) = self.peek_type(0) {
+            let precedence = token_type.binary_precedence();
+
+            if precedence == 0 || precedence <= parent_precedence {
+                break;
+            }
+
+            self.advance();
+            self.parse_expression(precedence, holder);
+            self.wrap(mark, NodeKind::Binary);
+            parsed = true;
+        }
+
+        parsed
+    }
+
+    fn parse_application_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let mut parsed = self.parse_postfix_expression(holder);
+
+        while self
+            .peek_type(0)
+            .is_some_and(|token_type| token_type.is_argument_start())
+        {
+            self.parse_postfix_expression(holder);
+            self.wrap(mark, NodeKind::Call);
+            parsed = true;
+        }
+
+        parsed
+    }
 
-                if precedence == 0 || precedence <= parent_precedence {
+    fn parse_postfix_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let mut parsed = self.parse_literal_expression(holder);
+
+        while self.assert(Type::Dot).is_some() {
+            if matches!(self.peek_type(0), Some(Type::Identifier | Type::Number)) {
+                self.advance();
+                self.wrap(mark, NodeKind::Field);
+                parsed = true;
+                continue;
+            }
+
+            if self.assert(Type::OpenBracket).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected [, field name or tuple position after .",
+                );
+                // A field without a name, which stands for the expression before the dot.
+                self.wrap(mark, NodeKind::Field);
+                break;
+            }
+
+            self.parse_expression(0, holder);
+
+            if self.assert(Type::CloseBracket).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected ] to close index.",
+                );
+            }
+
+            self.wrap(mark, NodeKind::Index);
+            parsed = true;
+        }
+
+        parsed
+    }
+
+    fn parse_list_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::OpenBracket);
+
+        if self.assert(Type::CloseBracket).is_none() {
+            loop {
+                self.parse_expression(0, holder);
+
+                if self.assert(Type::Comma).is_none() {
                     break;
                 }
-                self.position += 1;
-
-                left = match precedence_token.token_type {
-                    Type::DoubleAmpersand => Some(Expression::AND(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::DoublePipe => Some(Expression::OR(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::BangEqual => Some(Expression::BangEqual(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::DoubleEqual => Some(Expression::Equal(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::GreaterThan => Some(Expression::Greater(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::GreaterEqualThan => Some(Expression::GreaterEqual(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::LessThan => Some(Expression::Less(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::LessEqualThan => Some(Expression::LessEqual(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::Plus => Some(Expression::Addition(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::Minus => Some(Expression::Subtraction(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::Star => Some(Expression::Multiplication(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::Slash => Some(Expression::Division(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    Type::Percent => Some(Expression::Remainder(
-                        Box::new(left),
-                        Box::new(self.parse_expression(precedence, holder)),
-                    )),
-                    _ => None,
-                };
-            } else {
+            }
+
+            if self.assert(Type::CloseBracket).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected ] to close list.",
+                );
+            }
+        }
+
+        self.wrap(mark, NodeKind::List);
+        true
+    }
+
+    /// Parses either a parenthesized expression or, when commas follow, a tuple.
+    fn parse_parenthesis_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::OpenParenthesis);
+        self.parse_expression(0, holder);
+
+        let kind = if self.peek_type(0) == Some(&Type::Comma) {
+            while self.assert(Type::Comma).is_some() {
+                self.parse_expression(0, holder);
+            }
+
+            NodeKind::Tuple
+        } else {
+            NodeKind::Parenthesis
+        };
+
+        if self.assert(Type::CloseParenthesis).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected ) to close parenthesis.",
+            );
+        }
+
+        self.wrap(mark, kind);
+        true
+    }
+
+    /// Parses a record literal `{ a: 1 }` or a functional record update `{ r with a: 1 }`.
+    fn parse_record_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::OpenBrace);
+
+        let is_literal = self.peek_type(0) == Some(&Type::CloseBrace)
+            || (self.peek_type(0) == Some(&Type::Identifier)
+                && self.peek_type(1) == Some(&Type::Colon));
+
+        let kind = if is_literal {
+            self.parse_record_fields(holder);
+            NodeKind::Record
+        } else {
+            self.parse_expression(0, holder);
+
+            if self.assert(Type::WithKeyword).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected with after record to update.",
+                );
+            }
+
+            self.parse_record_fields(holder);
+            NodeKind::RecordUpdate
+        };
+
+        if self.assert(Type::CloseBrace).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected } to close record.",
+            );
+        }
+
+        self.wrap(mark, kind);
+        true
+    }
+
+    fn parse_record_fields(&mut self, holder: &mut DiagnosticHolder) {
+        loop {
+            let field = self.mark();
+
+            if self.assert(Type::Identifier).is_none() {
+                break;
+            }
+
+            if self.assert(Type::Colon).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected : after field name.",
+                );
+            }
+
+            self.parse_expression(0, holder);
+            self.wrap(field, NodeKind::RecordField);
+
+            if self.assert(Type::Comma).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn parse_match_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::MatchKeyword);
+        self.parse_expression(0, holder);
+
+        if self.assert(Type::WithKeyword).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected with after matched expression.",
+            );
+        }
+
+        // The bar before the first arm is optional.
+        let _ = self.assert(Type::VerticalBar);
+
+        loop {
+            let arm = self.mark();
+            self.parse_pattern(holder);
+
+            if self.assert(Type::Arrow).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected -> after pattern.",
+                );
+            }
+
+            self.parse_expression(0, holder);
+            self.wrap(arm, NodeKind::MatchArm);
+
+            if self.assert(Type::VerticalBar).is_none() {
                 break;
             }
         }
 
-        left
+        self.wrap(mark, NodeKind::Match);
+        true
     }
 
-    fn parse_literal_expression(&mut self, holder: &mut DiagnosticHolder) -> Option<Expression> {
-        let current = self.tokens.get(self.position);
+    fn parse_if_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::IfKeyword);
+        self.parse_expression(0, holder);
 
-        if let Some(token) = current {
-            match token.token_type {
-                Type::OpenParenthesis => {
-                    std::mem::drop(self.assert(Type::OpenParenthesis));
-                    let expression = self.parse_expression(0, holder);
-                    std::mem::drop(self.assert(Type::CloseParenthesis));
+        if self.assert(Type::ThenKeyword).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected then after condition.",
+            );
+        }
 
-                    Some(Expression::Parenthesis(Box::new(expression)))
+        self.parse_expression(0, holder);
+
+        if self.assert(Type::ElseKeyword).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected else after then branch.",
+            );
+        }
+
+        self.parse_expression(0, holder);
+        self.wrap(mark, NodeKind::If);
+        true
+    }
+
+    fn parse_let_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::LetKeyword);
+        let _ = self.assert(Type::RecKeyword);
+
+        if self.assert(Type::Identifier).is_none() {
+            self.error(holder, "Unexpected parsing error: Expected binding name.");
+        }
+
+        while self.assert(Type::Identifier).is_some() {}
+
+        if self.assert(Type::Equal).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected = after binding name.",
+            );
+        }
+
+        self.parse_expression(0, holder);
+
+        if self.assert(Type::InKeyword).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected in after let binding.",
+            );
+        }
+
+        self.parse_expression(0, holder);
+        self.wrap(mark, NodeKind::Let);
+        true
+    }
+
+    // Pattern and type parsers read exactly one node as well, and return whether it is a bare
+    // name which arguments may follow, e.g. `Some x` or `Maybe number`.
+
+    fn parse_pattern(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let name = self.parse_pattern_atom(holder);
+
+        if name
+            && self
+                .peek_type(0)
+                .is_some_and(|token_type| token_type.is_argument_start())
+        {
+            while self
+                .peek_type(0)
+                .is_some_and(|token_type| token_type.is_argument_start())
+            {
+                self.parse_pattern_atom(holder);
+            }
+
+            self.wrap(mark, NodeKind::ConstructorPattern);
+            return false;
+        }
+
+        name
+    }
+
+    fn parse_pattern_atom(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let token = match self.peek(0) {
+            Some(token) => token.to_owned(),
+            None => {
+                self.error(holder, "Unexpected parsing error: Expected pattern.");
+                self.wrap(mark, NodeKind::Error);
+                return false;
+            }
+        };
+
+        self.advance();
+
+        let kind = match token.token_type {
+            Type::Identifier if token.literal == "_" => NodeKind::WildcardPattern,
+            Type::Identifier if token.literal == "true" || token.literal == "false" => {
+                NodeKind::LiteralPattern
+            }
+            Type::Identifier => {
+                self.qualify(mark);
+                return true;
+            }
+            Type::Number | Type::Literal => NodeKind::LiteralPattern,
+            Type::Minus => match self.assert(Type::Number) {
+                Some(_) => NodeKind::LiteralPattern,
+                None => {
+                    self.error(holder, "Unexpected parsing error: Expected number after -.");
+                    NodeKind::Error
                 }
-                Type::Number => {
-                    let number_token = self.assert(Type::Number);
+            },
+            Type::OpenParenthesis => {
+                let name = self.parse_pattern(holder);
+                let mut tuple = false;
 
-                    if let Some(token) = number_token {
-                        Some(Expression::Number(Box::new(token.to_owned())))
-                    } else {
-                        holder.error("Unexpected parsing error: Expected integer.");
-                        None
-                    }
+                while self.assert(Type::Comma).is_some() {
+                    self.parse_pattern(holder);
+                    tuple = true;
                 }
-                Type::Literal => {
-                    let string_literal = self.assert(Type::Literal);
 
-                    if let Some(token) = string_literal {
-                        Some(Expression::Literal(Box::new(token.to_owned())))
-                    } else {
-                        holder.error("Unexpected parsing error: Expected string literal.");
-                        None
-                    }
+                if self.assert(Type::CloseParenthesis).is_none() {
+                    self.error(
+                        holder,
+                        "Unexpected parsing error: Expected ) to close pattern.",
+                    );
+                }
+
+                if tuple {
+                    NodeKind::TuplePattern
+                } else {
+                    self.wrap(mark, NodeKind::ParenthesizedPattern);
+                    return name;
                 }
-                Type::Identifier => {
-                    let identifier_token = self.assert(Type::Identifier);
+            }
+            Type::OpenBracket => {
+                if self.assert(Type::CloseBracket).is_none() {
+                    loop {
+                        self.parse_pattern(holder);
 
-                    if let Some(token) = identifier_token {
-                        match token.literal.as_str() {
-                            "true" | "false" => Some(Expression::Bool(Box::new(token.to_owned()))),
-                            _ => Some(Expression::Identifier(Box::new(token.to_owned()))),
+                        if self.assert(Type::Comma).is_none() {
+                            break;
                         }
-                    } else {
-                        holder
-                            .error("Unexpected parsing error: Expected identifier / type literal.");
-                        None
                     }
+
+                    if self.assert(Type::CloseBracket).is_none() {
+                        self.error(
+                            holder,
+                            "Unexpected parsing error: Expected ] to close pattern.",
+                        );
+                    }
+                }
+
+                NodeKind::ListPattern
+            }
+            _ => {
+                holder.at(Some(token.span.clone()), |holder| {
+                    holder.error(&format!(
+                        "Unexpected parsing error: Unexpected token {:} in pattern",
+                        token.literal
+                    ))
+                });
+                NodeKind::Error
+            }
+        };
+
+        self.wrap(mark, kind);
+        false
+    }
+
+    /// Parses `type Name parameters = Constructor fields | Constructor fields`.
+    fn parse_type_declaration(&mut self, holder: &mut DiagnosticHolder) {
+        let mark = self.mark();
+        let _ = self.assert(Type::TypeKeyword);
+
+        if self.assert(Type::Identifier).is_none() {
+            self.error(holder, "Unexpected parsing error: Expected type name.");
+        }
+
+        while self.assert(Type::Identifier).is_some() {}
+
+        if self.assert(Type::Equal).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected = after type name.",
+            );
+        }
+
+        let _ = self.assert(Type::VerticalBar);
+
+        loop {
+            let constructor = self.mark();
+
+            if self.assert(Type::Identifier).is_some() {
+                while self
+                    .peek_type(0)
+                    .is_some_and(|token_type| token_type.is_type_start())
+                {
+                    self.parse_type_atom(holder);
+                }
+
+                self.wrap(constructor, NodeKind::Constructor);
+            } else {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected constructor name.",
+                );
+            }
+
+            if self.assert(Type::VerticalBar).is_none() {
+                break;
+            }
+        }
+
+        self.wrap(mark, NodeKind::TypeDeclaration);
+    }
+
+    fn parse_type_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let name = self.parse_type_application(holder);
+
+        if self.assert(Type::Arrow).is_some() {
+            self.parse_type_expression(holder);
+            self.wrap(mark, NodeKind::FunctionType);
+            return false;
+        }
+
+        name
+    }
+
+    /// Parses type arguments written by juxtaposition, e.g. `Maybe number`, as in type declarations.
+    fn parse_type_application(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let name = self.parse_type_atom(holder);
+
+        if name
+            && self
+                .peek_type(0)
+                .is_some_and(|token_type| token_type.is_type_start())
+        {
+            while self
+                .peek_type(0)
+                .is_some_and(|token_type| token_type.is_type_start())
+            {
+                self.parse_type_atom(holder);
+            }
+
+            self.wrap(mark, NodeKind::TypeApplication);
+            return false;
+        }
+
+        name
+    }
+
+    fn parse_type_atom(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+
+        if self.assert(Type::Identifier).is_some() {
+            self.qualify(mark);
+
+            let name = self.assert(Type::LessThan).is_none();
+
+            if !name {
+                loop {
+                    self.parse_type_expression(holder);
+
+                    if self.assert(Type::Comma).is_none() {
+                        break;
+                    }
+                }
+
+                if self.assert(Type::GreaterThan).is_none() {
+                    self.error(
+                        holder,
+                        "Unexpected parsing error: Expected > to close type arguments.",
+                    );
+                }
+            }
+
+            self.wrap(mark, NodeKind::NamedType);
+            name
+        } else if self.assert(Type::OpenParenthesis).is_some() {
+            let name = self.parse_type_expression(holder);
+            let mut tuple = false;
+
+            while self.assert(Type::Comma).is_some() {
+                self.parse_type_expression(holder);
+                tuple = true;
+            }
+
+            if self.assert(Type::CloseParenthesis).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected ) to close type.",
+                );
+            }
+
+            if tuple {
+                self.wrap(mark, NodeKind::TupleType);
+                false
+            } else {
+                self.wrap(mark, NodeKind::ParenthesizedType);
+                name
+            }
+        } else if self.assert(Type::OpenBrace).is_some() {
+            loop {
+                let field = self.mark();
+
+                if self.assert(Type::Identifier).is_none() {
+                    break;
+                }
+
+                if self.assert(Type::Colon).is_none() {
+                    self.error(
+                        holder,
+                        "Unexpected parsing error: Expected : after field name.",
+                    );
                 }
-                _ => panic!("Unknown expression: {:?}", token),
+
+                self.parse_type_expression(holder);
+                self.wrap(field, NodeKind::RecordFieldType);
+
+                if self.assert(Type::Comma).is_none() {
+                    break;
+                }
+            }
+
+            if self.assert(Type::CloseBrace).is_none() {
+                self.error(
+                    holder,
+                    "Unexpected parsing error: Expected } to close record type.",
+                );
             }
+
+            self.wrap(mark, NodeKind::RecordType);
+            false
         } else {
-            None
+            self.error(holder, "Unexpected parsing error: Expected type.");
+            self.advance();
+            // An erroneous type is an empty name, which arguments may still follow.
+            self.wrap(mark, NodeKind::Error);
+            true
         }
     }
+
+    fn parse_lambda_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let _ = self.assert(Type::FnKeyword);
+        let mut parameters = 0;
+
+        while self.assert(Type::Identifier).is_some() {
+            parameters += 1;
+        }
+
+        if parameters == 0 {
+            self.error(holder, "Unexpected parsing error: Expected parameter name.");
+        }
+
+        if self.assert(Type::Arrow).is_none() {
+            self.error(
+                holder,
+                "Unexpected parsing error: Expected -> after parameters.",
+            );
+        }
+
+        self.parse_expression(0, holder);
+        self.wrap(mark, NodeKind::Lambda);
+        true
+    }
+
+    fn parse_literal_expression(&mut self, holder: &mut DiagnosticHolder) -> bool {
+        let mark = self.mark();
+        let token = match self.peek(0) {
+            Some(token) => token.to_owned(),
+            None => {
+                self.wrap(mark, NodeKind::Error);
+                return false;
+            }
+        };
+
+        match token.token_type {
+            Type::OpenParenthesis => return self.parse_parenthesis_expression(holder),
+            Type::OpenBracket => return self.parse_list_expression(holder),
+            Type::OpenBrace => return self.parse_record_expression(holder),
+            Type::FnKeyword => return self.parse_lambda_expression(holder),
+            Type::MatchKeyword => return self.parse_match_expression(holder),
+            Type::IfKeyword => return self.parse_if_expression(holder),
+            Type::LetKeyword => return self.parse_let_expression(holder),
+            Type::TypeKeyword => {
+                self.parse_type_declaration(holder);
+
+                if self.assert(Type::InKeyword).is_none() {
+                    self.error(
+                        holder,
+                        "Unexpected parsing error: Expected in after type declaration.",
+                    );
+                }
+
+                self.parse_expression(0, holder);
+                self.wrap(mark, NodeKind::TypeDeclarationExpression);
+            }
+            Type::Number | Type::Literal => {
+                self.advance();
+                self.wrap(mark, NodeKind::Literal);
+            }
+            Type::Identifier => {
+                self.advance();
+
+                match token.literal.as_str() {
+                    "true" | "false" => self.wrap(mark, NodeKind::Literal),
+                    _ => self.qualify(mark),
+                }
+            }
+            // Separators are left for the program parser to resume from.
+            Type::Newline => {
+                self.error(holder, "Unexpected parsing error: Unexpected end of line");
+                self.wrap(mark, NodeKind::Error);
+                return false;
+            }
+            Type::Semicolon => {
+                self.error(holder, "Unexpected parsing error: Unexpected token ;");
+                self.wrap(mark, NodeKind::Error);
+                return false;
+            }
+            _ => {
+                self.error(
+                    holder,
+                    &format!(
+                        "Unexpected parsing error: Unexpected token {:}",
+                        token.literal
+                    ),
+                );
+                self.advance();
+                self.wrap(mark, NodeKind::Error);
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 pub trait SyntaxNode<T>: Debug + PartialEq
@@ -215,7 +1016,102 @@ pub struct Tree {
     pub root_expression: Option<Expression>,
 }
 
-#[derive(Debug, Clone, PartialEq, ToString)]
+/// A source file made of top-level declarations and at most one entry expression.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub struct Program {
+    pub declarations: Vec<Declaration>,
+    pub entry: Option<Expression>,
+}
+
+impl Program {
+    /// Names of the modules imported by this program.
+    pub fn imports(&self) -> Vec<String> {
+        self.declarations
+            .iter()
+            .filter_map(|declaration| match declaration {
+                Declaration::Import(module) => Some(module.literal.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub enum Declaration {
+    /// `name parameters = value`, where parameters are already turned into a lambda.
+    Definition(Token, Option<Expression>),
+    Type(TypeDeclaration),
+    /// `import module`, with nested modules written as `a::b`.
+    Import(Token),
+    /// A definition or type visible to the modules importing this one.
+    Export(Box<Declaration>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub struct TypeDeclaration {
+    pub name: Token,
+    pub parameters: Vec<Token>,
+    pub constructors: Vec<(Token, Vec<TypeExpression>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub enum TypeExpression {
+    Named(Token, Vec<TypeExpression>),
+    Function(Box<TypeExpression>, Box<TypeExpression>),
+    Tuple(Vec<TypeExpression>),
+    Record(Vec<(Token, TypeExpression)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
+pub enum Pattern {
+    Wildcard,
+    /// Either a variable binding or a constructor without fields, decided by the binder.
+    Identifier(Token),
+    Constructor(Token, Vec<Pattern>),
+    Number(Token),
+    Literal(Token),
+    Bool(Token),
+    Tuple(Vec<Pattern>),
+    List(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Byte range from the first to the last token of the pattern, or `None` if it has none,
+    /// e.g. a wildcard.
+    pub fn span(&self) -> Option<Range<usize>> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+
+        Some(tokens.first()?.span.start..tokens.last()?.span.end)
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token>) {
+        match self {
+            Pattern::Wildcard => {}
+            Pattern::Identifier(token)
+            | Pattern::Number(token)
+            | Pattern::Literal(token)
+            | Pattern::Bool(token) => tokens.push(token),
+            Pattern::Constructor(token, arguments) => {
+                tokens.push(token);
+                arguments
+                    .iter()
+                    .for_each(|argument| argument.collect_tokens(tokens));
+            }
+            Pattern::Tuple(elements) | Pattern::List(elements) => elements
+                .iter()
+                .for_each(|element| element.collect_tokens(tokens)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Display)]
+#[cfg_attr(feature = "playground", derive(serde::Serialize))]
 pub enum Expression {
     Identifier(Box<Token>),
     Literal(Box<Token>),
@@ -238,6 +1134,154 @@ pub enum Expression {
     Division(Box<Option<Expression>>, Box<Option<Expression>>),
     Remainder(Box<Option<Expression>>, Box<Option<Expression>>),
     Parenthesis(Box<Option<Expression>>),
+    List(Vec<Option<Expression>>),
+    Index(Box<Option<Expression>>, Box<Option<Expression>>),
+    Lambda(Vec<Token>, Box<Option<Expression>>),
+    Call(Box<Option<Expression>>, Box<Option<Expression>>),
+    Tuple(Vec<Option<Expression>>),
+    Record(Vec<(Token, Option<Expression>)>),
+    RecordUpdate(Box<Option<Expression>>, Vec<(Token, Option<Expression>)>),
+    Field(Box<Option<Expression>>, Box<Token>),
+    TypeDeclaration(Box<TypeDeclaration>, Box<Option<Expression>>),
+    Match(Box<Option<Expression>>, Vec<(Pattern, Option<Expression>)>),
+    If(
+        Box<Option<Expression>>,
+        Box<Option<Expression>>,
+        Box<Option<Expression>>,
+    ),
+    Let(Box<Token>, Box<Option<Expression>>, Box<Option<Expression>>),
+    LetRec(Box<Token>, Box<Option<Expression>>, Box<Option<Expression>>),
+}
+
+impl Expression {
+    /// Byte range from the first to the last token of the expression. Keywords, operators and
+    /// brackets before its first or after its last operand are left out, e.g. the `let` of a
+    /// `let` expression.
+    pub fn span(&self) -> Option<Range<usize>> {
+        Some(self.first_token()?.span.start..self.last_token()?.span.end)
+    }
+
+    fn first_token(&self) -> Option<&Token> {
+        fn first<'a>(expressions: &[&'a Option<Expression>]) -> Option<&'a Token> {
+            expressions
+                .iter()
+                .find_map(|expression| expression.as_ref()?.first_token())
+        }
+
+        match self {
+            Expression::Identifier(token)
+            | Expression::Literal(token)
+            | Expression::Bool(token)
+            | Expression::Number(token) => Some(token),
+            Expression::Positive(operand)
+            | Expression::Negative(operand)
+            | Expression::NOT(operand)
+            | Expression::Parenthesis(operand) => first(&[operand]),
+            Expression::AND(left, right)
+            | Expression::OR(left, right)
+            | Expression::Equal(left, right)
+            | Expression::BangEqual(left, right)
+            | Expression::Greater(left, right)
+            | Expression::GreaterEqual(left, right)
+            | Expression::Less(left, right)
+            | Expression::LessEqual(left, right)
+            | Expression::Addition(left, right)
+            | Expression::Subtraction(left, right)
+            | Expression::Multiplication(left, right)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right)
+            | Expression::Index(left, right)
+            | Expression::Call(left, right) => first(&[left, right]),
+            Expression::List(elements) | Expression::Tuple(elements) => elements
+                .iter()
+                .find_map(|element| element.as_ref()?.first_token()),
+            Expression::Lambda(parameters, body) => parameters.first().or_else(|| first(&[body])),
+            Expression::Record(fields) => fields.first().map(|(name, _)| name),
+            Expression::RecordUpdate(record, fields) => {
+                first(&[record]).or_else(|| fields.first().map(|(name, _)| name))
+            }
+            Expression::Field(record, name) => first(&[record]).or(Some(name)),
+            Expression::TypeDeclaration(declaration, _) => Some(&declaration.name),
+            Expression::Match(scrutinee, arms) => first(&[scrutinee]).or_else(|| {
+                arms.iter()
+                    .find_map(|(_, body)| body.as_ref()?.first_token())
+            }),
+            Expression::If(condition, then_branch, else_branch) => {
+                first(&[condition, then_branch, else_branch])
+            }
+            Expression::Let(name, _, _) | Expression::LetRec(name, _, _) => Some(name),
+        }
+    }
+
+    fn last_token(&self) -> Option<&Token> {
+        fn last<'a>(expressions: &[&'a Option<Expression>]) -> Option<&'a Token> {
+            expressions
+                .iter()
+                .rev()
+                .find_map(|expression| expression.as_ref()?.last_token())
+        }
+
+        match self {
+            Expression::Identifier(token)
+            | Expression::Literal(token)
+            | Expression::Bool(token)
+            | Expression::Number(token) => Some(token),
+            Expression::Positive(operand)
+            | Expression::Negative(operand)
+            | Expression::NOT(operand)
+            | Expression::Parenthesis(operand) => last(&[operand]),
+            Expression::AND(left, right)
+            | Expression::OR(left, right)
+            | Expression::Equal(left, right)
+            | Expression::BangEqual(left, right)
+            | Expression::Greater(left, right)
+            | Expression::GreaterEqual(left, right)
+            | Expression::Less(left, right)
+            | Expression::LessEqual(left, right)
+            | Expression::Addition(left, right)
+            | Expression::Subtraction(left, right)
+            | Expression::Multiplication(left, right)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right)
+            | Expression::Index(left, right)
+            | Expression::Call(left, right) => last(&[left, right]),
+            Expression::List(elements) | Expression::Tuple(elements) => elements
+                .iter()
+                .rev()
+                .find_map(|element| element.as_ref()?.last_token()),
+            Expression::Lambda(parameters, body) => last(&[body]).or(parameters.last()),
+            Expression::Record(fields) => fields.last().map(|(name, value)| {
+                value
+                    .as_ref()
+                    .and_then(Expression::last_token)
+                    .unwrap_or(name)
+            }),
+            Expression::RecordUpdate(record, fields) => fields
+                .last()
+                .map(|(name, value)| {
+                    value
+                        .as_ref()
+                        .and_then(Expression::last_token)
+                        .unwrap_or(name)
+                })
+                .or_else(|| last(&[record])),
+            Expression::Field(_, name) => Some(name),
+            Expression::TypeDeclaration(declaration, body) => {
+                last(&[body]).or(Some(&declaration.name))
+            }
+            Expression::Match(scrutinee, arms) => arms
+                .iter()
+                .rev()
+                .find_map(|(_, body)| body.as_ref()?.last_token())
+                .or_else(|| last(&[scrutinee])),
+            Expression::If(condition, then_branch, else_branch) => {
+                last(&[condition, then_branch, else_branch])
+            }
+            Expression::Let(name, value, body) | Expression::LetRec(name, value, body) => {
+                last(&[value, body]).or(Some(name))
+            }
+        }
+    }
 }
 
 impl SyntaxNode<Expression> for Expression {
@@ -260,15 +1304,64 @@ impl SyntaxNode<Expression> for Expression {
             Expression::Division(left, right) => vec![left, right],
             Expression::Remainder(left, right) => vec![left, right],
             Expression::Parenthesis(expression) => vec![expression],
+            Expression::List(elements) => elements.into_iter().map(Box::new).collect(),
+            Expression::Index(list, index) => vec![list, index],
+            Expression::Lambda(_, body) => vec![body],
+            Expression::Call(callee, argument) => vec![callee, argument],
+            Expression::Tuple(elements) => elements.into_iter().map(Box::new).collect(),
+            Expression::Record(fields) => fields
+                .into_iter()
+                .map(|(_, value)| Box::new(value))
+                .collect(),
+            Expression::RecordUpdate(record, fields) => std::iter::once(record)
+                .chain(fields.into_iter().map(|(_, value)| Box::new(value)))
+                .collect(),
+            Expression::Field(record, _) => vec![record],
+            Expression::TypeDeclaration(_, body) => vec![body],
+            Expression::Match(scrutinee, arms) => std::iter::once(scrutinee)
+                .chain(arms.into_iter().map(|(_, body)| Box::new(body)))
+                .collect(),
+            Expression::If(condition, then_branch, else_branch) => {
+                vec![condition, then_branch, else_branch]
+            }
+            Expression::Let(_, value, body) | Expression::LetRec(_, value, body) => {
+                vec![value, body]
+            }
             _ => vec![],
         }
     }
 
     fn as_string(&self) -> String {
         match self {
-            Expression::Literal(token) => format!("{}({})", self.to_string(), token.literal),
-            Expression::Bool(token) => format!("{}({})", self.to_string(), token.literal),
-            Expression::Number(token) => format!("{}({})", self.to_string(), token.literal),
+            Expression::Literal(token) => format!("{}({})", self, token.literal),
+            Expression::Bool(token) => format!("{}({})", self, token.literal),
+            Expression::Number(token) => format!("{}({})", self, token.literal),
+            Expression::Identifier(token) => format!("{}({})", self, token.literal),
+            Expression::Field(_, token) => format!("{}({})", self, token.literal),
+            Expression::Let(token, _, _) | Expression::LetRec(token, _, _) => {
+                format!("{}({})", self, token.literal)
+            }
+            Expression::TypeDeclaration(declaration, _) => {
+                format!("{}({})", self, declaration.name.literal)
+            }
+            Expression::Record(fields) | Expression::RecordUpdate(_, fields) => format!(
+                "{}({})",
+                self,
+                fields
+                    .iter()
+                    .map(|(name, _)| name.literal.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ),
+            Expression::Lambda(parameters, _) => format!(
+                "{}({})",
+                self,
+                parameters
+                    .iter()
+                    .map(|parameter| parameter.literal.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ),
             _ => self.to_string(),
         }
     }
@@ -0,0 +1,52 @@
+use crate::binder::BoundProgram;
+use crate::compilation::Compilation;
+use crate::cst;
+use crate::utils::set_panic_hook;
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+/// Compiles and runs `source` for the online playground, returning a JSON object with its
+/// `tokens`, `syntaxTree`, `boundTree`, `diagnostics` and `result`. The bound tree leaves out
+/// the prelude and imported modules, and the result is `null` when there are errors.
+#[wasm_bindgen]
+pub fn evaluate(source: &str) -> String {
+    set_panic_hook();
+
+    let mut compilation = Compilation::new(source.to_string());
+    let tree = cst::parse(source, &mut compilation.holder);
+    let program = cst::lower_program(&tree);
+    let syntax_tree = json!(program);
+    let bound_program = compilation.bind_program(program);
+    let bound_tree = json!(BoundProgram {
+        definitions: bound_program
+            .definitions
+            .iter()
+            .filter(|(name, _)| !name.contains("::"))
+            .cloned()
+            .collect(),
+        entry: bound_program.entry.clone(),
+    });
+    let result = compilation.eval_program(bound_program).to_string();
+
+    let tokens = tree
+        .leaves()
+        .iter()
+        .filter_map(|leaf| leaf.token())
+        .map(|token| json!({ "literal": token.literal, "type": token.token_type }))
+        .collect::<Vec<_>>();
+    let diagnostics = compilation
+        .holder
+        .diagonistic_units
+        .iter()
+        .map(|unit| json!({ "severity": unit.severity(), "message": unit.message() }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "tokens": tokens,
+        "syntaxTree": syntax_tree,
+        "boundTree": bound_tree,
+        "diagnostics": diagnostics,
+        "result": Some(result).filter(|_| compilation.holder.success()),
+    })
+    .to_string()
+}
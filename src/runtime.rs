@@ -1,8 +1,16 @@
-use crate::binder::{BoundExpression, BoundType};
+use crate::binder::{BoundExpression, BoundPattern, BoundProgram};
+use crate::bytecode::CompiledFunction;
 use crate::diagnostic::DiagnosticHolder;
+use crate::host::{HostEnvironment, HostFunction};
+use crate::lexer::is_number_literal;
 use std::any::Any;
-use std::fmt::Display;
-use std::ops::Deref;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use strum_macros::{Display, EnumIter, EnumString};
 
 pub trait Result: Any + Display {
     fn as_any(&self) -> &dyn Any;
@@ -50,142 +58,1303 @@ impl Result for f64 {
     }
 }
 
+impl Result for Value {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_display(&self) -> &dyn Display {
+        self
+    }
+}
+
+pub type EvalResult = std::result::Result<Value, RuntimeError>;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Number(f64),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+    Record(BTreeMap<String, Value>),
+    /// A value built by a constructor of a user-defined sum type.
+    Data(String, Vec<Value>),
+    Function(Rc<Function>),
+}
+
+impl Value {
+    pub fn some(value: Value) -> Self {
+        Value::Data("Some".to_string(), vec![value])
+    }
+
+    pub fn none() -> Self {
+        Value::Data("None".to_string(), vec![])
+    }
+
+    pub fn ok(value: Value) -> Self {
+        Value::Data("Ok".to_string(), vec![value])
+    }
+
+    pub fn err(error: Value) -> Self {
+        Value::Data("Err".to_string(), vec![error])
+    }
+
+    /// Unwraps primitive values so they can be downcast the same way as before compound values existed.
+    pub fn into_result(self) -> Box<dyn Result> {
+        match self {
+            Value::String(string) => Box::new(string),
+            Value::Bool(bool) => Box::new(bool),
+            Value::Number(number) => Box::new(number),
+            value => Box::new(value),
+        }
+    }
+
+    pub fn equals(&self, other: &Value) -> bool {
+        let all_equal = |left: &[Value], right: &[Value]| {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|(left, right)| left.equals(right))
+        };
+
+        match (self, other) {
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::List(left), Value::List(right)) | (Value::Tuple(left), Value::Tuple(right)) => {
+                all_equal(left, right)
+            }
+            (Value::Record(left), Value::Record(right)) => {
+                left.len() == right.len()
+                    && left.iter().all(|(name, value)| {
+                        right.get(name).is_some_and(|other| value.equals(other))
+                    })
+            }
+            (Value::Data(left_name, left), Value::Data(right_name, right)) => {
+                left_name == right_name && all_equal(left, right)
+            }
+            _ => false,
+        }
+    }
+
+    fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => {
+                left.partial_cmp(right).unwrap_or(Ordering::Equal)
+            }
+            (Value::String(left), Value::String(right)) => left.cmp(right),
+            (Value::Bool(left), Value::Bool(right)) => left.cmp(right),
+            (Value::List(left), Value::List(right)) | (Value::Tuple(left), Value::Tuple(right)) => {
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| left.compare(right))
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or_else(|| left.len().cmp(&right.len()))
+            }
+            (Value::Data(left_name, left), Value::Data(right_name, right)) => {
+                if left_name != right_name {
+                    return left_name.cmp(right_name);
+                }
+
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| left.compare(right))
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            }
+            _ => Ordering::Equal,
+        }
+    }
+
+    fn fmt_sequence(elements: &[Value], f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, element) in elements.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+
+            element.fmt_nested(f)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn fmt_nested(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{:?}", string),
+            _ => write!(f, "{:}", self),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{:}", string),
+            Value::Bool(bool) => write!(f, "{:}", bool),
+            Value::Number(number) => write!(f, "{:}", number),
+            Value::List(elements) => {
+                write!(f, "[")?;
+                Value::fmt_sequence(elements, f)?;
+                write!(f, "]")
+            }
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                Value::fmt_sequence(elements, f)?;
+                write!(f, ")")
+            }
+            Value::Record(fields) if fields.is_empty() => write!(f, "{{}}"),
+            Value::Record(fields) => {
+                write!(f, "{{ ")?;
+
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: ", name)?;
+                    value.fmt_nested(f)?;
+                }
+
+                write!(f, " }}")
+            }
+            Value::Data(name, arguments) => {
+                write!(f, "{}", name)?;
+
+                for argument in arguments {
+                    match argument {
+                        Value::Data(_, nested) if !nested.is_empty() => {
+                            write!(f, " ({:})", argument)?
+                        }
+                        _ => {
+                            write!(f, " ")?;
+                            argument.fmt_nested(f)?;
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            Value::Function(function) => match function.as_ref() {
+                Function::Closure { .. } | Function::Compiled { .. } => write!(f, "<function>"),
+                Function::Constructor { name, .. } => write!(f, "<constructor {}>", name),
+                Function::Builtin { builtin, .. } => write!(f, "<builtin {}>", builtin),
+                Function::Host { function, .. } => write!(f, "<host {}>", function.name),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Function {
+    Closure {
+        parameter: String,
+        body: Rc<Option<BoundExpression>>,
+        environment: Environment,
+        /// The name a recursive closure refers to itself by, bound again on every call.
+        name: Option<String>,
+    },
+    /// A closure of compiled code run by [`crate::vm::Vm`], with the values it captured.
+    Compiled {
+        function: Rc<CompiledFunction>,
+        captures: Vec<Value>,
+    },
+    /// A built-in function together with the arguments it has been partially applied to.
+    Builtin {
+        builtin: Builtin,
+        arguments: Vec<Value>,
+    },
+    /// A function registered by the embedder together with the arguments it has been partially
+    /// applied to.
+    Host {
+        function: Rc<HostFunction>,
+        arguments: Vec<Value>,
+    },
+    /// A constructor of a sum type waiting for the rest of its fields.
+    Constructor {
+        name: String,
+        arity: usize,
+        arguments: Vec<Value>,
+    },
+}
+
+impl Function {
+    /// Applies a function implemented outside of Collage code, returning the function again
+    /// until it has received all of its arguments.
+    pub fn apply_native(&self, argument: Value, machine: &dyn Machine) -> EvalResult {
+        match self {
+            Function::Builtin { builtin, arguments } => {
+                let mut arguments = arguments.clone();
+                arguments.push(argument);
+
+                if arguments.len() == builtin.arity() {
+                    builtin.call(arguments, machine)
+                } else {
+                    Ok(Value::Function(Rc::new(Function::Builtin {
+                        builtin: *builtin,
+                        arguments,
+                    })))
+                }
+            }
+            Function::Host {
+                function,
+                arguments,
+            } => {
+                let mut arguments = arguments.clone();
+                arguments.push(argument);
+
+                if arguments.len() == function.arity {
+                    function.call(&arguments)
+                } else {
+                    Ok(Value::Function(Rc::new(Function::Host {
+                        function: function.clone(),
+                        arguments,
+                    })))
+                }
+            }
+            Function::Constructor {
+                name,
+                arity,
+                arguments,
+            } => {
+                let mut arguments = arguments.clone();
+                arguments.push(argument);
+
+                if arguments.len() == *arity {
+                    Ok(Value::Data(name.clone(), arguments))
+                } else {
+                    Ok(Value::Function(Rc::new(Function::Constructor {
+                        name: name.clone(),
+                        arity: *arity,
+                        arguments,
+                    })))
+                }
+            }
+            Function::Closure { .. } | Function::Compiled { .. } => {
+                Err(RuntimeError::NotCallable("<function>".to_string()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Builtin {
+    Map,
+    Filter,
+    Fold,
+    Zip,
+    Range,
+    Pow,
+    Length,
+    Concat,
+    Reverse,
+    Sort,
+    Get,
+    ParseNumber,
+    Chars,
+    Join,
+    Split,
+    OptionMap,
+    OptionAndThen,
+    OptionUnwrapOr,
+    ResultMap,
+    ResultAndThen,
+    ResultUnwrapOr,
+}
+
+impl Builtin {
+    pub fn arity(&self) -> usize {
+        match self {
+            Builtin::Length
+            | Builtin::Reverse
+            | Builtin::Sort
+            | Builtin::ParseNumber
+            | Builtin::Chars => 1,
+            Builtin::Map
+            | Builtin::Filter
+            | Builtin::Range
+            | Builtin::Pow
+            | Builtin::Concat
+            | Builtin::Get
+            | Builtin::Join
+            | Builtin::Split
+            | Builtin::OptionMap
+            | Builtin::OptionAndThen
+            | Builtin::OptionUnwrapOr
+            | Builtin::ResultMap
+            | Builtin::ResultAndThen
+            | Builtin::ResultUnwrapOr => 2,
+            Builtin::Fold | Builtin::Zip => 3,
+        }
+    }
+
+    fn call(self, arguments: Vec<Value>, machine: &dyn Machine) -> EvalResult {
+        match self {
+            Builtin::Map => Ok(Value::List(
+                expect_list(&arguments[1])
+                    .iter()
+                    .map(|element| machine.apply(&arguments[0], element.clone()))
+                    .collect::<std::result::Result<Vec<Value>, RuntimeError>>()?,
+            )),
+            Builtin::Filter => {
+                let mut filtered = vec![];
+
+                for element in expect_list(&arguments[1]) {
+                    if expect_bool(&machine.apply(&arguments[0], element.clone())?) {
+                        filtered.push(element.clone());
+                    }
+                }
+
+                Ok(Value::List(filtered))
+            }
+            Builtin::Fold => {
+                let mut accumulator = arguments[1].clone();
+
+                for element in expect_list(&arguments[2]) {
+                    let partial = machine.apply(&arguments[0], accumulator)?;
+                    accumulator = machine.apply(&partial, element.clone())?;
+                }
+
+                Ok(accumulator)
+            }
+            Builtin::Zip => {
+                let mut zipped = vec![];
+
+                for (left, right) in expect_list(&arguments[1])
+                    .iter()
+                    .zip(expect_list(&arguments[2]))
+                {
+                    let partial = machine.apply(&arguments[0], left.clone())?;
+                    zipped.push(machine.apply(&partial, right.clone())?);
+                }
+
+                Ok(Value::List(zipped))
+            }
+            Builtin::Range => {
+                let start = expect_number(&arguments[0]);
+                let end = expect_number(&arguments[1]);
+
+                // Beyond 2^53, adding 1 to a number may leave it unchanged.
+                if !(start.abs() <= MAX_RANGE_BOUND && end.abs() <= MAX_RANGE_BOUND) {
+                    return Err(RuntimeError::InvalidRange(start, end));
+                }
+
+                let size = if end > start {
+                    (end - start).ceil() as usize
+                } else {
+                    0
+                };
+                let mut range = vec![];
+
+                machine.budget().check_size(size)?;
+
+                for index in 0..size {
+                    machine.budget().step()?;
+                    range.push(Value::Number(start + index as f64));
+                }
+
+                Ok(Value::List(range))
+            }
+            Builtin::Pow => machine.arithmetic().check(
+                expect_number(&arguments[0]).powf(expect_number(&arguments[1])),
+                None,
+            ),
+            Builtin::Length => Ok(Value::Number(expect_list(&arguments[0]).len() as f64)),
+            Builtin::Concat => {
+                machine.budget().check_size(
+                    expect_list(&arguments[0]).len() + expect_list(&arguments[1]).len(),
+                )?;
+
+                let mut concatenated = expect_list(&arguments[0]).clone();
+                concatenated.extend(expect_list(&arguments[1]).iter().cloned());
+
+                Ok(Value::List(concatenated))
+            }
+            Builtin::Reverse => {
+                let mut reversed = expect_list(&arguments[0]).clone();
+                reversed.reverse();
+
+                Ok(Value::List(reversed))
+            }
+            Builtin::Sort => {
+                let mut sorted = expect_list(&arguments[0]).clone();
+                sorted.sort_by(|left, right| left.compare(right));
+
+                Ok(Value::List(sorted))
+            }
+            Builtin::Get => Ok(
+                match index_list(expect_list(&arguments[1]), expect_number(&arguments[0])) {
+                    Ok(element) => Value::some(element),
+                    Err(_) => Value::none(),
+                },
+            ),
+            Builtin::ParseNumber => {
+                let string = arguments[0].to_string();
+
+                Ok(
+                    match Some(string.trim())
+                        .filter(|text| is_number_literal(text))
+                        .and_then(|text| text.parse::<f64>().ok())
+                    {
+                        Some(number) => Value::ok(Value::Number(number)),
+                        None => Value::err(Value::String(format!(
+                            "Cannot parse {:?} as a number",
+                            string
+                        ))),
+                    },
+                )
+            }
+            Builtin::Chars => Ok(Value::List(
+                arguments[0]
+                    .to_string()
+                    .chars()
+                    .map(|char| Value::String(char.to_string()))
+                    .collect(),
+            )),
+            Builtin::Join => {
+                let joined = expect_list(&arguments[1])
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>()
+                    .join(&arguments[0].to_string());
+
+                machine.budget().check_size(joined.chars().count())?;
+
+                Ok(Value::String(joined))
+            }
+            // An empty separator is never found, leaving the string whole.
+            Builtin::Split => {
+                let (separator, string) = (arguments[0].to_string(), arguments[1].to_string());
+                let parts = if separator.is_empty() {
+                    vec![string.as_str()]
+                } else {
+                    string.split(separator.as_str()).collect()
+                };
+
+                machine.budget().check_size(parts.len())?;
+
+                Ok(Value::List(
+                    parts
+                        .into_iter()
+                        .map(|part| Value::String(part.to_string()))
+                        .collect(),
+                ))
+            }
+            Builtin::OptionMap | Builtin::ResultMap => match &arguments[1] {
+                Value::Data(name, values) if matches!(name.as_str(), "Some" | "Ok") => {
+                    Ok(Value::Data(
+                        name.clone(),
+                        vec![machine.apply(&arguments[0], values[0].clone())?],
+                    ))
+                }
+                value => Ok(value.clone()),
+            },
+            Builtin::OptionAndThen | Builtin::ResultAndThen => match &arguments[1] {
+                Value::Data(name, values) if matches!(name.as_str(), "Some" | "Ok") => {
+                    machine.apply(&arguments[0], values[0].clone())
+                }
+                value => Ok(value.clone()),
+            },
+            Builtin::OptionUnwrapOr | Builtin::ResultUnwrapOr => match &arguments[1] {
+                Value::Data(name, values) if matches!(name.as_str(), "Some" | "Ok") => {
+                    Ok(values[0].clone())
+                }
+                _ => Ok(arguments[0].clone()),
+            },
+        }
+    }
+}
+
+/// Variables visible to an expression, stored as a persistent linked list so closures can share it.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    frame: Option<Rc<Frame>>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    value: Value,
+    parent: Environment,
+}
+
+impl Environment {
+    pub fn extend(&self, name: String, value: Value) -> Environment {
+        Environment {
+            frame: Some(Rc::new(Frame {
+                name,
+                value,
+                parent: self.clone(),
+            })),
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Value> {
+        let mut environment = self;
+
+        while let Some(frame) = &environment.frame {
+            if frame.name == name {
+                return Some(&frame.value);
+            }
+
+            environment = &frame.parent;
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    IndexOutOfBounds {
+        index: f64,
+        length: usize,
+    },
+    NotCallable(String),
+    UnknownIdentifier(String),
+    NoMatchingArm(String),
+    MissingEntry,
+    MissingExpression,
+    /// A host function received a value it cannot convert to its Rust argument type.
+    HostArgument {
+        function: String,
+        value: String,
+    },
+    /// An error reported by a host function.
+    Host(String),
+    OutOfFuel(u64),
+    RecursionLimit(usize),
+    CollectionTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    Timeout(Duration),
+    /// A range whose bounds are not finite or too large to count up to by steps of 1.
+    InvalidRange(f64, f64),
+    /// Division or remainder by zero under [`ArithmeticPolicy::Error`].
+    DivisionByZero,
+    /// An arithmetic operation resulting in NaN under [`ArithmeticPolicy::Error`].
+    NotANumber,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::IndexOutOfBounds { index, length } => write!(
+                f,
+                "Index {:} is out of bounds for list of length {:}",
+                index, length
+            ),
+            RuntimeError::NotCallable(value) => {
+                write!(f, "Cannot call non-function value {:}", value)
+            }
+            RuntimeError::UnknownIdentifier(name) => write!(f, "Unknown identifier \"{:}\"", name),
+            RuntimeError::NoMatchingArm(value) => {
+                write!(f, "No match arm matches value {:}", value)
+            }
+            RuntimeError::MissingEntry => write!(f, "Program has no entry expression"),
+            RuntimeError::MissingExpression => write!(f, "Cannot evaluate a missing expression"),
+            RuntimeError::HostArgument { function, value } => write!(
+                f,
+                "Host function \"{:}\" cannot accept argument {:}",
+                function, value
+            ),
+            RuntimeError::Host(message) => write!(f, "{:}", message),
+            RuntimeError::OutOfFuel(fuel) => {
+                write!(f, "Evaluation ran out of fuel after {:} steps", fuel)
+            }
+            RuntimeError::RecursionLimit(depth) => {
+                write!(f, "Recursion depth exceeds the limit of {:}", depth)
+            }
+            RuntimeError::CollectionTooLarge { size, limit } => write!(
+                f,
+                "Collection of size {:} exceeds the limit of {:}",
+                size, limit
+            ),
+            RuntimeError::Timeout(duration) => write!(
+                f,
+                "Evaluation exceeds the time limit of {:} ms",
+                duration.as_millis()
+            ),
+            RuntimeError::InvalidRange(start, end) => write!(
+                f,
+                "Cannot make a range from {:} to {:} by steps of 1",
+                start, end
+            ),
+            RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::NotANumber => write!(f, "Arithmetic operation results in NaN"),
+        }
+    }
+}
+
+/// The outcome of evaluating an expression whose call in tail position is not applied yet.
+enum Step {
+    Done(Value),
+    TailCall(Value, Value),
+}
+
+/// Budgets for evaluating untrusted programs, each unlimited when `None`. Exceeding one fails
+/// the evaluation with its own [`RuntimeError`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// Maximum number of evaluated expressions and function applications.
+    pub fuel: Option<u64>,
+    /// Maximum number of nested function applications, not counting tail calls. It should be low
+    /// enough for the stack of the evaluating thread, as every application takes several frames.
+    pub max_depth: Option<usize>,
+    /// Maximum length of a list or string created by a built-in function.
+    pub max_collection_size: Option<usize>,
+    /// Maximum wall-clock time, checked while evaluating.
+    pub time_limit: Option<Duration>,
+}
+
+/// What arithmetic evaluates to when IEEE 754 would give infinity or NaN for a division by
+/// zero, or NaN for another operation, e.g. subtracting infinity from itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ArithmeticPolicy {
+    /// Keeps the IEEE 754 result.
+    #[default]
+    Ieee,
+    /// Fails with [`RuntimeError::DivisionByZero`] or [`RuntimeError::NotANumber`].
+    Error,
+}
+
+impl ArithmeticPolicy {
+    /// Checks the result of an arithmetic operation, with `divisor` given for division and
+    /// remainder.
+    pub(crate) fn check(self, result: f64, divisor: Option<f64>) -> EvalResult {
+        match self {
+            ArithmeticPolicy::Error if divisor == Some(0.0) => Err(RuntimeError::DivisionByZero),
+            ArithmeticPolicy::Error if result.is_nan() => Err(RuntimeError::NotANumber),
+            _ => Ok(Value::Number(result)),
+        }
+    }
+}
+
+/// The largest magnitude of a range bound, up to which every integer is a distinct number.
+const MAX_RANGE_BOUND: f64 = 9007199254740992.;
+
+/// How many steps pass between two checks of the deadline, as reading the clock is not free.
+const DEADLINE_CHECK_INTERVAL: u64 = 64;
+
+/// Tracks how much of its [`Limits`] an evaluation has used.
+#[derive(Debug, Default)]
+pub struct Budget {
+    limits: Limits,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    deadline: Cell<Option<Instant>>,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Resets the used budget at the start of an evaluation.
+    pub fn start(&self) {
+        self.steps.set(0);
+        self.depth.set(0);
+        self.deadline.set(
+            self.limits
+                .time_limit
+                .map(|time_limit| Instant::now() + time_limit),
+        );
+    }
+
+    /// Counts one evaluation step against the fuel and checks the deadline now and then.
+    pub fn step(&self) -> std::result::Result<(), RuntimeError> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+
+        if let Some(fuel) = self.limits.fuel.filter(|fuel| steps > *fuel) {
+            return Err(RuntimeError::OutOfFuel(fuel));
+        }
+
+        if let (Some(deadline), Some(time_limit)) = (self.deadline.get(), self.limits.time_limit) {
+            if steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(RuntimeError::Timeout(time_limit));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Enters a nested function application, which must be followed by [`Budget::leave`].
+    pub fn enter(&self) -> std::result::Result<(), RuntimeError> {
+        let depth = self.depth.get() + 1;
+
+        if let Some(max_depth) = self.limits.max_depth.filter(|max_depth| depth > *max_depth) {
+            return Err(RuntimeError::RecursionLimit(max_depth));
+        }
+
+        self.depth.set(depth);
+        Ok(())
+    }
+
+    pub fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    pub fn check_size(&self, size: usize) -> std::result::Result<(), RuntimeError> {
+        match self.limits.max_collection_size {
+            Some(limit) if size > limit => Err(RuntimeError::CollectionTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What built-in functions need from the evaluator or virtual machine calling them.
+pub trait Machine {
+    fn apply(&self, function: &Value, argument: Value) -> EvalResult;
+
+    fn budget(&self) -> &Budget;
+
+    fn arithmetic(&self) -> ArithmeticPolicy;
+}
+
 pub struct Evaluator {
-    root_expression: BoundExpression,
+    definitions: Vec<(String, Option<BoundExpression>)>,
+    root_expression: Option<BoundExpression>,
+    /// Values of top-level definitions, used for names which are not bound locally.
+    globals: RefCell<HashMap<String, Value>>,
+    budget: Budget,
+    arithmetic: ArithmeticPolicy,
 }
 
 impl Evaluator {
     pub fn new(root_expression: BoundExpression) -> Self {
-        Self { root_expression }
+        Self {
+            definitions: vec![],
+            root_expression: Some(root_expression),
+            globals: RefCell::new(HashMap::new()),
+            budget: Budget::default(),
+            arithmetic: ArithmeticPolicy::default(),
+        }
+    }
+
+    pub fn from_program(program: BoundProgram) -> Self {
+        Self {
+            definitions: program.definitions,
+            root_expression: program.entry,
+            globals: RefCell::new(HashMap::new()),
+            budget: Budget::default(),
+            arithmetic: ArithmeticPolicy::default(),
+        }
+    }
+
+    /// Makes the functions and constants of `host` visible to the program. Top-level
+    /// definitions of the root module shadow them.
+    pub fn with_host(self, host: &HostEnvironment) -> Self {
+        self.globals.borrow_mut().extend(
+            host.items()
+                .iter()
+                .map(|item| (item.name.clone(), item.value.clone())),
+        );
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.budget = Budget::new(limits);
+        self
+    }
+
+    pub fn with_arithmetic(mut self, arithmetic: ArithmeticPolicy) -> Self {
+        self.arithmetic = arithmetic;
+        self
     }
 
     pub fn eval(&self, holder: &DiagnosticHolder) -> Box<dyn Result> {
         if holder.success() {
-            self.eval_expression(&self.root_expression)
+            match self.evaluate() {
+                Ok(value) => value.into_result(),
+                Err(error) => Box::new(format!("<Error: {}>", error)),
+            }
         } else {
             Box::new("<Error>".to_string())
         }
     }
 
-    fn eval_expression(&self, expression: &BoundExpression) -> Box<dyn Result> {
+    /// Evaluates the top-level definitions in order, then the entry expression.
+    pub fn evaluate(&self) -> EvalResult {
+        self.budget.start();
+
+        for (name, value) in &self.definitions {
+            let evaluated_value = self.eval_child(value, &Environment::default())?;
+
+            self.globals
+                .borrow_mut()
+                .insert(name.clone(), evaluated_value);
+        }
+
+        match &self.root_expression {
+            Some(root_expression) => self.eval_expression(root_expression, &Environment::default()),
+            None => Err(RuntimeError::MissingEntry),
+        }
+    }
+
+    fn eval_child(
+        &self,
+        expression: &Option<BoundExpression>,
+        environment: &Environment,
+    ) -> EvalResult {
+        match expression {
+            Some(expression) => self.eval_expression(expression, environment),
+            None => Err(RuntimeError::MissingExpression),
+        }
+    }
+
+    fn eval_expression(
+        &self,
+        expression: &BoundExpression,
+        environment: &Environment,
+    ) -> EvalResult {
+        match self.eval_tail(expression, environment)? {
+            Step::Done(value) => Ok(value),
+            Step::TailCall(function, argument) => self.apply(&function, argument),
+        }
+    }
+
+    fn eval_tail_child(
+        &self,
+        expression: &Option<BoundExpression>,
+        environment: &Environment,
+    ) -> std::result::Result<Step, RuntimeError> {
+        match expression {
+            Some(expression) => self.eval_tail(expression, environment),
+            None => Err(RuntimeError::MissingExpression),
+        }
+    }
+
+    /// Evaluates an expression, leaving a call in tail position to the caller instead of
+    /// applying it, so that [`Evaluator::apply`] can run tail calls without growing the stack.
+    fn eval_tail(
+        &self,
+        expression: &BoundExpression,
+        environment: &Environment,
+    ) -> std::result::Result<Step, RuntimeError> {
+        self.budget.step()?;
+
         match expression {
-            BoundExpression::Literal(string) => Box::new(string.clone()),
-            BoundExpression::Bool(string) => Box::new(string.clone().parse::<bool>().unwrap()),
-            BoundExpression::Number(string) => Box::new(string.clone().parse::<f64>().unwrap()),
             BoundExpression::Parenthesis(expression) => {
-                self.eval_expression(&expression.clone().unwrap())
-            }
-            BoundExpression::Identity(expression) => {
-                self.eval_expression(&expression.clone().unwrap())
+                self.eval_tail_child(expression, environment)
             }
-            BoundExpression::Negation(expression) => {
-                let evaluated_expression = self.eval_expression(&expression.clone().unwrap());
+            BoundExpression::Call(callee, argument, _) => {
+                let evaluated_callee = self.eval_child(callee, environment)?;
+                let evaluated_argument = self.eval_child(argument, environment)?;
 
-                if let Some(val) = evaluated_expression.as_any().downcast_ref::<f64>() {
-                    Box::new(-val)
+                Ok(Step::TailCall(evaluated_callee, evaluated_argument))
+            }
+            BoundExpression::If(condition, then_branch, else_branch) => {
+                if expect_bool(&self.eval_child(condition, environment)?) {
+                    self.eval_tail_child(then_branch, environment)
                 } else {
-                    panic!("Cannot apply negative on non numeric types.")
+                    self.eval_tail_child(else_branch, environment)
                 }
             }
-            BoundExpression::LogicalNot(expression) => {
-                let evaluated_expression = self.eval_expression(&expression.clone().unwrap());
+            // The right operand is only evaluated if the left one does not decide the result.
+            BoundExpression::LogicalOr(left, right) => {
+                if expect_bool(&self.eval_child(left, environment)?) {
+                    Ok(Step::Done(Value::Bool(true)))
+                } else {
+                    self.eval_tail_child(right, environment)
+                }
+            }
+            BoundExpression::LogicalAnd(left, right) => {
+                if expect_bool(&self.eval_child(left, environment)?) {
+                    self.eval_tail_child(right, environment)
+                } else {
+                    Ok(Step::Done(Value::Bool(false)))
+                }
+            }
+            BoundExpression::Let(name, value, body) => {
+                let evaluated_value = self.eval_child(value, environment)?;
+
+                self.eval_tail_child(body, &environment.extend(name.clone(), evaluated_value))
+            }
+            BoundExpression::LetRec(name, value, body) => {
+                let function = match value.as_ref() {
+                    Some(BoundExpression::Lambda(parameter, lambda_body, _)) => {
+                        Value::Function(Rc::new(Function::Closure {
+                            parameter: parameter.clone(),
+                            body: lambda_body.clone(),
+                            environment: environment.clone(),
+                            name: Some(name.clone()),
+                        }))
+                    }
+                    _ => return Err(RuntimeError::MissingExpression),
+                };
+
+                self.eval_tail_child(body, &environment.extend(name.clone(), function))
+            }
+            BoundExpression::Match(scrutinee, arms, _) => {
+                let value = self.eval_child(scrutinee, environment)?;
+
+                for (pattern, body) in arms {
+                    if let Some(environment) = self.match_pattern(pattern, &value, environment) {
+                        return self.eval_tail_child(body, &environment);
+                    }
+                }
+
+                Err(RuntimeError::NoMatchingArm(value.to_string()))
+            }
+            _ => self.eval_value(expression, environment).map(Step::Done),
+        }
+    }
 
-                if let Some(val) = evaluated_expression.as_any().downcast_ref::<bool>() {
-                    Box::new(!val)
+    /// Evaluates the expressions which never end in a call, see [`Evaluator::eval_tail`].
+    fn eval_value(&self, expression: &BoundExpression, environment: &Environment) -> EvalResult {
+        match expression {
+            BoundExpression::Literal(string) => Ok(Value::String(string.clone())),
+            BoundExpression::Bool(string) => Ok(Value::Bool(string.parse::<bool>().unwrap())),
+            BoundExpression::Number(string) => Ok(Value::Number(string.parse::<f64>().unwrap())),
+            BoundExpression::Identifier(name, _) => {
+                if let Some(value) = environment.lookup(name) {
+                    Ok(value.clone())
+                } else if let Some(value) = self.globals.borrow().get(name) {
+                    Ok(value.clone())
+                } else if let Ok(builtin) = name.parse::<Builtin>() {
+                    Ok(Value::Function(Rc::new(Function::Builtin {
+                        builtin,
+                        arguments: vec![],
+                    })))
                 } else {
-                    panic!("Cannot apply negative on non numeric types.")
+                    Err(RuntimeError::UnknownIdentifier(name.clone()))
                 }
             }
-            BoundExpression::LogicalOr(left, right) => {
-                let evaluated_binary = self.eval_binary::<bool>(left, right);
+            BoundExpression::Identity(expression) => self.eval_child(expression, environment),
+            BoundExpression::Negation(expression) => {
+                let evaluated_expression = self.eval_child(expression, environment)?;
 
-                Box::new(evaluated_binary[0] || evaluated_binary[1])
+                Ok(Value::Number(-expect_number(&evaluated_expression)))
             }
-            BoundExpression::LogicalAnd(left, right) => {
-                let evaluated_binary = self.eval_binary::<bool>(left, right);
+            BoundExpression::LogicalNot(expression) => {
+                let evaluated_expression = self.eval_child(expression, environment)?;
 
-                Box::new(evaluated_binary[0] && evaluated_binary[1])
+                Ok(Value::Bool(!expect_bool(&evaluated_expression)))
             }
             BoundExpression::NotEqual(left, right) => {
-                let evaluated_left = self.eval_expression(&left.clone().unwrap());
-                let evaluated_right = self.eval_expression(&right.clone().unwrap());
+                let evaluated_binary = self.eval_binary(left, right, environment)?;
 
-                Box::new(evaluated_left.to_string() != evaluated_right.to_string())
+                Ok(Value::Bool(
+                    !evaluated_binary[0].equals(&evaluated_binary[1]),
+                ))
             }
             BoundExpression::Equal(left, right) => {
-                let evaluated_left = self.eval_expression(&left.clone().unwrap());
-                let evaluated_right = self.eval_expression(&right.clone().unwrap());
+                let evaluated_binary = self.eval_binary(left, right, environment)?;
 
-                Box::new(evaluated_left.to_string() == evaluated_right.to_string())
+                Ok(Value::Bool(
+                    evaluated_binary[0].equals(&evaluated_binary[1]),
+                ))
             }
             BoundExpression::Greater(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] > evaluated_binary[1])
+                Ok(Value::Bool(left > right))
             }
             BoundExpression::GreaterEqual(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] >= evaluated_binary[1])
+                Ok(Value::Bool(left >= right))
             }
             BoundExpression::Less(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] < evaluated_binary[1])
+                Ok(Value::Bool(left < right))
             }
             BoundExpression::LessEqual(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] <= evaluated_binary[1])
+                Ok(Value::Bool(left <= right))
             }
             BoundExpression::Addition(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] + evaluated_binary[1])
+                self.arithmetic.check(left + right, None)
             }
             BoundExpression::Subtraction(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] - evaluated_binary[1])
+                self.arithmetic.check(left - right, None)
             }
             BoundExpression::Multiplication(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] * evaluated_binary[1])
+                self.arithmetic.check(left * right, None)
             }
             BoundExpression::Division(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] / evaluated_binary[1])
+                self.arithmetic.check(left / right, Some(right))
             }
             BoundExpression::Remainder(left, right) => {
-                let evaluated_binary = self.eval_binary::<f64>(left, right);
+                let [left, right] = self.eval_numbers(left, right, environment)?;
 
-                Box::new(evaluated_binary[0] % evaluated_binary[1])
+                self.arithmetic.check(left % right, Some(right))
             }
-            _ => panic!("Evaluation error."),
+            BoundExpression::List(elements, _) => Ok(Value::List(
+                elements
+                    .iter()
+                    .map(|element| self.eval_child(element, environment))
+                    .collect::<std::result::Result<Vec<Value>, RuntimeError>>()?,
+            )),
+            BoundExpression::Index(list, index, _) => {
+                let evaluated_list = self.eval_child(list, environment)?;
+                let evaluated_index = self.eval_child(index, environment)?;
+
+                index_list(
+                    expect_list(&evaluated_list),
+                    expect_number(&evaluated_index),
+                )
+            }
+            BoundExpression::Lambda(parameter, body, _) => {
+                Ok(Value::Function(Rc::new(Function::Closure {
+                    parameter: parameter.clone(),
+                    body: body.clone(),
+                    environment: environment.clone(),
+                    name: None,
+                })))
+            }
+            BoundExpression::Tuple(elements, _) => Ok(Value::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.eval_child(element, environment))
+                    .collect::<std::result::Result<Vec<Value>, RuntimeError>>()?,
+            )),
+            BoundExpression::Record(fields, _) => {
+                Ok(Value::Record(self.eval_fields(fields, environment)?))
+            }
+            BoundExpression::RecordUpdate(record, fields, _) => {
+                let mut evaluated_record =
+                    expect_record(&self.eval_child(record, environment)?).clone();
+                evaluated_record.extend(self.eval_fields(fields, environment)?);
+
+                Ok(Value::Record(evaluated_record))
+            }
+            BoundExpression::Field(record, field, _) => {
+                let evaluated_record = self.eval_child(record, environment)?;
+
+                Ok(expect_record(&evaluated_record)[field].clone())
+            }
+            BoundExpression::TupleField(tuple, position, _) => {
+                match self.eval_child(tuple, environment)? {
+                    Value::Tuple(elements) => Ok(elements[*position].clone()),
+                    value => panic!("Evaluation error: expected tuple, found {:}", value),
+                }
+            }
+            BoundExpression::Constructor(name, 0, _) => Ok(Value::Data(name.clone(), vec![])),
+            BoundExpression::Constructor(name, arity, _) => {
+                Ok(Value::Function(Rc::new(Function::Constructor {
+                    name: name.clone(),
+                    arity: *arity,
+                    arguments: vec![],
+                })))
+            }
+            BoundExpression::Parenthesis(_)
+            | BoundExpression::Call(_, _, _)
+            | BoundExpression::If(_, _, _)
+            | BoundExpression::LogicalOr(_, _)
+            | BoundExpression::LogicalAnd(_, _)
+            | BoundExpression::Let(_, _, _)
+            | BoundExpression::LetRec(_, _, _)
+            | BoundExpression::Match(_, _, _) => {
+                unreachable!("Evaluation error: tail expression is handled by eval_tail")
+            }
+        }
+    }
+
+    /// Returns the environment extended with the pattern's variables if `value` matches `pattern`.
+    fn match_pattern(
+        &self,
+        pattern: &BoundPattern,
+        value: &Value,
+        environment: &Environment,
+    ) -> Option<Environment> {
+        match (pattern, value) {
+            (BoundPattern::Wildcard, _) => Some(environment.clone()),
+            (BoundPattern::Variable(name), _) => {
+                Some(environment.extend(name.clone(), value.clone()))
+            }
+            (BoundPattern::Number(number), Value::Number(value)) => {
+                (number.parse::<f64>().ok()? == *value).then(|| environment.clone())
+            }
+            (BoundPattern::Literal(string), Value::String(value)) => {
+                (string == value).then(|| environment.clone())
+            }
+            (BoundPattern::Bool(bool), Value::Bool(value)) => {
+                (bool.parse::<bool>().ok()? == *value).then(|| environment.clone())
+            }
+            (BoundPattern::Constructor(name, patterns), Value::Data(value_name, values))
+                if name == value_name =>
+            {
+                self.match_patterns(patterns, values, environment)
+            }
+            (BoundPattern::Tuple(patterns), Value::Tuple(values))
+            | (BoundPattern::List(patterns), Value::List(values))
+                if patterns.len() == values.len() =>
+            {
+                self.match_patterns(patterns, values, environment)
+            }
+            _ => None,
         }
     }
 
-    fn eval_binary<T>(
+    fn match_patterns(
+        &self,
+        patterns: &[BoundPattern],
+        values: &[Value],
+        environment: &Environment,
+    ) -> Option<Environment> {
+        patterns
+            .iter()
+            .zip(values)
+            .try_fold(environment.clone(), |environment, (pattern, value)| {
+                self.match_pattern(pattern, value, &environment)
+            })
+    }
+
+    fn eval_binary(
         &self,
-        left: &Box<Option<BoundExpression>>,
-        right: &Box<Option<BoundExpression>>,
-    ) -> [T; 2]
-    where
-        T: Result + Clone,
-    {
-        [
-            (*self.eval_expression(&left.clone().unwrap()))
-                .as_any()
-                .downcast_ref::<T>()
-                .unwrap()
-                .to_owned(),
-            (*self.eval_expression(&right.clone().unwrap()))
-                .as_any()
-                .downcast_ref::<T>()
-                .unwrap()
-                .to_owned(),
-        ]
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        environment: &Environment,
+    ) -> std::result::Result<[Value; 2], RuntimeError> {
+        Ok([
+            self.eval_child(left, environment)?,
+            self.eval_child(right, environment)?,
+        ])
+    }
+
+    fn eval_fields(
+        &self,
+        fields: &[(String, Option<BoundExpression>)],
+        environment: &Environment,
+    ) -> std::result::Result<BTreeMap<String, Value>, RuntimeError> {
+        fields
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), self.eval_child(value, environment)?)))
+            .collect()
+    }
+
+    fn eval_numbers(
+        &self,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        environment: &Environment,
+    ) -> std::result::Result<[f64; 2], RuntimeError> {
+        let evaluated_binary = self.eval_binary(left, right, environment)?;
+
+        Ok([
+            expect_number(&evaluated_binary[0]),
+            expect_number(&evaluated_binary[1]),
+        ])
+    }
+
+    pub fn apply(&self, function: &Value, argument: Value) -> EvalResult {
+        self.budget.enter()?;
+        let result = self.apply_function(function, argument);
+        self.budget.leave();
+
+        result
+    }
+
+    fn apply_function(&self, function: &Value, argument: Value) -> EvalResult {
+        let mut function = function.clone();
+        let mut argument = argument;
+
+        // Calls in tail position of a closure's body are run by this loop instead of recursing.
+        loop {
+            self.budget.step()?;
+
+            let step = match &function {
+                Value::Function(callable) => match callable.as_ref() {
+                    Function::Closure {
+                        parameter,
+                        body,
+                        environment,
+                        name,
+                    } => {
+                        let environment = match name {
+                            Some(name) => environment.extend(name.clone(), function.clone()),
+                            None => environment.clone(),
+                        };
+
+                        self.eval_tail_child(
+                            body,
+                            &environment.extend(parameter.clone(), argument),
+                        )?
+                    }
+                    callable => return callable.apply_native(argument, self),
+                },
+                _ => return Err(RuntimeError::NotCallable(function.to_string())),
+            };
+
+            match step {
+                Step::Done(value) => return Ok(value),
+                Step::TailCall(next_function, next_argument) => {
+                    function = next_function;
+                    argument = next_argument;
+                }
+            }
+        }
+    }
+}
+
+impl Machine for Evaluator {
+    fn apply(&self, function: &Value, argument: Value) -> EvalResult {
+        Evaluator::apply(self, function, argument)
+    }
+
+    fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    fn arithmetic(&self) -> ArithmeticPolicy {
+        self.arithmetic
+    }
+}
+
+// The binder guarantees operand types, so a mismatch here is an internal error.
+pub(crate) fn expect_number(value: &Value) -> f64 {
+    match value {
+        Value::Number(number) => *number,
+        _ => panic!("Evaluation error: expected number, found {:}", value),
+    }
+}
+
+pub(crate) fn expect_bool(value: &Value) -> bool {
+    match value {
+        Value::Bool(bool) => *bool,
+        _ => panic!("Evaluation error: expected bool, found {:}", value),
+    }
+}
+
+pub(crate) fn expect_list(value: &Value) -> &Vec<Value> {
+    match value {
+        Value::List(elements) => elements,
+        _ => panic!("Evaluation error: expected list, found {:}", value),
+    }
+}
+
+pub(crate) fn expect_record(value: &Value) -> &BTreeMap<String, Value> {
+    match value {
+        Value::Record(fields) => fields,
+        _ => panic!("Evaluation error: expected record, found {:}", value),
+    }
+}
+
+pub(crate) fn index_list(elements: &[Value], index: f64) -> EvalResult {
+    if index < 0. || index.fract() != 0. || index as usize >= elements.len() {
+        Err(RuntimeError::IndexOutOfBounds {
+            index,
+            length: elements.len(),
+        })
+    } else {
+        Ok(elements[index as usize].clone())
     }
 }
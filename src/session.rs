@@ -0,0 +1,220 @@
+use crate::binder::{Binder, BoundExpression, BoundProgram};
+use crate::compilation::{with_keywords, Compilation};
+use crate::cst;
+use crate::diagnostic::DiagnosticHolder;
+use crate::lexer::Lexer;
+use crate::parser::{Declaration, Parser};
+use crate::runtime::Result;
+
+/// The definitions, types and imports entered so far in the REPL, bound once so that later
+/// inputs can use them.
+#[derive(Debug, Clone)]
+pub struct Session {
+    items: Vec<Item>,
+    /// Binder which bound the items, and the prelude and modules they import, to bind the next
+    /// input.
+    binder: Binder,
+    /// Bound definitions of the items and of the modules they import, run before every input.
+    definitions: Vec<(String, Option<BoundExpression>)>,
+}
+
+/// A top-level declaration of the session with the names it declares.
+#[derive(Debug, Clone)]
+struct Item {
+    names: Vec<String>,
+    source: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let mut compilation = Compilation::new(String::new());
+        let mut binder = compilation.session_binder();
+        let definitions = compilation.bind_input(&mut binder).definitions;
+
+        Self {
+            items: vec![],
+            binder,
+            definitions,
+        }
+    }
+
+    /// Source code of the session as a program, one top-level declaration per line.
+    pub fn source(&self) -> String {
+        self.items
+            .iter()
+            .map(|item| format!("{:}\n", item.source))
+            .collect()
+    }
+
+    /// Compiles `input` after the session's declarations and runs its entry expression, if
+    /// any. When it succeeds, its declarations join the session, replacing earlier ones which
+    /// declare the same names. Returns the diagnostics and the value of the entry expression.
+    pub fn eval(&mut self, input: &str) -> (DiagnosticHolder, Option<Box<dyn Result>>) {
+        let mut holder = DiagnosticHolder::new();
+        let mut items = self.items.clone();
+        let mut added: Vec<Item> = vec![];
+        let mut entries = vec![];
+
+        // Each item is lexed again below, which reports the lexing errors.
+        let sources = Lexer::new(input.to_string()).split_items(&mut DiagnosticHolder::new());
+
+        for source in sources {
+            let tokens = cst::lex(&source, &mut holder);
+            let program = Parser::new(tokens).parse_program(&mut holder);
+
+            if program.entry.is_some() {
+                entries.push(source);
+                continue;
+            }
+
+            let names = program
+                .declarations
+                .iter()
+                .flat_map(declared_names)
+                .collect::<Vec<String>>();
+            let item = Item { names, source };
+
+            items.retain(|other| !item.replaces(other));
+            added.retain(|other| !item.replaces(other));
+            items.push(item.clone());
+            added.push(item);
+        }
+
+        if !holder.success() {
+            return (holder, None);
+        }
+
+        // Replaced declarations may be used by the remaining ones, so all are bound again.
+        let replaced = items.len() - added.len() < self.items.len();
+        let (mut session, new_items) = if replaced {
+            (Self::new(), &items)
+        } else {
+            (self.clone(), &added)
+        };
+
+        let mut source = new_items
+            .iter()
+            .map(|item| format!("{:}\n", item.source))
+            .collect::<String>();
+        source.push_str(&entries.join("\n"));
+
+        let (mut compilation, bound_program) = bind(&mut session.binder, source);
+        session.definitions.extend(bound_program.definitions);
+
+        let result = if entries.is_empty() {
+            None
+        } else {
+            Some(compilation.eval_program(BoundProgram {
+                definitions: session.definitions.clone(),
+                entry: bound_program.entry,
+            }))
+        };
+
+        if compilation.holder.success() {
+            session.items = items;
+            *self = session;
+        }
+
+        (compilation.holder, result)
+    }
+
+    /// Binds `input` after the session's declarations, without adding its own to the session,
+    /// e.g. to show its type. Returns the compilation, which reports the diagnostics, and the
+    /// bound input whose definitions include the session's, ready to run.
+    pub fn compile(&self, input: &str) -> (Compilation, BoundProgram) {
+        let (compilation, mut bound_program) = bind(&mut self.binder.clone(), input.to_string());
+        let mut definitions = self.definitions.clone();
+
+        definitions.append(&mut bound_program.definitions);
+        bound_program.definitions = definitions;
+
+        (compilation, bound_program)
+    }
+
+    /// Names to complete in the REPL, see [`Compilation::completions`].
+    pub fn completions(&self) -> Vec<String> {
+        let mut names = self.binder.names();
+
+        names.extend(
+            self.definitions
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| !name.contains("::")),
+        );
+
+        with_keywords(names)
+    }
+
+    /// Names and types of the definitions in the session, in the order they were entered.
+    pub fn bindings(&self) -> Vec<(String, String)> {
+        self.items
+            .iter()
+            .flat_map(|item| &item.names)
+            .filter_map(|name| {
+                self.definitions
+                    .iter()
+                    .find(|(definition, _)| definition == name)
+            })
+            .map(|(name, definition)| {
+                let bound_type = definition
+                    .as_ref()
+                    .map_or_else(String::new, |definition| definition.get_type().to_string());
+
+                (name.clone(), bound_type)
+            })
+            .collect()
+    }
+}
+
+impl Item {
+    /// Whether this item declares a name `other` declares too, so that it replaces `other`.
+    fn replaces(&self, other: &Item) -> bool {
+        other.names.iter().any(|name| self.names.contains(name))
+    }
+}
+
+/// Binds `source` as the next input of the session `binder` bound, which learns its
+/// declarations.
+fn bind(binder: &mut Binder, source: String) -> (Compilation, BoundProgram) {
+    let mut compilation = Compilation::new(source);
+    let bound_program = compilation.bind_input(binder);
+
+    (compilation, bound_program)
+}
+
+/// Whether `input` ends in the middle of an item, e.g. inside brackets or a string, before the
+/// `in` of a `let` or after an operator, so that the REPL should read another line.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut holder = DiagnosticHolder::new();
+    let mut lexer = Lexer::new(input.to_string());
+    let spans = lexer.lex_spans(&mut holder);
+    let mut parser = Parser::new(cst::green_tokens(input, spans));
+    parser.parse_program(&mut holder);
+
+    lexer.is_incomplete() || parser.is_incomplete()
+}
+
+/// Names declared by a top-level declaration. Imports are named after the `import` itself so
+/// that importing a module again does not repeat it.
+fn declared_names(declaration: &Declaration) -> Vec<String> {
+    match declaration {
+        Declaration::Definition(name, _) => vec![name.literal.clone()],
+        Declaration::Type(type_declaration) => std::iter::once(&type_declaration.name)
+            .chain(
+                type_declaration
+                    .constructors
+                    .iter()
+                    .map(|(constructor, _)| constructor),
+            )
+            .map(|token| token.literal.clone())
+            .collect(),
+        Declaration::Import(module) => vec![format!("import {:}", module.literal)],
+        Declaration::Export(declaration) => declared_names(declaration),
+    }
+}
@@ -3,15 +3,24 @@ mod test {
     use test_case::test_case;
 
     use crate::binder::Binder;
+    use crate::compilation::{Backend, Compilation};
+    use crate::cst::{self, Node, NodeKind};
     use crate::diagnostic::Unit;
+    use crate::formatter::{format_source, Spelling};
+    use crate::highlight::highlight;
+    use crate::host::HostEnvironment;
+    use crate::lsp::{read_message, run, write_message};
+    use crate::module::{source_files, MemoryResolver};
+    use crate::runtime::{ArithmeticPolicy, Limits, RuntimeError, Value};
+    use crate::session::{is_incomplete, Session};
+    use crate::wasm::{ENTRY_EXPORT, MEMORY_EXPORT};
     use crate::{
-        diagnostic::DiagnosticHolder,
-        lexer::Lexer,
-        parser::Parser,
-        runtime::Evaluator,
-        utils::{print_syntax_tree, to_string},
+        diagnostic::DiagnosticHolder, parser::Parser, runtime::Evaluator, utils::to_string,
     };
+    use std::convert::TryInto;
     use std::fmt::Display;
+    use std::rc::Rc;
+    use std::time::Duration;
 
     #[test_case("\"Hi\"", "Hi" ; "string literal test")]
     #[test_case("true", true ; "bool literal test")]
@@ -36,10 +45,10 @@ mod test {
     #[test_case("2 >= 1", true ; "greater equal than expression test")]
     #[test_case("2 < 1", false ; "less than expression test")]
     #[test_case("2 <= 1", false ; "less equal than expression test")]
+    #[test_case("(1 + # one\n 2) * 3 ＃ 三", 9. ; "comment test")]
     fn eval_test<T: Display + 'static>(source_code: &'static str, expected_result: T) {
         let mut diagnostic_holder = DiagnosticHolder::new();
-        let mut lexer = Lexer::new(source_code.trim().to_string());
-        let tokens = lexer.lex(&mut diagnostic_holder);
+        let tokens = cst::lex(source_code.trim(), &mut diagnostic_holder);
 
         assert!(diagnostic_holder.success());
 
@@ -48,7 +57,7 @@ mod test {
 
         assert!(diagnostic_holder.success());
 
-        let binder = Binder::new();
+        let mut binder = Binder::new();
         let bound_expression = binder.bind_expression(tree.root_expression, &mut diagnostic_holder);
 
         assert!(diagnostic_holder.success());
@@ -64,11 +73,140 @@ mod test {
         );
     }
 
+    #[test_case("[1, 2, 3]", "[1, 2, 3]" ; "list literal test")]
+    #[test_case("[]", "[]" ; "empty list literal test")]
+    #[test_case("[\"a\", \"b\"]", "[\"a\", \"b\"]" ; "string list literal test")]
+    #[test_case("[[1], [2, 3]]", "[[1], [2, 3]]" ; "nested list literal test")]
+    #[test_case("[10, 20, 30].[1]", "20" ; "list index test")]
+    #[test_case("(fn x -> x * 2) 21", "42" ; "lambda call test")]
+    #[test_case("(函數 x y -> x - y) 5 3", "2" ; "mandarin lambda call test")]
+    #[test_case("map (fn x -> x * x) [1, 2, 3]", "[1, 4, 9]" ; "map test")]
+    #[test_case("filter (fn x -> x % 2 == 0) (range 0 10)", "[0, 2, 4, 6, 8]" ; "filter range test")]
+    #[test_case("fold (fn acc x -> acc + x) 0 [1, 2, 3, 4]", "10" ; "fold test")]
+    #[test_case("zip (fn x y -> x * y) [1, 2, 3] [4, 5]", "[4, 10]" ; "zip test")]
+    #[test_case("length [1, 2, 3]", "3" ; "length test")]
+    #[test_case("concat [1] [2, 3]", "[1, 2, 3]" ; "concat test")]
+    #[test_case("reverse [1, 2, 3]", "[3, 2, 1]" ; "reverse test")]
+    #[test_case("sort [3, 1, 2]", "[1, 2, 3]" ; "sort test")]
+    #[test_case("sort [\"b\", \"c\", \"a\"]", "[\"a\", \"b\", \"c\"]" ; "sort string test")]
+    #[test_case("[1, 2] == [1, 2]", "true" ; "list equality test")]
+    #[test_case("map", "<builtin map>" ; "builtin value test")]
+    #[test_case("(1, \"a\")", "(1, \"a\")" ; "tuple literal test")]
+    #[test_case("(1, (true, [2]))", "(1, (true, [2]))" ; "nested tuple test")]
+    #[test_case("(1, \"a\").1", "a" ; "tuple position test")]
+    #[test_case("{ 名字: \"小明\", 年齡: 12 }", "{ 名字: \"小明\", 年齡: 12 }" ; "record literal test")]
+    #[test_case("{ 名字: \"小明\", 年齡: 12 }.年齡", "12" ; "record field test")]
+    #[test_case("{ { 名字: \"小明\", 年齡: 12 } with 年齡: 13 }", "{ 名字: \"小明\", 年齡: 13 }" ; "record update test")]
+    #[test_case("(fn p -> p.x + p.y) { x: 1, y: 2, z: 3 }", "3" ; "structural record test")]
+    #[test_case("map (fn p -> p.x) [{ x: 1 }, { x: 2 }]", "[1, 2]" ; "record list test")]
+    #[test_case("{ a: 1, b: 2 } == { b: 2, a: 1 }", "true" ; "record equality test")]
+    #[test_case("type 形狀 = 圓 number | 方 number number in match 方 2 3 with | 圓 r -> r * r | 方 w h -> w * h", "6" ; "match test")]
+    #[test_case("type 形狀 = 圓 數字 ｜ 方 數字 數字 在 匹配 圓 2 帶 ｜ 圓 r -> r * r ｜ 方 w h -> w * h", "4" ; "mandarin match test")]
+    #[test_case("type Shape = Circle number | Square number in Square 2", "Square 2" ; "constructor value test")]
+    #[test_case("type Maybe a = None | Some a in map Some [1, 2]", "[Some 1, Some 2]" ; "constructor function test")]
+    #[test_case("type Maybe a = None | Some a in match Some (Some 1) with | Some (Some x) -> x | _ -> 0", "1" ; "nested pattern test")]
+    #[test_case("type Maybe a = None | Some a in Some (Some \"a\")", "Some (Some \"a\")" ; "nested constructor display test")]
+    #[test_case("type List a = Nil | Cons a (List a) in match Cons 1 (Cons 2 Nil) with | Cons x (Cons y _) -> x + y | _ -> 0", "3" ; "recursive type test")]
+    #[test_case("match (1, \"a\") with | (0, s) -> s | (_, s) -> s", "a" ; "tuple pattern test")]
+    #[test_case("match [1, 2] with | [] -> 0 | [x] -> x | [x, y] -> x + y | _ -> 10", "3" ; "list pattern test")]
+    #[test_case("match \"b\" with | \"a\" -> 1 | \"b\" -> 2 | _ -> 3", "2" ; "literal pattern test")]
+    #[test_case("match 1 == 1 with | true -> \"yes\" | false -> \"no\"", "yes" ; "bool pattern test")]
+    #[test_case("parse_number \"42\"", "Ok 42" ; "parse number test")]
+    #[test_case("parse_number \" 3.25 \"", "Ok 3.25" ; "parse float number test")]
+    #[test_case("parse_number \"nan\"", "Err \"Cannot parse \\\"nan\\\" as a number\"" ; "parse nan number error test")]
+    #[test_case("parse_number \"inf\"", "Err \"Cannot parse \\\"inf\\\" as a number\"" ; "parse inf number error test")]
+    #[test_case("parse_number \"infinity\"", "Err \"Cannot parse \\\"infinity\\\" as a number\"" ; "parse infinity number error test")]
+    #[test_case("parse_number \"-1\"", "Err \"Cannot parse \\\"-1\\\" as a number\"" ; "parse negative number error test")]
+    #[test_case("parse_number \"1e3\"", "Err \"Cannot parse \\\"1e3\\\" as a number\"" ; "parse exponent number error test")]
+    #[test_case("parse_number \"abc\"", "Err \"Cannot parse \\\"abc\\\" as a number\"" ; "parse number error test")]
+    #[test_case("get 1 [1, 2]", "Some 2" ; "get test")]
+    #[test_case("get 2 [1, 2]", "None" ; "get out of bounds test")]
+    #[test_case("option_map (fn x -> x * 2) (Some 2)", "Some 4" ; "option map test")]
+    #[test_case("option_and_then (fn x -> get x [1, 2]) (有 5)", "None" ; "option and then test")]
+    #[test_case("option_unwrap_or 0 無", "0" ; "option unwrap or test")]
+    #[test_case("result_map (fn x -> x + 1) (parse_number \"1\")", "Ok 2" ; "result map test")]
+    #[test_case("result_and_then parse_number (成功 \"7\")", "Ok 7" ; "result and then test")]
+    #[test_case("result_unwrap_or 0 (parse_number \"x\")", "0" ; "result unwrap or test")]
+    #[test_case("有 1 == Some 1", "true" ; "option alias equality test")]
+    #[test_case("match parse_number \"3\" with | 成功 n -> n | 失敗 _ -> 0", "3" ; "result alias match test")]
+    #[test_case("type T = A (選項 數字) in match A 無 with | A (Some n) -> n | A None -> 0", "0" ; "option alias type test")]
+    #[test_case("if 1 < 2 then \"a\" else \"b\"", "a" ; "if test")]
+    #[test_case("如果 1 > 2 那麼 1 否則 2", "2" ; "mandarin if test")]
+    #[test_case("let x = 2 in x * x", "4" ; "let test")]
+    #[test_case("let id x = x in (id 1, id \"a\")", "(1, \"a\")" ; "let polymorphism test")]
+    #[test_case("let rec fact n = if n == 0 then 1 else n * fact (n - 1) in fact 5", "120" ; "let rec test")]
+    #[test_case("令 遞迴 fib n = if n < 2 then n else fib (n - 1) + fib (n - 2) 在 fib 10", "55" ; "mandarin let rec test")]
+    #[test_case("let rec count n = if n == 0 then 0 else count (n - 1) in count 1000000", "0" ; "tail call test")]
+    #[test_case("let rec sum acc n = if n == 0 then acc else sum (acc + n) (n - 1) in sum 0 100000", "5000050000" ; "curried tail call test")]
+    #[test_case("let rec last xs = match xs with | [x] -> x | _ -> last (reverse (reverse [0])) in last [1, 2]", "0" ; "match tail call test")]
+    #[test_case("double x = x * 2\ndouble 21", "42" ; "definition test")]
+    #[test_case("double 21; double x = x * 2", "42" ; "forward reference test")]
+    #[test_case("id x = x；(id 1, id \"a\")", "(1, \"a\")" ; "definition polymorphism test")]
+    #[test_case("x = 1\ny = x + 1\n\ny", "2" ; "value definition test")]
+    #[test_case("count n = if n == 0 then 0 else count (n - 1)\ncount 1000000", "0" ; "top-level tail call test")]
+    #[test_case("even n = if n == 0 then true else odd (n - 1)\nodd n = if n == 0 then false else even (n - 1)\neven 10001", "false" ; "mutual recursion test")]
+    #[test_case("even n = n == 0 || odd (n - 1)\nodd n = n != 0 && even (n - 1)\n(even 4, odd 100001)", "(true, true)" ; "short circuit recursion test")]
+    #[test_case("(false && [1].[3] == 1, true || [1].[3] == 1)", "(false, true)" ; "short circuit test")]
+    #[test_case("type Shape = Circle number | Square number\narea s = match s with\n  | Circle r -> r * r\n  | Square w -> w * w\narea (Square 3)", "9" ; "top-level type test")]
+    #[test_case("type Tree = Leaf | Node Forest\ntype Forest = Trees (List Tree)\nsize t = match t with\n  | Leaf -> 1\n  | Node (Trees ts) -> fold (fn acc t -> acc + size t) 1 ts\nsize (Node (Trees [Leaf, Leaf]))", "3" ; "mutually recursive type test")]
+    #[test_case("sum = fold (fn acc x -> acc + x) 0\nsum [\n  1,\n2\n]", "3" ; "bracket newline test")]
+    #[test_case("import std::math\nstd::math::clamp 0 10 (std::math::abs (-12))", "10" ; "standard library import test")]
+    #[test_case("引入 std::math；std::math::max 1 2", "2" ; "mandarin import test")]
+    fn compound_eval_test(source_code: &'static str, expected_result: &'static str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+
+        assert_eq!(result.to_string(), expected_result);
+    }
+
+    #[test_case("[1, 2].[2]", "Runtime error: Index 2 is out of bounds for list of length 2" ; "index out of bounds test")]
+    #[test_case("[1, 2].[0.5]", "Runtime error: Index 0.5 is out of bounds for list of length 2" ; "fractional index test")]
+    fn runtime_error_test(source_code: &'static str, expected_message: &'static str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        let result = compilation.eval();
+
+        assert_eq!(result.to_string(), "<Error>");
+        assert_eq!(
+            compilation.holder.diagonistic_units[0].to_string(),
+            format!("Error: {}", expected_message)
+        );
+    }
+
     #[test_case("1 || true", &["Cannot apply logical OR on type \"number\" and \"bool\""] ; "type check test A")]
+    #[test_case("[1, true]", &["List elements must have the same type, found \"number\" and \"bool\""] ; "list element type test")]
+    #[test_case("(1).[0]", &["Cannot index into type \"number\""] ; "index type test")]
+    #[test_case("map (fn x -> x + 1) [\"a\"]", &["Cannot apply argument of type \"List<string>\" to type \"List<number> -> List<number>\""] ; "map argument type test")]
+    #[test_case("foo", &["Unknown identifier \"foo\""] ; "unknown identifier test")]
+    #[test_case("{ a: 1 }.b", &["Type \"{ a: number }\" has no field \"b\""] ; "missing field test")]
+    #[test_case("{ a: 1, a: 2 }", &["Duplicate record field \"a\""] ; "duplicate field test")]
+    #[test_case("{ { a: 1 } with a: true }", &["Cannot update type \"{ a: number }\" with fields of type \"{ a: bool, .. }\""] ; "record update type test")]
+    #[test_case("(1, 2).2", &["Type \"(number, number)\" has no position 2"] ; "tuple position type test")]
+    #[test_case("(1, 2) == (1, true)", &["Cannot check equality on type \"(number, number)\" and \"(number, bool)\""] ; "tuple equality type test")]
+    #[test_case("(fn x -> x + 1) == (fn x -> x + 1)", &["Cannot check equality on type \"number -> number\" holding functions"] ; "function equality type test")]
+    #[test_case("let f = fn x -> x + 1 in ([f], { a: (f, 1) }.a) != ([f], (f, 2))", &["Cannot check equality on type \"(List<number -> number>, (number -> number, number))\" holding functions"] ; "nested function equality type test")]
+    #[test_case("type Handler = Handler (number -> number) in Handler (fn x -> x) == Handler (fn x -> x)", &["Cannot check equality on type \"Handler\" holding functions"] ; "constructor function equality type test")]
+    #[test_case("type Shape = Circle number | Square number in match Circle 1 with | Circle r -> r", &["Non-exhaustive match, missing pattern \"Square _\""] ; "non-exhaustive match test")]
+    #[test_case("type Maybe a = None | Some a in match Some true with | Some true -> 1 | None -> 0", &["Non-exhaustive match, missing pattern \"Some false\""] ; "non-exhaustive nested match test")]
+    #[test_case("match 1 with | 0 -> 1", &["Non-exhaustive match, missing pattern \"_\""] ; "non-exhaustive number match test")]
+    #[test_case("type Shape = Circle number in match 1 with | Circle r -> r", &["Cannot match type \"number\" against pattern of type \"Shape\""] ; "pattern type test")]
+    #[test_case("type Shape = Circle number in match Circle 1 with | Circle -> 1", &["Constructor \"Circle\" expects 1 fields, found 0"] ; "constructor arity test")]
+    #[test_case("match 1 with | Circle r -> r", &["Unknown constructor \"Circle\""] ; "unknown constructor test")]
+    #[test_case("match 1 with | 0 -> 1 | _ -> true", &["Match arms must have the same type, found \"number\" and \"bool\""] ; "match arm type test")]
+    #[test_case("type Shape = Circle Radius in 1", &["Unknown type \"Radius\""] ; "unknown type test")]
+    #[test_case("if 1 then 1 else 2", &["Condition must be of type \"bool\", found \"number\""] ; "if condition type test")]
+    #[test_case("if true then 1 else \"a\"", &["If branches must have the same type, found \"number\" and \"string\""] ; "if branch type test")]
+    #[test_case("let rec x = 1 in x", &["Recursive binding \"x\" must be a function"] ; "let rec value test")]
+    #[test_case("let rec f x = f in f", &["Recursive binding \"f\" is used as type \"'b\" but defined as \"'c -> 'b\""] ; "let rec infinite type test")]
+    #[test_case("fn f -> (f 1, f \"a\")", &["Cannot apply argument of type \"string\" to type \"number -> 'c\""] ; "lambda monomorphism test")]
+    #[test_case("match get 0 [1] with | Some n -> n", &["Non-exhaustive match, missing pattern \"None\""] ; "non-exhaustive option test")]
+    #[test_case("(1 + true, if 1 then 2 else 3)", &["Cannot apply addition on type \"number\" and \"bool\"", "Condition must be of type \"bool\", found \"number\""] ; "independent errors test")]
+    #[test_case("match 1 with | Circle r -> r + true", &["Unknown constructor \"Circle\"", "Cannot apply addition on type \"unidentified\" and \"bool\""] ; "erroneous pattern variable test")]
+    #[test_case("option_unwrap_or \"a\" (Some 1)", &["Cannot apply argument of type \"Option<number>\" to type \"Option<string> -> string\""] ; "option unwrap type test")]
     fn parsing_error_test(source_code: &'static str, expected_messages: &[&'static str]) {
         let mut diagnostic_holder = DiagnosticHolder::new();
-        let mut lexer = Lexer::new(source_code.trim().to_string());
-        let tokens = lexer.lex(&mut diagnostic_holder);
+        let tokens = cst::lex(source_code.trim(), &mut diagnostic_holder);
 
         assert!(diagnostic_holder.success());
 
@@ -85,16 +223,1311 @@ mod test {
             expected_messages.len()
         );
 
-        for message in diagnostic_holder
+        for (unit, expected) in diagnostic_holder
             .diagonistic_units
             .iter()
             .zip(expected_messages)
         {
-            match message {
-                (unit, expected) => {
-                    if let Unit::Error(string) = unit {
-                        assert_eq!(string, expected);
-                    }
+            if let Unit::Error(string, _) = unit {
+                assert_eq!(string, expected);
+            }
+        }
+    }
+
+    #[test_case("match 1 with | _ -> 1 | 0 -> 2", "Unreachable match arm \"0\"" ; "unreachable wildcard arm test")]
+    #[test_case("type Maybe a = None | Some a in match None with | Some _ -> 1 | None -> 0 | Some 1 -> 2", "Unreachable match arm \"Some 1\"" ; "unreachable constructor arm test")]
+    fn warning_test(source_code: &'static str, expected_message: &'static str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        compilation.eval();
+
+        assert!(compilation.holder.success());
+
+        assert_eq!(
+            compilation
+                .holder
+                .diagonistic_units
+                .iter()
+                .map(Unit::message)
+                .collect::<Vec<&str>>(),
+            vec![expected_message]
+        );
+    }
+
+    #[test_case("sum (map (fn x -> pow x 2) (range 1 4))", "14" ; "prelude list and math test")]
+    #[test_case("(maximum [3, 9, 2], head [], find is_even [1, 3, 4])", "(Some 9, None, Some 4)" ; "prelude option test")]
+    #[test_case("(take 2 [1, 2, 3], drop 2 [1, 2, 3], flat_map (fn x -> [x, x]) [1, 2])", "([1, 2], [3], [1, 1, 2, 2])" ; "prelude slicing test")]
+    #[test_case("(split \",\" \"a,b,,c\", words \" 你好  世界 \", reverse_string \"中文\")", "([\"a\", \"b\", \"\", \"c\"], [\"你好\", \"世界\"], \"文中\")" ; "prelude string test")]
+    #[test_case("(split \", \" \"a, b,c, \", split \"::\" \"std::math\")", "([\"a\", \"b,c\", \"\"], [\"std\", \"math\"])" ; "prelude multi-character split test")]
+    #[test_case("(pow 4 0.5, pow 2 (0 - 1), pow 0 0)", "(2, 0.5, 1)" ; "prelude fractional and negative pow test")]
+    #[test_case("(abs (0 - 3), min 1 2, max 1 2, clamp 0 10 12)", "(3, 1, 2, 10)" ; "prelude math re-export test")]
+    #[test_case("head xs = 0\n(head [1], all is_odd [1, 3])", "(0, true)" ; "prelude shadowing test")]
+    #[test_case("import std::prelude\nstd::prelude::gcd 12 18", "6" ; "explicit prelude import test")]
+    fn prelude_eval_test(source_code: &'static str, expected_result: &'static str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+
+        assert_eq!(result.to_string(), expected_result);
+    }
+
+    #[test]
+    fn without_prelude_test() {
+        let mut compilation = Compilation::new("sum [1, 2]".to_string()).without_prelude();
+        compilation.eval();
+
+        assert_eq!(
+            compilation.holder.diagonistic_units,
+            vec![Unit::Error(
+                "Unknown identifier \"sum\"".to_string(),
+                Some(0..3)
+            )]
+        );
+    }
+
+    #[test_case("1 + 2", "number" ; "number entry type test")]
+    #[test_case("map (fn x -> x + 1)", "List<number> -> List<number>" ; "prelude entry type test")]
+    #[test_case("double x = x * 2\n(double 1, \"a\")", "(number, string)" ; "definition entry type test")]
+    fn entry_type_test(source_code: &'static str, expected_type: &'static str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        let bound_expression = compilation.bind_entry();
+
+        assert!(compilation.holder.success());
+        assert_eq!(
+            bound_expression.map(|expression| expression.get_type().to_string()),
+            Some(expected_type.to_string())
+        );
+    }
+
+    #[test_case("f x = 1\nf x = 2\nf 1", &["Duplicate top-level definition \"f\""] ; "duplicate definition test")]
+    #[test_case("1\n2", &["Unexpected parsing error: A program can only have one entry expression."] ; "multiple entry test")]
+    #[test_case("x = x + 1\nx", &["Recursive binding \"x\" must be a function"] ; "recursive value test")]
+    #[test_case("x = 1", &["Runtime error: Program has no entry expression"] ; "missing entry test")]
+    #[test_case("f x = x +\n1", &["Unexpected parsing error: Unexpected end of line"] ; "unindented continuation test")]
+    fn program_error_test(source_code: &'static str, expected_messages: &[&'static str]) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        compilation.eval();
+
+        assert_eq!(
+            compilation
+                .holder
+                .diagonistic_units
+                .iter()
+                .map(|unit| unit.to_string())
+                .collect::<Vec<String>>(),
+            expected_messages
+                .iter()
+                .map(|message| format!("Error: {}", message))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test_case(&[("main", "import geometry\ngeometry::area 2"), ("geometry", "export area r = r * r * pi\npi = 3")], "12" ; "module import test")]
+    #[test_case(&[("main", "import shapes\nmatch shapes::square 2 with\n  | shapes::Circle r -> r\n  | shapes::Square w -> w * w"), ("shapes", "export type Shape = Circle number | Square number\nexport square w = Square w")], "4" ; "module type test")]
+    #[test_case(&[("main", "import shapes\nshapes::Square 2"), ("shapes", "匯出 type Shape = Circle number | Square number")], "shapes::Square 2" ; "module constructor value test")]
+    #[test_case(&[("main", "import a\nimport b\na::twice 1 + b::inc 1"), ("a", "import b\nexport twice x = b::inc (b::inc x)"), ("b", "export inc x = x + 1")], "5" ; "transitive import test")]
+    #[test_case(&[("main", "import a\nf x = x\na::f 1"), ("a", "export f x = f2 x\nf2 x = x * 10")], "10" ; "module namespace test")]
+    #[test_case(&[("main", "import tools::text\ntools::text::greet \"你好\""), ("tools::text", "export greet name = concat [name] [\"!\"]")], "[\"你好\", \"!\"]" ; "nested module test")]
+    fn module_eval_test(modules: &[(&'static str, &'static str)], expected_result: &'static str) {
+        let resolver = modules
+            .iter()
+            .fold(MemoryResolver::new(), |resolver, (name, source)| {
+                resolver.with(name, source)
+            });
+        let mut compilation = Compilation::with_resolver("main", Box::new(resolver));
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+
+        assert_eq!(result.to_string(), expected_result);
+    }
+
+    #[test_case(&[("main", "import a\na::secret")], &["Cannot find module \"a\""] ; "missing module test")]
+    #[test_case(&[("main", "import a\na::secret"), ("a", "secret = 1")], &["Module \"a\" does not export \"secret\""] ; "private definition test")]
+    #[test_case(&[("main", "import a\na::Hidden"), ("a", "type T = Hidden")], &["Module \"a\" does not export \"Hidden\""] ; "private type test")]
+    #[test_case(&[("main", "a::f 1"), ("a", "export f x = x")], &["Module \"a\" is not imported"] ; "not imported test")]
+    #[test_case(&[("main", "import a\n1"), ("a", "import b"), ("b", "import a")], &["Import cycle detected: a -> b -> a"] ; "import cycle test")]
+    #[test_case(&[("main", "import main\n1")], &["Import cycle detected: main -> main"] ; "self import test")]
+    fn module_error_test(
+        modules: &[(&'static str, &'static str)],
+        expected_messages: &[&'static str],
+    ) {
+        let resolver = modules
+            .iter()
+            .fold(MemoryResolver::new(), |resolver, (name, source)| {
+                resolver.with(name, source)
+            });
+        let mut compilation = Compilation::with_resolver("main", Box::new(resolver));
+        compilation.eval();
+
+        assert_eq!(
+            compilation
+                .holder
+                .diagonistic_units
+                .iter()
+                .map(|unit| unit.to_string())
+                .collect::<Vec<String>>(),
+            expected_messages
+                .iter()
+                .map(|message| format!("Error: {}", message))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn file_module_test() {
+        let root = std::env::temp_dir().join(format!("collage-module-test-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("util")).unwrap();
+        std::fs::write(
+            root.join("main.clg"),
+            "import util::numbers\nutil::numbers::double 21",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("util").join("numbers.clg"),
+            "export double x = x * 2",
+        )
+        .unwrap();
+
+        let mut compilation = Compilation::from_path(root.join("main.clg")).unwrap();
+        let result = compilation.eval();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), "42");
+    }
+
+    #[test]
+    fn byte_order_mark_test() {
+        let root = std::env::temp_dir().join(format!("collage-bom-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("main.clg"),
+            "\u{feff}import numbers\nnumbers::double 21",
+        )
+        .unwrap();
+        std::fs::write(root.join("numbers.clg"), "\u{feff}export double x = x * 2").unwrap();
+        std::fs::write(root.join("invalid.clg"), [0xff, 0xfe]).unwrap();
+
+        let mut compilation = Compilation::from_path(root.join("main.clg")).unwrap();
+        let result = compilation.eval();
+        let invalid = Compilation::from_path(root.join("invalid.clg"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), "42");
+        assert_eq!(
+            invalid.err().map(|error| error.kind()),
+            Some(std::io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn check_all_errors_test() {
+        let mut compilation = Compilation::new(
+            "half x = x / \"2\"\nsign n = if n < 0 then \"-\" else 1\nmain = (half 4, sign 1)"
+                .to_string(),
+        );
+
+        assert!(!compilation.check());
+        assert_eq!(
+            compilation.holder.diagonistic_units,
+            vec![
+                Unit::Error(
+                    "Cannot apply division on type \"number\" and \"string\"".to_string(),
+                    Some(9..16)
+                ),
+                Unit::Error(
+                    "If branches must have the same type, found \"string\" and \"number\""
+                        .to_string(),
+                    Some(29..50)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_test() {
+        let root = std::env::temp_dir().join(format!("collage-check-test-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("util")).unwrap();
+        std::fs::write(
+            root.join("main.clg"),
+            "import util::numbers\nutil::numbers::double 1 / 0",
+        )
+        .unwrap();
+        std::fs::write(root.join("util/numbers.clg"), "export double x = x * 2").unwrap();
+        std::fs::write(root.join("invalid.clg"), "double 1").unwrap();
+        std::fs::write(root.join("notes.txt"), "not a source file").unwrap();
+
+        let files = source_files(&root).unwrap();
+        let mut main = Compilation::from_path_in(&root, root.join("main.clg"))
+            .unwrap()
+            .with_arithmetic(ArithmeticPolicy::Error);
+        let main_success = main.check();
+        let mut invalid = Compilation::from_path_in(&root, root.join("invalid.clg")).unwrap();
+        let invalid_success = invalid.check();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                root.join("invalid.clg"),
+                root.join("main.clg"),
+                root.join("util/numbers.clg")
+            ]
+        );
+        assert!(main_success);
+        assert!(main.runtime_error().is_none());
+        assert_eq!(
+            main.holder.diagonistic_units,
+            vec![Unit::Warning("Division by zero".to_string(), Some(21..48))]
+        );
+        assert!(!invalid_success);
+        assert_eq!(
+            invalid.holder.diagonistic_units,
+            vec![Unit::Error(
+                "Unknown identifier \"double\"".to_string(),
+                Some(0..6)
+            )]
+        );
+    }
+
+    #[test_case(&["double x = x * 2", "double 21"], "42" ; "session definition test")]
+    #[test_case(&["x = 1; y = x + 1", "x = 10", "(x, y)"], "(10, 11)" ; "session redefinition test")]
+    #[test_case(&["import std::math", "std::math::abs (-3)"], "3" ; "session import test")]
+    #[test_case(&["type Shape = Circle number | Square number", "area s = match s with\n  | Circle r -> r * r * 3\n  | Square w -> w * w", "area (Square 2)"], "4" ; "session type test")]
+    #[test_case(&["x = 1", "x = oops", "x"], "1" ; "session error test")]
+    #[test_case(&["x = 1 # one\n# two\ny = x + 1", "y # three"], "2" ; "session comment test")]
+    #[test_case(&["x = 1\nf y = x + y", "x = \"a\"", "import std::math\nf (std::math::abs 1)"], "2" ; "session broken redefinition test")]
+    fn session_test(inputs: &[&str], expected: &str) {
+        let mut session = Session::new();
+        let (last, inputs) = inputs.split_last().unwrap();
+
+        for input in inputs {
+            assert!(session.eval(input).1.is_none());
+        }
+
+        let (holder, result) = session.eval(last);
+
+        assert!(holder.success());
+        assert_eq!(
+            result.map(|result| result.to_string()),
+            Some(expected.to_string())
+        );
+    }
+
+    #[test_case("(1 +\n", true ; "open parenthesis test")]
+    #[test_case("[1, 2", true ; "open list test")]
+    #[test_case("\"你好", true ; "unterminated string test")]
+    #[test_case("let x = 1", true ; "unfinished let test")]
+    #[test_case("match x with", true ; "unfinished match test")]
+    #[test_case("f x =", true ; "unfinished definition test")]
+    #[test_case("1 *", true ; "trailing operator test")]
+    #[test_case("(1 +\n  2)", false ; "complete parenthesis test")]
+    #[test_case("let x = 1\n  in x", false ; "complete let test")]
+    #[test_case("1 +* 2", false ; "invalid input test")]
+    #[test_case("", false ; "empty input test")]
+    fn incomplete_input_test(input: &str, expected: bool) {
+        assert_eq!(is_incomplete(input), expected);
+    }
+
+    #[test]
+    fn session_completions_test() {
+        let mut session = Session::new();
+        session.eval("double x = x * 2\ntype Shape = Circle number | Square number");
+        let completions = session.completions();
+
+        for name in [
+            "double", "Circle", "map", "sum", "Some", "有", "match", "函數", "true",
+        ] {
+            assert!(completions.contains(&name.to_string()), "{:}", name);
+        }
+
+        assert!(!completions.iter().any(|name| name.contains("::")));
+        assert!(completions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test_case("if x then 1 else \"a\"", "\x1b[38;5;13mif\x1b[0m x \x1b[38;5;13mthen\x1b[0m \x1b[38;5;14m1\x1b[0m \x1b[38;5;13melse\x1b[0m \x1b[38;5;10m\"a\"\x1b[0m" ; "keyword highlight test")]
+    #[test_case("f (Some true) + 2", "f (\x1b[38;5;11mSome\x1b[0m \x1b[38;5;14mtrue\x1b[0m) \x1b[38;5;12m+\x1b[0m \x1b[38;5;14m2\x1b[0m" ; "operator highlight test")]
+    #[test_case("令 x = \"未完", "\x1b[38;5;13m令\x1b[0m x \x1b[38;5;12m=\x1b[0m \x1b[38;5;9m\"未完\x1b[0m" ; "error highlight test")]
+    #[test_case("x # 註解", "x \x1b[38;5;8m# 註解\x1b[0m" ; "comment highlight test")]
+    fn highlight_test(source: &str, expected: &str) {
+        assert_eq!(highlight(source), expected);
+    }
+
+    #[test_case("f   x=x+1", "f x = x + 1\n" ; "spacing format test")]
+    #[test_case("a = 1;b = 2\n\n\nc = (3)", "a = 1\nb = 2\n\nc = (3)\n" ; "item format test")]
+    #[test_case("g = fn x y -> x", "g x y = x\n" ; "definition parameters format test")]
+    #[test_case("f x = match x with | Some (Some y) -> y | _ -> 0", "f x = match x with\n  | Some (Some y) -> y\n  | _ -> 0\n" ; "match format test")]
+    #[test_case("f x =\n  let y = match x with\n        | 1 -> 2\n        | _ -> 3 in y", "f x =\n  let y = match x with\n    | 1 -> 2\n    | _ -> 3\n  in y\n" ; "multiline let format test")]
+    #[test_case("type T a=A (a->a) | B {x:number,y:List<a>}", "type T a = A (a -> a) | B { x: number, y: List<a> }\n" ; "type format test")]
+    #[test_case("令 r = {a：1，b：[1,2]} 在 { r 帶 a: -1 }.a", "令 r = { a：1，b：[1，2] } 在 { r 帶 a：-1 }.a\n" ; "mandarin format test")]
+    #[test_case("# header\n\n# two\nf   x=x # trailing\n# between\n\ng = [1, # one\n  2]\n# end\n", "# header\n\n# two\nf x = x # trailing\n# between\n\ng = [1, # one\n  2]\n# end\n" ; "comment format test")]
+    #[test_case("f x = # why\n  match x with\n    # first\n    | 1 -> 2 # arm\n    | _ -> let y = 3 # three\n      in y", "f x = # why\n  match x with\n    # first\n    | 1 -> 2 # arm\n    | _ -> let y = 3 # three\n    in y\n" ; "inner comment format test")]
+    fn format_test(source: &str, expected: &str) {
+        let formatted = format_source(source, None, &mut DiagnosticHolder::new());
+
+        assert_eq!(formatted.as_deref(), Some(expected));
+    }
+
+    #[test_case("import std::math\nexport area s = match s with\n  | Circle r -> r * r\n  | Square (w, h) -> w * h\n\nmain = area (Circle 2)" ; "declarations round trip test")]
+    #[test_case("let rec go n = if n == 0 then [] else [n] in map (fn x -> x * 2) (go 3)" ; "let rec round trip test")]
+    #[test_case("令 遞迴 f x = 如果 x > 0 那麼 f (x - 1) 否則 函數 y -> !y 在 f 1" ; "mandarin round trip test")]
+    #[test_case("type Pair a b = Pair a b in let p = (1, \"a\") in (p.0, xs.[0], {}, -p.0)" ; "type declaration round trip test")]
+    #[test_case("g x = match x with | A -> let y = match x with | A -> f (fn z -> match z with | _ -> 1) | _ -> 2 in y | _ -> 0" ; "nested match round trip test")]
+    fn format_round_trip_test(source: &str) {
+        let parse = |source: &str| {
+            let mut holder = DiagnosticHolder::new();
+            let tokens = cst::lex(source, &mut holder);
+            let program = Parser::new(tokens).parse_program(&mut holder);
+
+            assert!(holder.success(), "{:?}", holder.diagonistic_units);
+            program
+        };
+
+        for spelling in [None, Some(Spelling::Ascii), Some(Spelling::Mandarin)] {
+            let formatted = format_source(source, spelling, &mut DiagnosticHolder::new()).unwrap();
+            let reformatted = format_source(&formatted, None, &mut DiagnosticHolder::new());
+
+            assert_eq!(parse(&formatted), parse(source), "{:}", formatted);
+            assert_eq!(reformatted.as_deref(), Some(formatted.as_str()));
+        }
+    }
+
+    #[test_case("# before\n\n＃ 之前\nf x = x\n" ; "comments before items test")]
+    #[test_case("f x = match x with # what\n  # first\n  | Some y -> (y, # one\n    [1, 2 # two\n  ])\n  | _ -> { a: 1 # three\n  }" ; "comments inside items test")]
+    #[test_case("f x = x # after\n# between\ntype T = A | B # types\n\n# end\n" ; "comments after items test")]
+    fn format_comment_test(source: &str) {
+        let comments = |source: &str| {
+            cst::parse(source, &mut DiagnosticHolder::new())
+                .leaves()
+                .into_iter()
+                .filter(|leaf| {
+                    leaf.token()
+                        .is_some_and(|token| token.literal.starts_with(['#', '＃']))
+                })
+                .map(|leaf| leaf.text().to_string())
+                .collect::<Vec<String>>()
+        };
+        let formatted = format_source(source, None, &mut DiagnosticHolder::new()).unwrap();
+        let reformatted = format_source(&formatted, None, &mut DiagnosticHolder::new());
+
+        assert_eq!(comments(&formatted), comments(source), "{:}", formatted);
+        assert_eq!(
+            Compilation::new(formatted.clone()).lex_parse_program(),
+            Compilation::new(source.to_string()).lex_parse_program()
+        );
+        assert_eq!(reformatted.as_deref(), Some(formatted.as_str()));
+    }
+
+    #[test]
+    fn format_spelling_test() {
+        let source = "f x = match x with | (a, b) -> fn y -> a\n";
+        let mandarin = format_source(
+            source,
+            Some(Spelling::Mandarin),
+            &mut DiagnosticHolder::new(),
+        );
+
+        assert_eq!(
+            mandarin.as_deref(),
+            Some("f x = 匹配 x 帶\n  ｜ (a，b) -> 函數 y -> a\n")
+        );
+        assert_eq!(
+            format_source(
+                &mandarin.unwrap(),
+                Some(Spelling::Ascii),
+                &mut DiagnosticHolder::new()
+            )
+            .as_deref(),
+            Some("f x = match x with\n  | (a, b) -> fn y -> a\n")
+        );
+    }
+
+    #[test]
+    fn format_error_test() {
+        let mut holder = DiagnosticHolder::new();
+
+        assert_eq!(format_source("f x = (1 +", None, &mut holder), None);
+        assert!(!holder.success());
+    }
+
+    #[test]
+    fn format_std_test() {
+        for file in source_files("src/std").unwrap() {
+            let source = std::fs::read_to_string(&file).unwrap();
+            let formatted = format_source(&source, None, &mut DiagnosticHolder::new());
+
+            assert_eq!(
+                formatted.as_deref(),
+                Some(source.as_str()),
+                "{:}",
+                file.display()
+            );
+        }
+    }
+
+    #[test_case("import std::math\n\nexport area s = match s with\n  | Circle r -> r * r\n  | _ -> 0\n\narea (Circle 2)\n" ; "declarations cst test")]
+    #[test_case("  let rec  f x =\tif x > 0 then f (x - 1) else { r with a: [1 , 2] }.a   in f 1  " ; "whitespace cst test")]
+    #[test_case("令 遞迴 f x = 如果 x > 0 那麼 f (x - 1) 否則 函數 y -> !y 在 f 1；type T a = A (a, number) | B {x: List<a>} in B" ; "mandarin cst test")]
+    #[test_case("let x = in ) + [1, 2\nf = match x with | (Some -> \"a" ; "erroneous cst test")]
+    #[test_case("# 註解\nf x = # why\n  x + 1 # one\n\n# end" ; "comment cst test")]
+    fn cst_lossless_test(source: &str) {
+        let tree = cst::parse(source, &mut DiagnosticHolder::new());
+        let leaves = tree.leaves();
+
+        assert_eq!(tree.text(), source);
+        assert_eq!(tree.range(), 0..source.len());
+        assert_eq!(
+            leaves.iter().map(|leaf| leaf.text()).collect::<String>(),
+            source
+        );
+
+        // The tree keeps what the abstract syntax tree is derived from.
+        assert_eq!(
+            cst::lower_program(&tree),
+            Compilation::new(source.to_string()).lex_parse_program()
+        );
+    }
+
+    #[test]
+    fn parse_program_lossless_test() {
+        let source = "s = \"a，b\"；f x = (x，{ y：\"「引」\" })\n";
+        let mut holder = DiagnosticHolder::new();
+        let tree = Parser::new(cst::lex(source, &mut holder)).parse_syntax(&mut holder);
+
+        assert!(holder.success());
+        assert_eq!(tree.text(), source);
+        assert_eq!(
+            Parser::new(cst::lex(source, &mut holder)).parse_program(&mut holder),
+            cst::lower_program(&tree)
+        );
+    }
+
+    #[test_case(7, NodeKind::Binary, "1 + 2" ; "binary node test")]
+    #[test_case(3, NodeKind::Parenthesis, "( 1 + 2 )" ; "parenthesis node test")]
+    #[test_case(0, NodeKind::Path, "f" ; "path node test")]
+    #[test_case(15, NodeKind::Literal, "3" ; "literal node test")]
+    fn cst_node_test(offset: usize, kind: NodeKind, text: &str) {
+        let source = "f ( 1 + 2 ) *  3 ";
+        let node = cst::parse(source, &mut DiagnosticHolder::new())
+            .node_at(offset)
+            .unwrap();
+
+        assert_eq!(node.kind(), kind);
+        assert_eq!(node.text(), text);
+        assert_eq!(&source[node.range()], text);
+        assert_eq!(
+            node.ancestors().last().map(|root| root.kind()),
+            Some(NodeKind::Program)
+        );
+    }
+
+    #[test]
+    fn cst_replace_test() {
+        let source = "f (1 + 2) * 3";
+        let tree = cst::parse(source, &mut DiagnosticHolder::new());
+        let two = tree.node_at(7).unwrap();
+        let forty = cst::parse("40", &mut DiagnosticHolder::new()).nodes()[0].clone();
+        let root = two.replace_with(forty.green().as_ref().clone());
+        let edited = Node::new(root);
+
+        assert_eq!(edited.text(), "f (1 + 40) * 3");
+        assert_eq!(tree.text(), source);
+
+        // Nodes outside the replaced one are shared with the original tree.
+        let callee = |tree: &Node| tree.node_at(0).unwrap().green().clone();
+
+        assert!(Rc::ptr_eq(&callee(&tree), &callee(&edited)));
+    }
+
+    const DOCUMENT: &str = "file:///project/main.clg";
+
+    /// Runs a language server over the given messages and returns the messages it sent back.
+    fn language_server(messages: &[serde_json::Value]) -> Vec<serde_json::Value> {
+        let mut input = vec![];
+        let mut output = vec![];
+
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        run(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let mut replies = vec![];
+
+        while let Some(content) = read_message(&mut output).unwrap() {
+            replies.push(serde_json::from_str(&content).unwrap());
+        }
+
+        replies
+    }
+
+    fn open_document(text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": DOCUMENT, "languageId": "collage", "version": 1, "text": text },
+            },
+        })
+    }
+
+    fn document_request(method: &str, line: usize, character: usize) -> serde_json::Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": DOCUMENT },
+                "position": { "line": line, "character": character },
+            },
+        })
+    }
+
+    #[test]
+    fn lsp_lifecycle_test() {
+        let replies = language_server(&[
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "workspace/symbol", "params": {} }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "exit" }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+        ]);
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["id"], 1);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(
+            replies[0]["result"]["capabilities"]["semanticTokensProvider"]["legend"]["tokenTypes"]
+                [0],
+            "keyword"
+        );
+        assert_eq!(replies[1]["error"]["code"], -32601);
+        assert_eq!(
+            replies[2],
+            serde_json::json!({ "jsonrpc": "2.0", "id": 3, "result": null })
+        );
+    }
+
+    #[test_case("main = 1", &[] ; "no diagnostics test")]
+    #[test_case("main = 1 +", &[(1, "Unexpected parsing error: Unexpected end of input.")] ; "parsing diagnostics test")]
+    #[test_case("main = \"a\" + 1", &[(1, "Cannot apply addition on type \"string\" and \"number\"")] ; "type diagnostics test")]
+    fn lsp_diagnostics_test(text: &str, expected: &[(u64, &str)]) {
+        let replies = language_server(&[open_document(text)]);
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+
+        assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(replies[0]["params"]["uri"], DOCUMENT);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (
+                    diagnostic["severity"].as_u64().unwrap(),
+                    diagnostic["message"].as_str().unwrap()
+                ))
+                .collect::<Vec<(u64, &str)>>(),
+            expected
+        );
+    }
+
+    #[test_case("main = 1 +", (0, 10), (0, 10) ; "end of input diagnostic range test")]
+    #[test_case("main = \"a\" + 1", (0, 7), (0, 14) ; "expression diagnostic range test")]
+    #[test_case("double x = x * 2\nmain = doubel 1", (1, 7), (1, 13) ; "identifier diagnostic range test")]
+    #[test_case("main =\n  match 1 with\n  | Circle r -> r", (2, 4), (2, 12) ; "pattern diagnostic range test")]
+    fn lsp_diagnostic_range_test(text: &str, start: (u64, u64), end: (u64, u64)) {
+        let replies = language_server(&[open_document(text)]);
+        let range = &replies[0]["params"]["diagnostics"][0]["range"];
+
+        assert_eq!(
+            (&range["start"]["line"], &range["start"]["character"]),
+            (&start.0.into(), &start.1.into())
+        );
+        assert_eq!(
+            (&range["end"]["line"], &range["end"]["character"]),
+            (&end.0.into(), &end.1.into())
+        );
+    }
+
+    #[test]
+    fn lsp_did_change_test() {
+        let replies = language_server(&[
+            open_document("main = 1 +"),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": DOCUMENT, "version": 2 },
+                    "contentChanges": [{ "text": "main = 1 + 2" }],
+                },
+            }),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didClose",
+                "params": { "textDocument": { "uri": DOCUMENT } },
+            }),
+            document_request("textDocument/hover", 0, 0),
+        ]);
+
+        assert_eq!(
+            replies[0]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(replies[1]["params"]["diagnostics"], serde_json::json!([]));
+        assert_eq!(replies[2]["params"]["diagnostics"], serde_json::json!([]));
+        assert_eq!(replies[3]["result"], serde_json::Value::Null);
+    }
+
+    #[test_case(0, 1, Some("double : number -> number") ; "definition hover test")]
+    #[test_case(0, 7, Some("x : number") ; "parameter hover test")]
+    #[test_case(1, 8, Some("map : (number -> number) -> List<number> -> List<number>") ; "prelude hover test")]
+    #[test_case(1, 13, Some("double : number -> number") ; "reference hover test")]
+    #[test_case(2, 11, Some("y : string") ; "let hover test")]
+    #[test_case(3, 18, Some("Circle : number -> Shape") ; "constructor hover test")]
+    #[test_case(1, 5, None ; "operator hover test")]
+    fn lsp_hover_test(line: usize, character: usize, expected: Option<&str>) {
+        let text = "double x = x * 2\nmain = map double [1, 2]\nname = let y = \"a\" in y\ntype Shape = Circle number\nshape = Circle 1";
+        let replies = language_server(&[
+            open_document(text),
+            document_request("textDocument/hover", line, character),
+        ]);
+        let contents = replies[1]["result"]["contents"]["value"].as_str();
+
+        assert_eq!(
+            contents,
+            expected
+                .map(|expected| format!("```collage\n{:}\n```", expected))
+                .as_deref()
+        );
+    }
+
+    #[test_case(0, 11, Some((0, 7)) ; "parameter definition test")]
+    #[test_case(1, 16, Some((0, 0)) ; "top-level definition test")]
+    #[test_case(1, 27, Some((1, 11)) ; "let definition test")]
+    #[test_case(2, 38, Some((2, 33)) ; "pattern definition test")]
+    #[test_case(2, 26, Some((3, 13)) ; "constructor definition test")]
+    #[test_case(0, 0, Some((0, 0)) ; "definition itself test")]
+    #[test_case(1, 22, None ; "number definition test")]
+    fn lsp_definition_test(line: usize, character: usize, expected: Option<(u64, u64)>) {
+        let text = "double x = x * 2\nmain = let y = double 1 in y + double y\nunwrap s = match s with | Circle r -> r\ntype Shape = Circle number";
+        let replies = language_server(&[
+            open_document(text),
+            document_request("textDocument/definition", line, character),
+        ]);
+        let result = &replies[1]["result"];
+
+        match expected {
+            Some((line, character)) => {
+                assert_eq!(result["uri"], DOCUMENT);
+                assert_eq!(result["range"]["start"]["line"], line);
+                assert_eq!(result["range"]["start"]["character"], character);
+            }
+            None => assert_eq!(result, &serde_json::Value::Null),
+        }
+    }
+
+    #[test_case(1, 14, "x : number", Some((1, 2)) ; "shadowing parameter test")]
+    #[test_case(1, 23, "x : number", Some((1, 10)) ; "shadowing let test")]
+    #[test_case(0, 0, "x : string", Some((0, 0)) ; "shadowed definition test")]
+    #[test_case(2, 13, "x : string", Some((0, 0)) ; "shadowed reference test")]
+    fn lsp_scope_test(
+        line: usize,
+        character: usize,
+        expected_hover: &str,
+        expected_definition: Option<(u64, u64)>,
+    ) {
+        let text = "x = \"top\"\nf x = let x = x + 1 in x\nmain = (f 1, x)";
+        let replies = language_server(&[
+            open_document(text),
+            document_request("textDocument/hover", line, character),
+            document_request("textDocument/definition", line, character),
+        ]);
+        let start = &replies[2]["result"]["range"]["start"];
+
+        assert_eq!(
+            replies[1]["result"]["contents"]["value"],
+            format!("```collage\n{:}\n```", expected_hover)
+        );
+        assert_eq!(
+            expected_definition,
+            start["line"].as_u64().zip(start["character"].as_u64())
+        );
+    }
+
+    #[test]
+    fn lsp_completion_test() {
+        let replies = language_server(&[
+            open_document("double x = x * 2\ntype Shape = Circle number"),
+            document_request("textDocument/completion", 0, 0),
+        ]);
+        let items = replies[1]["result"].as_array().unwrap();
+
+        for (label, kind) in [
+            ("double", 6),
+            ("map", 6),
+            ("Circle", 20),
+            ("match", 14),
+            ("匹配", 14),
+        ] {
+            assert!(
+                items.contains(&serde_json::json!({ "label": label, "kind": kind })),
+                "{:}",
+                label
+            );
+        }
+    }
+
+    #[test]
+    fn lsp_semantic_tokens_test() {
+        let replies = language_server(&[
+            open_document("令 x = 1 在\n  \"a\" + Some"),
+            document_request("textDocument/semanticTokens/full", 0, 0),
+        ]);
+
+        assert_eq!(
+            replies[1]["result"]["data"],
+            serde_json::json!([
+                0, 0, 1, 0, 0, 0, 4, 1, 4, 0, 0, 2, 1, 1, 0, 0, 2, 1, 0, 0, 1, 2, 3, 2, 0, 0, 4, 1,
+                4, 0, 0, 2, 4, 3, 0
+            ])
+        );
+    }
+
+    #[test_case("f   x=x", Some("f x = x\n") ; "formatting edit test")]
+    #[test_case("f x = x\n", None ; "formatted document test")]
+    fn lsp_formatting_test(text: &str, expected: Option<&str>) {
+        let replies = language_server(&[
+            open_document(text),
+            document_request("textDocument/formatting", 0, 0),
+        ]);
+        let edits = replies[1]["result"].as_array().unwrap();
+
+        match expected {
+            Some(expected) => {
+                assert_eq!(edits.len(), 1);
+                assert_eq!(edits[0]["newText"], expected);
+                assert_eq!(edits[0]["range"]["end"]["character"], text.len());
+            }
+            None => assert!(edits.is_empty()),
+        }
+    }
+
+    #[test]
+    fn session_lexing_error_test() {
+        let (holder, result) = Session::new().eval("\"abc");
+
+        assert!(result.is_none());
+        assert_eq!(
+            holder.diagonistic_units,
+            vec![Unit::Error(
+                "Unterminated string literal.".to_string(),
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn session_bindings_test() {
+        let mut session = Session::new();
+        session.eval("double x = x * 2; name = \"a;b\"");
+        session.eval("import std::math\ndouble x = x + x");
+        session.eval("double 1");
+
+        assert_eq!(
+            session.source(),
+            "name = \"a;b\"\nimport std::math\ndouble x = x + x\n"
+        );
+        assert_eq!(
+            session.bindings(),
+            vec![
+                ("name".to_string(), "string".to_string()),
+                ("double".to_string(), "number -> number".to_string())
+            ]
+        );
+    }
+
+    #[test_case("args", vec![], "[]" ; "empty arguments test")]
+    #[test_case("(length args, args)", vec!["甲", "b"], "(2, [\"甲\", \"b\"])" ; "string arguments test")]
+    #[test_case("args = 1\nargs", vec!["a"], "1" ; "shadowed arguments test")]
+    fn arguments_test(source_code: &str, arguments: Vec<&str>, expected: &str) {
+        let mut compilation = Compilation::new(source_code.to_string())
+            .with_arguments(arguments.into_iter().map(str::to_string).collect());
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[test]
+    fn arguments_before_host_test() {
+        let mut compilation = Compilation::new("(args, double 2)".to_string())
+            .with_arguments(vec!["a".to_string()])
+            .with_host(host_environment());
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), "([\"a\"], 4)");
+    }
+
+    fn host_environment() -> HostEnvironment {
+        let mut host = HostEnvironment::new();
+        host.register_function("double", "number -> number", |x: f64| x * 2.0)
+            .register_function(
+                "greet",
+                "string -> number -> string",
+                |name: String, times: i64| format!("{}{}", name, "!".repeat(times as usize)),
+            )
+            .register_function("first", "List a -> Option a", |xs: Vec<Value>| {
+                xs.first().cloned()
+            })
+            .register_function(
+                "split_at",
+                "number -> string -> (string, string)",
+                |at: i64, text: String| {
+                    let (left, right) = text.split_at(at as usize);
+                    (left.to_string(), right.to_string())
+                },
+            )
+            .register_function("broken", "number -> number", |text: String| text)
+            .register_raw_function("fail", "string -> a", 1, |arguments| {
+                Err(RuntimeError::Host(format!(
+                    "Host failure: {}",
+                    arguments[0]
+                )))
+            })
+            .register_constant("answer", "數字", 42.0)
+            .register_constant("names", "List string", vec!["甲", "乙"]);
+        host
+    }
+
+    #[test_case("double 21", "42" ; "host function test")]
+    #[test_case("map (greet \"hi\") [1, 2]", "[\"hi!\", \"hi!!\"]" ; "host partial application test")]
+    #[test_case("(first [1, 2], first [\"a\"], first [])", "(Some 1, Some \"a\", None)" ; "host polymorphic function test")]
+    #[test_case("split_at 2 \"abcd\"", "(\"ab\", \"cd\")" ; "host tuple conversion test")]
+    #[test_case("double answer + length names", "86" ; "host constant test")]
+    #[test_case("double x = x\ndouble 21", "21" ; "host shadowing test")]
+    #[test_case("import a\na::quadruple 2", "8" ; "host module test")]
+    fn host_eval_test(source_code: &'static str, expected_result: &'static str) {
+        let resolver = MemoryResolver::new()
+            .with("main", source_code)
+            .with("a", "export quadruple x = double (double x)");
+        let mut compilation =
+            Compilation::with_resolver("main", Box::new(resolver)).with_host(host_environment());
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+
+        assert_eq!(result.to_string(), expected_result);
+    }
+
+    #[test_case("double \"a\"", "Error: Cannot apply argument of type \"string\" to type \"number -> number\"" ; "host argument type test")]
+    #[test_case("broken 1", "Error: Runtime error: Host function \"broken\" cannot accept argument 1" ; "host conversion error test")]
+    #[test_case("fail \"oops\"", "Error: Runtime error: Host failure: oops" ; "host runtime error test")]
+    fn host_error_test(source_code: &'static str, expected_message: &'static str) {
+        let mut compilation =
+            Compilation::new(source_code.to_string()).with_host(host_environment());
+        compilation.eval();
+
+        assert_eq!(
+            compilation
+                .holder
+                .diagonistic_units
+                .iter()
+                .map(|unit| unit.to_string())
+                .collect::<Vec<String>>(),
+            vec![expected_message]
+        );
+    }
+
+    #[test]
+    fn host_declaration_error_test() {
+        let mut host = HostEnvironment::new();
+        host.register_function("add", "number -> number", |x: f64, y: f64| x + y)
+            .register_constant("origin", "Point", 0.0);
+
+        let mut compilation = Compilation::new("1".to_string()).with_host(host);
+        compilation.eval();
+
+        assert_eq!(
+            compilation.holder.diagonistic_units,
+            vec![
+                Unit::Error(
+                    "Host function \"add\" takes 2 arguments but is declared as \"number -> number\""
+                        .to_string(),
+                    None
+                ),
+                Unit::Error("Unknown type \"Point\"".to_string(), None),
+            ]
+        );
+    }
+
+    #[test_case("loop n = loop (n + 1)\nloop 0", Limits { fuel: Some(10000), ..Limits::default() }, RuntimeError::OutOfFuel(10000) ; "fuel limit test")]
+    #[test_case("depth n = if n == 0 then 0 else 1 + depth (n - 1)\ndepth 1000", Limits { max_depth: Some(20), ..Limits::default() }, RuntimeError::RecursionLimit(20) ; "recursion limit test")]
+    #[test_case("length (range 0 1000000000000)", Limits { max_collection_size: Some(1000), ..Limits::default() }, RuntimeError::CollectionTooLarge { size: 1000000000000, limit: 1000 } ; "range size limit test")]
+    #[test_case("grow xs = grow (concat xs xs)\ngrow [1]", Limits { max_collection_size: Some(1000), ..Limits::default() }, RuntimeError::CollectionTooLarge { size: 1024, limit: 1000 } ; "concat size limit test")]
+    #[test_case("repeat 100 \"ab\"", Limits { max_collection_size: Some(150), ..Limits::default() }, RuntimeError::CollectionTooLarge { size: 200, limit: 150 } ; "string size limit test")]
+    #[test_case("loop n = loop (n + 1)\nloop 0", Limits { time_limit: Some(Duration::from_millis(50)), ..Limits::default() }, RuntimeError::Timeout(Duration::from_millis(50)) ; "time limit test")]
+    #[test_case("length (range 0 100000)", Limits { fuel: Some(10000), ..Limits::default() }, RuntimeError::OutOfFuel(10000) ; "range fuel limit test")]
+    #[test_case("length (range 0 10000000000000)", Limits { time_limit: Some(Duration::from_millis(50)), ..Limits::default() }, RuntimeError::Timeout(Duration::from_millis(50)) ; "range time limit test")]
+    #[test_case("range 10000000000000000 10000000000000002", Limits { max_collection_size: Some(1000), fuel: Some(10000), ..Limits::default() }, RuntimeError::InvalidRange(10000000000000000., 10000000000000002.) ; "imprecise range test")]
+    #[test_case("range 0 (pow 10 400)", Limits::default(), RuntimeError::InvalidRange(0., f64::INFINITY) ; "infinite range test")]
+    fn limits_test(source_code: &'static str, limits: Limits, expected_error: RuntimeError) {
+        let mut compilation = Compilation::new(source_code.to_string()).with_limits(limits);
+        compilation.eval();
+
+        assert_eq!(compilation.runtime_error(), Some(&expected_error));
+        assert_eq!(
+            compilation.holder.diagonistic_units,
+            vec![Unit::Error(
+                format!("Runtime error: {}", expected_error),
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn within_limits_test() {
+        let limits = Limits {
+            fuel: Some(100000),
+            max_depth: Some(100),
+            max_collection_size: Some(100),
+            time_limit: Some(Duration::from_secs(10)),
+        };
+        let mut compilation = Compilation::new(
+            "count n = if n == 0 then 0 else count (n - 1)\ncount 1000".to_string(),
+        )
+        .with_limits(limits);
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), "0");
+    }
+
+    #[test_case("1 + 2 * 3 - 4 / 2 % 3" ; "arithmetic differential test")]
+    #[test_case("(!true || false, 1 < 2 && 2 >= 2, \"a\" != \"b\", [1, 2] == [1, 2])" ; "logic differential test")]
+    #[test_case("let x = 1 in let f = fn y -> x + y in let x = 10 in f x" ; "closure capture differential test")]
+    #[test_case("(fn a b c -> fn d -> a + b + c + d) 1 2 3 4" ; "nested capture differential test")]
+    #[test_case("let rec fact n = if n == 0 then 1 else n * fact (n - 1) in map fact (range 0 6)" ; "let rec differential test")]
+    #[test_case("let rec f n = map (fn x -> if n == 0 then x else f (n - 1)) [n] in f 3" ; "recursive capture differential test")]
+    #[test_case("count n acc = if n == 0 then acc else count (n - 1) (acc + 1)\ncount 100000 0" ; "tail call differential test")]
+    #[test_case("type Tree = Leaf | Node Tree number Tree\ninsert x t = match t with\n  | Leaf -> Node Leaf x Leaf\n  | Node l y r -> if x < y then Node (insert x l) y r else Node l y (insert x r)\nto_list t = match t with\n  | Leaf -> []\n  | Node l x r -> concat (to_list l) (concat [x] (to_list r))\nto_list (fold (fn t x -> insert x t) Leaf [3, 1, 2])" ; "tree differential test")]
+    #[test_case("match ([1, 2], (\"a\", true)) with | ([x, y], (_, false)) -> x | ([x, y], (s, true)) -> y | _ -> 0" ; "nested pattern differential test")]
+    #[test_case("let p = { x: 1, y: 2 } in let q = { p with y: 3 } in (p.y, q.y, (1, \"b\").1)" ; "record differential test")]
+    #[test_case("[10, 20].[1] + [1].[1]" ; "runtime error differential test")]
+    #[test_case("even n = n == 0 || odd (n - 1)\nodd n = n != 0 && even (n - 1)\n(even 4, odd 100001, false && [1].[3] == 1, true || [1].[3] == 1)" ; "short circuit differential test")]
+    #[test_case("(Some, option_map Some (Some 1), result_and_then parse_number (Ok \"2\"))" ; "constructor value differential test")]
+    #[test_case("x = 1" ; "missing entry differential test")]
+    #[test_case("even n = if n == 0 then true else odd (n - 1)\nodd n = if n == 0 then false else even (n - 1)\n(even 10, words \"a b\")" ; "definitions differential test")]
+    fn differential_test(source_code: &'static str) {
+        let run = |backend| {
+            let mut compilation = Compilation::new(source_code.to_string()).with_backend(backend);
+            let result = compilation.eval().to_string();
+
+            (result, compilation.holder.diagonistic_units)
+        };
+
+        assert_eq!(run(Backend::Bytecode), run(Backend::TreeWalker));
+    }
+
+    #[test]
+    fn deep_recursion_test() {
+        let mut compilation = Compilation::new(
+            "depth n = if n == 0 then 0 else 1 + depth (n - 1)\ndepth 100000".to_string(),
+        )
+        .with_backend(Backend::Bytecode);
+        let result = compilation.eval();
+
+        assert!(compilation.holder.success());
+        assert_eq!(result.to_string(), "100000");
+    }
+
+    #[test]
+    fn disassemble_test() {
+        let mut compilation = Compilation::new(
+            "add x y = x + y\nmatch Some 2 with | Some n -> add n 1 | None -> 0".to_string(),
+        )
+        .without_prelude();
+
+        assert_eq!(
+            compilation.disassemble().unwrap(),
+            "== add ==\n\
+             0000 CLOSURE 0    ; fn x\n\
+             0001 RETURN\n\
+             \n\
+             == fn x ==\n\
+             0000 CLOSURE 0 local 0    ; fn y\n\
+             0001 RETURN\n\
+             \n\
+             == fn y ==\n\
+             0000 CAPTURE 0\n\
+             0001 LOCAL 0\n\
+             0002 ADD\n\
+             0003 RETURN\n\
+             \n\
+             == <entry> ==\n\
+             0000 CONSTRUCTOR 0 1    ; Some\n\
+             0001 CONSTANT 0    ; 2\n\
+             0002 CALL\n\
+             0003 LOCAL 0\n\
+             0004 MATCH 0 12    ; Some n\n\
+             0005 GLOBAL 1    ; add\n\
+             0006 LOCAL 1\n\
+             0007 CALL\n\
+             0008 CONSTANT 1    ; 1\n\
+             0009 TAIL_CALL\n\
+             0010 SLIDE 2\n\
+             0011 JUMP 18\n\
+             0012 LOCAL 0\n\
+             0013 MATCH 1 17    ; None\n\
+             0014 CONSTANT 2    ; 0\n\
+             0015 SLIDE 1\n\
+             0016 JUMP 18\n\
+             0017 NO_MATCH 0\n\
+             0018 RETURN\n"
+        );
+    }
+
+    /// Instantiates the module emitted for `source_code` and reads the result of its entry
+    /// expression as the same kind of value as `expected`.
+    fn run_wasm(source_code: &str, expected: &Value) -> Value {
+        let mut compilation = Compilation::new(source_code.to_string());
+        let bytes = compilation
+            .emit_wasm()
+            .expect("program should compile to WebAssembly");
+
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, &bytes[..]).unwrap();
+        let mut store = wasmi::Store::new(&engine, ());
+        let instance = wasmi::Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        let main = instance.get_func(&store, ENTRY_EXPORT).unwrap();
+        let memory = instance.get_memory(&store, MEMORY_EXPORT).unwrap();
+        let mut results = [wasmi::Value::I32(0)];
+
+        if let Value::Number(_) = expected {
+            results[0] = wasmi::Value::F64(0.0.into());
+        }
+
+        main.call(&mut store, &[], &mut results).unwrap();
+
+        match (expected, &results[0]) {
+            (Value::Number(_), wasmi::Value::F64(number)) => Value::Number(f64::from(*number)),
+            (Value::Bool(_), wasmi::Value::I32(bool)) => Value::Bool(*bool != 0),
+            (Value::String(_), wasmi::Value::I32(address)) => {
+                let data = memory.data(&store);
+                let address = *address as usize;
+                let length =
+                    u32::from_le_bytes(data[address..address + 4].try_into().unwrap()) as usize;
+
+                Value::String(
+                    String::from_utf8(data[address + 4..address + 4 + length].to_vec()).unwrap(),
+                )
+            }
+            (_, result) => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test_case("1 + 2 * 3 - 4 / 2", Value::Number(5.) ; "arithmetic wasm test")]
+    #[test_case("7 % 3 + -(1.5)", Value::Number(-0.5) ; "remainder wasm test")]
+    #[test_case("let x = 5 in let y = x * x in y + x", Value::Number(30.) ; "let wasm test")]
+    #[test_case("fact n = if n == 0 then 1 else n * fact (n - 1)\nfact 10", Value::Number(3628800.) ; "recursive definition wasm test")]
+    #[test_case("even n = if n == 0 then true else odd (n - 1)\nodd n = if n == 0 then false else even (n - 1)\nodd 7", Value::Bool(true) ; "mutual recursion wasm test")]
+    #[test_case("x = 3\nsquare n = n * n\nsquare x", Value::Number(9.) ; "constant definition wasm test")]
+    #[test_case("abs (0 - 3) + max 1 2", Value::Number(5.) ; "prelude wasm test")]
+    #[test_case("!(1 > 2) && (true || false) && is_even 4", Value::Bool(true) ; "bool wasm test")]
+    #[test_case("even n = n == 0 || odd (n - 1)\nodd n = n != 0 && even (n - 1)\neven 6", Value::Bool(true) ; "short circuit wasm test")]
+    #[test_case("match 2 with | 1 -> 10 | 2 -> 20 | n -> n", Value::Number(20.) ; "match wasm test")]
+    #[test_case("\"abc\" == \"abc\" && \"abc\" != \"abd\" && \"ab\" != \"abc\"", Value::Bool(true) ; "string equality wasm test")]
+    #[test_case("greet name = match name with | \"cat\" -> \"meow\" | _ -> \"hello \" \ngreet \"cat\"", Value::String("meow".to_string()) ; "string wasm test")]
+    fn wasm_eval_test(source_code: &str, expected: Value) {
+        assert_eq!(
+            run_wasm(source_code, &expected).to_string(),
+            expected.to_string()
+        );
+    }
+
+    #[test_case("[1, 2].[0]", "Cannot compile lists to WebAssembly" ; "list wasm error test")]
+    #[test_case("let f = fn x -> x + 1 in f 1", "Cannot compile values of type \"number -> number\" to WebAssembly" ; "lambda wasm error test")]
+    #[test_case("add x y = x + y\nadd 1", "Cannot compile values of type \"number -> number\" to WebAssembly" ; "partial application wasm error test")]
+    #[test_case("length [1]", "Cannot compile \"length\" to WebAssembly, only top-level definitions can be called" ; "builtin wasm error test")]
+    fn wasm_error_test(source_code: &str, expected_message: &str) {
+        let mut compilation = Compilation::new(source_code.to_string());
+
+        assert_eq!(compilation.emit_wasm(), None);
+        assert_eq!(
+            compilation.holder.diagonistic_units[0],
+            Unit::Error(expected_message.to_string(), None)
+        );
+    }
+
+    #[cfg(feature = "playground")]
+    #[wasm_bindgen_test::wasm_bindgen_test(unsupported = test)]
+    fn playground_test() {
+        let json = crate::playground::evaluate("double x = x * 2\ndouble 21");
+
+        assert!(json.starts_with(
+            "{\"tokens\":[{\"literal\":\"double\",\"type\":\"Identifier\"},{\"literal\":\"x\",\"type\":\"Identifier\"},"
+        ));
+        assert!(json.contains("\"syntaxTree\":\"Program {\\n"));
+        assert!(json.contains("\"boundTree\":\"BoundProgram {\\n"));
+        assert!(!json.contains("std::prelude"));
+        assert!(json.ends_with("\"diagnostics\":[],\"result\":\"42\"}"));
+    }
+
+    #[cfg(feature = "playground")]
+    #[wasm_bindgen_test::wasm_bindgen_test(unsupported = test)]
+    fn playground_error_test() {
+        let json = crate::playground::evaluate("1 + \"a\"");
+
+        assert!(json.ends_with(
+            "\"diagnostics\":[{\"severity\":\"error\",\"message\":\"Cannot apply addition on type \\\"number\\\" and \\\"string\\\"\"}],\"result\":null}"
+        ));
+    }
+
+    #[test_case("(20 + 1) * 5 - -(3) % 2 / +4" ; "arithmetic optimization test")]
+    #[test_case("(1 / 0, 0 % 0, 0.1 + 0.2, 2 - 2 * 1.5)" ; "float optimization test")]
+    #[test_case("(1 < 2, 2 <= 2, 3 > 4, 3 >= 4, 1 == 1, \"a\" != \"a\", true == false)" ; "comparison optimization test")]
+    #[test_case("let x = 5 > 3 in (true && x, x && true, false || x, x || false, !!x, !(!x))" ; "bool identity optimization test")]
+    #[test_case("(false && [1].[3] == 1, true || [1].[3] == 1)" ; "short circuit optimization test")]
+    #[test_case("f x = if 1 + 1 == 2 then x * (2 + 3) else 0\nf 4" ; "constant condition optimization test")]
+    #[test_case("let rec go n = if n == 0 then 0 else go (n - (0 + 1)) in go (10 * 10)" ; "lambda body optimization test")]
+    fn optimization_equivalence_test(source_code: &'static str) {
+        let run = |compilation: Compilation| {
+            let mut compilation = compilation;
+            let result = compilation.eval().to_string();
+            let errors = compilation
+                .holder
+                .diagonistic_units
+                .into_iter()
+                .filter(|unit| matches!(unit, Unit::Error(..)))
+                .collect::<Vec<Unit>>();
+
+            (result, errors)
+        };
+
+        assert_eq!(
+            run(Compilation::new(source_code.to_string())),
+            run(Compilation::new(source_code.to_string()).without_optimization())
+        );
+    }
+
+    #[test_case("if 1 < 2 then 1 else 2", &["Condition is always true"] ; "true condition warning test")]
+    #[test_case("f x = if !true then x else 0\nf (if 2 > 3 then 1 else 2)", &["Condition is always false", "Condition is always false"] ; "false condition warning test")]
+    #[test_case("f x = if x > 1 then 1 else 2\nf 3", &[] ; "variable condition warning test")]
+    fn constant_condition_warning_test(source_code: &str, expected_warnings: &[&str]) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        compilation.eval();
+
+        assert_eq!(
+            compilation.holder.diagonistic_units,
+            expected_warnings
+                .iter()
+                .map(|warning| Unit::Warning(warning.to_string(), None))
+                .collect::<Vec<Unit>>()
+        );
+        let mut unoptimized = Compilation::new(source_code.to_string()).without_optimization();
+        unoptimized.eval();
+
+        assert!(unoptimized.holder.diagonistic_units.is_empty());
+    }
+
+    #[test]
+    fn constant_folding_test() {
+        let disassemble = |compilation: Compilation| compilation.without_prelude().disassemble();
+
+        assert_eq!(
+            disassemble(Compilation::new("(20 + 1) * 5".to_string())).unwrap(),
+            "== <entry> ==\n0000 CONSTANT 0    ; 105\n0001 RETURN\n"
+        );
+        assert_eq!(
+            disassemble(Compilation::new("(20 + 1) * 5".to_string()).without_optimization())
+                .unwrap(),
+            "== <entry> ==\n\
+             0000 CONSTANT 0    ; 20\n\
+             0001 CONSTANT 1    ; 1\n\
+             0002 ADD\n\
+             0003 CONSTANT 2    ; 5\n\
+             0004 MULTIPLY\n\
+             0005 RETURN\n"
+        );
+    }
+
+    #[test_case("false && [1].[3] == 1", "false" ; "false and folding test")]
+    #[test_case("true || [1].[3] == 1", "true" ; "true or folding test")]
+    fn short_circuit_folding_test(source_code: &str, expected: &str) {
+        let mut compilation = Compilation::new(source_code.to_string()).without_prelude();
+
+        assert_eq!(
+            compilation.disassemble().unwrap(),
+            format!(
+                "== <entry> ==\n0000 CONSTANT 0    ; {:}\n0001 RETURN\n",
+                expected
+            )
+        );
+
+        // The right operand is skipped, not evaluated and failing on its index.
+        for compilation in [
+            Compilation::new(source_code.to_string()),
+            Compilation::new(source_code.to_string()).without_optimization(),
+        ] {
+            let mut compilation = compilation;
+            let result = compilation.eval();
+
+            assert!(compilation.holder.diagonistic_units.is_empty());
+            assert_eq!(result.to_string(), expected);
+        }
+    }
+
+    #[test_case("1 / 0", &["Division by zero"] ; "division warning test")]
+    #[test_case("5 % -(0.0)", &["Remainder by zero"] ; "remainder warning test")]
+    #[test_case("let x = 0 in 1 / x", &[] ; "variable divisor warning test")]
+    fn division_by_zero_warning_test(source_code: &str, expected_warnings: &[&str]) {
+        let mut compilation = Compilation::new(source_code.to_string());
+        compilation.eval();
+
+        assert_eq!(
+            compilation
+                .holder
+                .diagonistic_units
+                .iter()
+                .map(Unit::message)
+                .collect::<Vec<&str>>(),
+            expected_warnings
+        );
+    }
+
+    #[test_case("let x = 0 in (1 / x, 0 % x, pow 10 400 - pow 10 400)", ArithmeticPolicy::Ieee, Ok("(inf, NaN, NaN)") ; "ieee policy test")]
+    #[test_case("let x = 0 in 1 / x", ArithmeticPolicy::Error, Err(RuntimeError::DivisionByZero) ; "division error policy test")]
+    #[test_case("let x = 0 in 0 % x", ArithmeticPolicy::Error, Err(RuntimeError::DivisionByZero) ; "remainder error policy test")]
+    #[test_case("pow 10 400 - pow 10 400", ArithmeticPolicy::Error, Err(RuntimeError::NotANumber) ; "nan error policy test")]
+    #[test_case("(pow 10 400, 1 / 3)", ArithmeticPolicy::Error, Ok("(inf, 0.3333333333333333)") ; "error policy test")]
+    fn arithmetic_policy_test(
+        source_code: &str,
+        arithmetic: ArithmeticPolicy,
+        expected: std::result::Result<&str, RuntimeError>,
+    ) {
+        for backend in [Backend::Bytecode, Backend::TreeWalker] {
+            let mut compilation = Compilation::new(source_code.to_string())
+                .with_arithmetic(arithmetic)
+                .with_backend(backend);
+            let result = compilation.eval().to_string();
+
+            match &expected {
+                Ok(expected) => assert_eq!(result, *expected),
+                Err(error) => {
+                    assert_eq!(compilation.runtime_error(), Some(error));
+                    assert_eq!(
+                        compilation.holder.diagonistic_units,
+                        vec![Unit::Error(format!("Runtime error: {:}", error), None)]
+                    );
                 }
             }
         }
@@ -1,6 +1,7 @@
 use std::any::{Any, TypeId};
 use std::fmt::Display;
 
+use crate::highlight::{highlight_branch, highlight_node};
 use crate::parser::SyntaxNode;
 
 pub fn set_panic_hook() {
@@ -23,16 +24,15 @@ pub fn to_string<T: Display + 'static>(any: &dyn Any) -> Result<String, &'static
 }
 
 pub fn print_syntax_tree<T: SyntaxNode<T> + PartialEq>(
-    node: &Box<Option<T>>,
+    node: &Option<T>,
     mut indent: String,
     is_last: bool,
 ) {
     if let Some(syntax_node) = node.as_ref() {
         let marker = if is_last { "└──" } else { "├──" };
 
-        print!("{}", indent);
-        print!("{}", marker);
-        print!("{:}", syntax_node.as_string());
+        print!("{}", highlight_branch(&format!("{}{}", indent, marker)));
+        print!("{:}", highlight_node(&syntax_node.as_string()));
 
         syntax_node.print();
 
@@ -50,16 +50,16 @@ pub fn print_syntax_tree<T: SyntaxNode<T> + PartialEq>(
 
 pub fn get_syntax_tree<T: SyntaxNode<T> + PartialEq>(
     builder: &mut String,
-    node: &Box<Option<T>>,
+    node: &Option<T>,
     mut indent: String,
     is_last: bool,
 ) -> String {
     if let Some(syntax_node) = node.as_ref() {
         let marker = if is_last { "└──" } else { "├──" };
 
-        builder.push_str(&*indent);
+        builder.push_str(&indent);
         builder.push_str(marker);
-        builder.push_str(&*format!("{:}", syntax_node.as_string()));
+        builder.push_str(&syntax_node.as_string());
         builder.push('\n');
 
         indent.push_str(if is_last { "   " } else { "│  " });
@@ -0,0 +1,432 @@
+use crate::binder::BoundPattern;
+use crate::bytecode::{Capture, CompiledFunction, CompiledProgram, Instruction};
+use crate::host::HostEnvironment;
+use crate::runtime::{
+    expect_bool, expect_list, expect_number, expect_record, index_list, ArithmeticPolicy, Budget,
+    Builtin, EvalResult, Function, Limits, Machine, RuntimeError, Value,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The function a frame runs, and where its slots start on the stack.
+struct Frame {
+    function: Rc<CompiledFunction>,
+    /// The closure being run, absent for top-level definitions and the entry expression.
+    callee: Option<Rc<Function>>,
+    ip: usize,
+    base: usize,
+}
+
+/// Runs bytecode produced by [`crate::bytecode::Compiler`]. Calls between compiled closures
+/// do not grow the Rust stack, only calls made by built-in functions do.
+pub struct Vm {
+    program: CompiledProgram,
+    /// Values of top-level definitions, used for names which are not bound locally.
+    globals: RefCell<HashMap<String, Value>>,
+    budget: Budget,
+    arithmetic: ArithmeticPolicy,
+}
+
+impl Vm {
+    pub fn new(program: CompiledProgram) -> Self {
+        Self {
+            program,
+            globals: RefCell::new(HashMap::new()),
+            budget: Budget::default(),
+            arithmetic: ArithmeticPolicy::default(),
+        }
+    }
+
+    /// Makes the functions and constants of `host` visible to the program. Top-level
+    /// definitions of the root module shadow them.
+    pub fn with_host(self, host: &HostEnvironment) -> Self {
+        self.globals.borrow_mut().extend(
+            host.items()
+                .iter()
+                .map(|item| (item.name.clone(), item.value.clone())),
+        );
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.budget = Budget::new(limits);
+        self
+    }
+
+    pub fn with_arithmetic(mut self, arithmetic: ArithmeticPolicy) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Evaluates the top-level definitions in order, then the entry expression.
+    pub fn evaluate(&self) -> EvalResult {
+        self.budget.start();
+
+        for (name, function) in &self.program.definitions {
+            let value = self.run(function.clone(), None, vec![])?;
+
+            self.globals.borrow_mut().insert(name.clone(), value);
+        }
+
+        match &self.program.entry {
+            Some(entry) => self.run(entry.clone(), None, vec![]),
+            None => Err(RuntimeError::MissingEntry),
+        }
+    }
+
+    /// Runs `function` until the frame it starts in returns.
+    fn run(
+        &self,
+        function: Rc<CompiledFunction>,
+        callee: Option<Rc<Function>>,
+        stack: Vec<Value>,
+    ) -> EvalResult {
+        let mut stack = stack;
+        let mut frames: Vec<Frame> = vec![];
+        let mut frame = Frame {
+            function,
+            callee,
+            ip: 0,
+            base: 0,
+        };
+
+        loop {
+            self.budget.step()?;
+
+            let function = frame.function.clone();
+            let instruction = &function.code[frame.ip];
+            frame.ip += 1;
+
+            match instruction {
+                Instruction::Constant(index) => stack.push(function.constants[*index].clone()),
+                Instruction::Local(slot) => stack.push(stack[frame.base + slot].clone()),
+                Instruction::Capture(index) => stack.push(captures(&frame)[*index].clone()),
+                Instruction::Current => stack.push(current(&frame)),
+                Instruction::Global(name) => stack.push(self.global(&function.names[*name])?),
+                Instruction::Constructor(name, 0) => {
+                    stack.push(Value::Data(function.names[*name].clone(), vec![]))
+                }
+                Instruction::Constructor(name, arity) => {
+                    stack.push(Value::Function(Rc::new(Function::Constructor {
+                        name: function.names[*name].clone(),
+                        arity: *arity,
+                        arguments: vec![],
+                    })))
+                }
+                Instruction::Negate => {
+                    let value = pop(&mut stack);
+                    stack.push(Value::Number(-expect_number(&value)));
+                }
+                Instruction::Not => {
+                    let value = pop(&mut stack);
+                    stack.push(Value::Bool(!expect_bool(&value)));
+                }
+                Instruction::Equal | Instruction::NotEqual => {
+                    let right = pop(&mut stack);
+                    let left = pop(&mut stack);
+
+                    stack.push(Value::Bool(
+                        left.equals(&right) == matches!(instruction, Instruction::Equal),
+                    ));
+                }
+                Instruction::Greater
+                | Instruction::GreaterEqual
+                | Instruction::Less
+                | Instruction::LessEqual => {
+                    let right = expect_number(&pop(&mut stack));
+                    let left = expect_number(&pop(&mut stack));
+
+                    stack.push(Value::Bool(match instruction {
+                        Instruction::Greater => left > right,
+                        Instruction::GreaterEqual => left >= right,
+                        Instruction::Less => left < right,
+                        _ => left <= right,
+                    }));
+                }
+                Instruction::Add
+                | Instruction::Subtract
+                | Instruction::Multiply
+                | Instruction::Divide
+                | Instruction::Remainder => {
+                    let right = expect_number(&pop(&mut stack));
+                    let left = expect_number(&pop(&mut stack));
+
+                    stack.push(match instruction {
+                        Instruction::Add => self.arithmetic.check(left + right, None),
+                        Instruction::Subtract => self.arithmetic.check(left - right, None),
+                        Instruction::Multiply => self.arithmetic.check(left * right, None),
+                        Instruction::Divide => self.arithmetic.check(left / right, Some(right)),
+                        _ => self.arithmetic.check(left % right, Some(right)),
+                    }?);
+                }
+                Instruction::List(length) => {
+                    let elements = stack.split_off(stack.len() - length);
+                    stack.push(Value::List(elements));
+                }
+                Instruction::Tuple(length) => {
+                    let elements = stack.split_off(stack.len() - length);
+                    stack.push(Value::Tuple(elements));
+                }
+                Instruction::Record(names) => {
+                    let values = stack.split_off(stack.len() - names.len());
+                    stack.push(Value::Record(
+                        names
+                            .iter()
+                            .map(|name| function.names[*name].clone())
+                            .zip(values)
+                            .collect(),
+                    ));
+                }
+                Instruction::RecordUpdate(names) => {
+                    let values = stack.split_off(stack.len() - names.len());
+                    let mut record = expect_record(&pop(&mut stack)).clone();
+                    record.extend(
+                        names
+                            .iter()
+                            .map(|name| function.names[*name].clone())
+                            .zip(values),
+                    );
+                    stack.push(Value::Record(record));
+                }
+                Instruction::Field(name) => {
+                    let record = pop(&mut stack);
+                    stack.push(expect_record(&record)[&function.names[*name]].clone());
+                }
+                Instruction::TupleField(position) => match pop(&mut stack) {
+                    Value::Tuple(elements) => stack.push(elements[*position].clone()),
+                    value => panic!("Evaluation error: expected tuple, found {:}", value),
+                },
+                Instruction::Index => {
+                    let index = expect_number(&pop(&mut stack));
+                    let list = pop(&mut stack);
+                    stack.push(index_list(expect_list(&list), index)?);
+                }
+                Instruction::Closure(index, captured) => {
+                    let captures = captured
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Local(slot) => stack[frame.base + slot].clone(),
+                            Capture::Capture(index) => captures(&frame)[*index].clone(),
+                            Capture::Current => current(&frame),
+                        })
+                        .collect();
+
+                    stack.push(Value::Function(Rc::new(Function::Compiled {
+                        function: function.functions[*index].clone(),
+                        captures,
+                    })));
+                }
+                Instruction::Call | Instruction::TailCall => {
+                    let argument = pop(&mut stack);
+                    let callee = pop(&mut stack);
+                    let tail = matches!(instruction, Instruction::TailCall);
+
+                    match &callee {
+                        Value::Function(callable) => match callable.as_ref() {
+                            Function::Compiled { function, .. } => {
+                                let next = Frame {
+                                    function: function.clone(),
+                                    callee: Some(callable.clone()),
+                                    ip: 0,
+                                    base: frame.base,
+                                };
+
+                                if tail {
+                                    stack.truncate(frame.base);
+                                    frame = next;
+                                } else {
+                                    self.budget.enter()?;
+                                    frames.push(std::mem::replace(
+                                        &mut frame,
+                                        Frame {
+                                            base: stack.len(),
+                                            ..next
+                                        },
+                                    ));
+                                }
+
+                                stack.push(argument);
+                            }
+                            callable => {
+                                let result = callable.apply_native(argument, self)?;
+
+                                if tail {
+                                    match self.return_from(
+                                        &mut frame,
+                                        &mut frames,
+                                        &mut stack,
+                                        result,
+                                    ) {
+                                        Some(result) => return Ok(result),
+                                        None => continue,
+                                    }
+                                }
+
+                                stack.push(result);
+                            }
+                        },
+                        _ => return Err(RuntimeError::NotCallable(callee.to_string())),
+                    }
+                }
+                Instruction::Return => {
+                    let result = pop(&mut stack);
+
+                    if let Some(result) =
+                        self.return_from(&mut frame, &mut frames, &mut stack, result)
+                    {
+                        return Ok(result);
+                    }
+                }
+                Instruction::Jump(target) => frame.ip = *target,
+                Instruction::JumpIfFalse(target) => {
+                    if !expect_bool(&pop(&mut stack)) {
+                        frame.ip = *target;
+                    }
+                }
+                Instruction::Match(pattern, target) => {
+                    let value = pop(&mut stack);
+                    let mut variables = vec![];
+
+                    if match_pattern(&function.patterns[*pattern], &value, &mut variables) {
+                        stack.extend(variables);
+                    } else {
+                        frame.ip = *target;
+                    }
+                }
+                Instruction::NoMatch(slot) => {
+                    return Err(RuntimeError::NoMatchingArm(
+                        stack[frame.base + slot].to_string(),
+                    ))
+                }
+                Instruction::Slide(count) => {
+                    let result = pop(&mut stack);
+                    stack.truncate(stack.len() - count);
+                    stack.push(result);
+                }
+                Instruction::Missing => return Err(RuntimeError::MissingExpression),
+            }
+        }
+    }
+
+    /// Leaves the current frame, returning the result once the frame `run` started in is left.
+    fn return_from(
+        &self,
+        frame: &mut Frame,
+        frames: &mut Vec<Frame>,
+        stack: &mut Vec<Value>,
+        result: Value,
+    ) -> Option<Value> {
+        stack.truncate(frame.base);
+
+        match frames.pop() {
+            Some(caller) => {
+                self.budget.leave();
+                *frame = caller;
+                stack.push(result);
+                None
+            }
+            None => Some(result),
+        }
+    }
+
+    fn global(&self, name: &str) -> EvalResult {
+        if let Some(value) = self.globals.borrow().get(name) {
+            Ok(value.clone())
+        } else if let Ok(builtin) = name.parse::<Builtin>() {
+            Ok(Value::Function(Rc::new(Function::Builtin {
+                builtin,
+                arguments: vec![],
+            })))
+        } else {
+            Err(RuntimeError::UnknownIdentifier(name.to_string()))
+        }
+    }
+}
+
+impl Machine for Vm {
+    fn apply(&self, function: &Value, argument: Value) -> EvalResult {
+        match function {
+            Value::Function(callable) => match callable.as_ref() {
+                Function::Compiled {
+                    function: compiled, ..
+                } => {
+                    self.budget.enter()?;
+                    let result = self.run(compiled.clone(), Some(callable.clone()), vec![argument]);
+                    self.budget.leave();
+
+                    result
+                }
+                callable => callable.apply_native(argument, self),
+            },
+            _ => Err(RuntimeError::NotCallable(function.to_string())),
+        }
+    }
+
+    fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    fn arithmetic(&self) -> ArithmeticPolicy {
+        self.arithmetic
+    }
+}
+
+// The compiler keeps track of the stack height, so an empty stack here is an internal error.
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("Evaluation error: stack underflow")
+}
+
+fn captures(frame: &Frame) -> &[Value] {
+    match frame.callee.as_deref() {
+        Some(Function::Compiled { captures, .. }) => captures,
+        _ => &[],
+    }
+}
+
+fn current(frame: &Frame) -> Value {
+    Value::Function(
+        frame
+            .callee
+            .clone()
+            .expect("Evaluation error: no closure is being run"),
+    )
+}
+
+/// Pushes the values of the pattern's variables in the order of
+/// [`crate::bytecode::collect_pattern_variables`] if `value` matches `pattern`.
+fn match_pattern(pattern: &BoundPattern, value: &Value, variables: &mut Vec<Value>) -> bool {
+    match (pattern, value) {
+        (BoundPattern::Wildcard, _) => true,
+        (BoundPattern::Variable(_), _) => {
+            variables.push(value.clone());
+            true
+        }
+        (BoundPattern::Number(number), Value::Number(value)) => {
+            number.parse::<f64>().is_ok_and(|number| number == *value)
+        }
+        (BoundPattern::Literal(string), Value::String(value)) => string == value,
+        (BoundPattern::Bool(bool), Value::Bool(value)) => {
+            bool.parse::<bool>().is_ok_and(|bool| bool == *value)
+        }
+        (BoundPattern::Constructor(name, patterns), Value::Data(value_name, values))
+            if name == value_name =>
+        {
+            match_patterns(patterns, values, variables)
+        }
+        (BoundPattern::Tuple(patterns), Value::Tuple(values))
+        | (BoundPattern::List(patterns), Value::List(values))
+            if patterns.len() == values.len() =>
+        {
+            match_patterns(patterns, values, variables)
+        }
+        _ => false,
+    }
+}
+
+fn match_patterns(patterns: &[BoundPattern], values: &[Value], variables: &mut Vec<Value>) -> bool {
+    patterns
+        .iter()
+        .zip(values)
+        .all(|(pattern, value)| match_pattern(pattern, value, variables))
+}
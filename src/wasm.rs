@@ -0,0 +1,697 @@
+use crate::binder::{BoundExpression, BoundPattern, BoundProgram, BoundType, TypeDestructable};
+use crate::diagnostic::DiagnosticHolder;
+use std::collections::{HashMap, VecDeque};
+use webassembly::*;
+
+/// Name of the exported function evaluating the entry expression.
+pub const ENTRY_EXPORT: &str = "main";
+/// Name of the exported linear memory holding strings.
+pub const MEMORY_EXPORT: &str = "memory";
+
+const ENTRY: &str = "<entry>";
+const STRING_EQUAL: &str = "<string equal>";
+const REMAINDER: &str = "<remainder>";
+
+const PAGE_SIZE: usize = 65536;
+const EXPORT_FUNCTION: u8 = 0x00;
+const EXPORT_MEMORY: u8 = 0x02;
+
+/// Lowers bound programs to WebAssembly modules.
+///
+/// Numbers are `f64`, bools are `i32`, and strings are `i32` addresses of their length, as a
+/// little-endian `u32`, followed by their UTF-8 bytes in the exported memory. Top-level
+/// definitions become functions taking all of their parameters at once, so they can only be
+/// called with all of their arguments. Only definitions reachable from the entry expression
+/// are emitted.
+#[derive(Default)]
+pub struct WasmCompiler<'a> {
+    definitions: HashMap<&'a str, &'a Option<BoundExpression>>,
+    /// Function indices by definition or helper name, in the order they are emitted.
+    indices: HashMap<String, u32>,
+    queue: VecDeque<String>,
+    /// Signatures as parameter types and result type.
+    types: Vec<(Vec<u8>, u8)>,
+    /// Type index of each function.
+    functions: Vec<u32>,
+    bodies: Vec<Vec<u8>>,
+    strings: HashMap<String, u32>,
+    data: Vec<u8>,
+}
+
+/// A function being lowered, with its locals counted after its parameters.
+struct Function {
+    parameters: usize,
+    locals: Vec<u8>,
+    scopes: Vec<(String, u32)>,
+    code: Vec<u8>,
+}
+
+impl Function {
+    fn new(parameters: &[(&str, BoundType)]) -> Self {
+        Self {
+            parameters: parameters.len(),
+            locals: vec![],
+            scopes: parameters
+                .iter()
+                .enumerate()
+                .map(|(index, (name, _))| (name.to_string(), index as u32))
+                .collect(),
+            code: vec![],
+        }
+    }
+
+    fn local(&self, name: &str) -> Option<u32> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, index)| *index)
+    }
+
+    fn new_local(&mut self, value_type: u8) -> u32 {
+        self.locals.push(value_type);
+        (self.parameters + self.locals.len() - 1) as u32
+    }
+
+    fn emit(&mut self, opcode: u8, immediate: impl TypeWasmExt) {
+        self.code.push(opcode);
+        self.code.extend(immediate.to_wasm_bytes());
+    }
+
+    /// The function body with its locals grouped by type.
+    fn finish(mut self) -> Vec<u8> {
+        let mut groups: Vec<(u32, u8)> = vec![];
+
+        for value_type in &self.locals {
+            match groups.last_mut() {
+                Some((count, last)) if last == value_type => *count += 1,
+                _ => groups.push((1, *value_type)),
+            }
+        }
+
+        self.code.push(END);
+
+        let mut body = vector(
+            groups
+                .into_iter()
+                .map(|(count, value_type)| {
+                    let mut group = count.to_wasm_bytes();
+                    group.push(value_type);
+                    group
+                })
+                .collect(),
+        );
+        body.extend(self.code);
+        body
+    }
+}
+
+impl<'a> WasmCompiler<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lowers `program` to the bytes of a module exporting its entry expression as
+    /// [`ENTRY_EXPORT`] and its memory as [`MEMORY_EXPORT`], reporting what cannot be lowered
+    /// to `holder`.
+    pub fn compile_program(
+        mut self,
+        program: &'a BoundProgram,
+        holder: &mut DiagnosticHolder,
+    ) -> Vec<u8> {
+        if program.entry.is_none() {
+            holder.error("Program has no entry expression");
+            return vec![];
+        }
+
+        self.definitions = program
+            .definitions
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .chain(std::iter::once((ENTRY, &program.entry)))
+            .collect();
+        self.function_index(ENTRY);
+
+        while let Some(name) = self.queue.pop_front() {
+            let (type_index, body) = match name.as_str() {
+                STRING_EQUAL => (self.type_index(vec![I32, I32], I32), string_equal()),
+                REMAINDER => (self.type_index(vec![F64, F64], F64), remainder()),
+                _ => self.compile_definition(&name, holder),
+            };
+
+            self.functions.push(type_index);
+            self.bodies.push(body);
+        }
+
+        self.module()
+    }
+
+    fn module(self) -> Vec<u8> {
+        let pages = self.data.len().div_ceil(PAGE_SIZE).max(1);
+        let mut module = [MAGIC_NUMBER, VERSION_1].concat();
+
+        module.extend(section(
+            SECTION_TYPE,
+            vector(
+                self.types
+                    .into_iter()
+                    .map(|(parameters, result)| {
+                        let mut signature = vec![FUNC];
+                        signature.extend(vector(parameters.into_iter().map(|p| vec![p]).collect()));
+                        signature.extend([1, result]);
+                        signature
+                    })
+                    .collect(),
+            ),
+        ));
+        module.extend(section(
+            SECTION_FUNCTION,
+            vector(self.functions.iter().map(u32::to_wasm_bytes).collect()),
+        ));
+        module.extend(section(
+            SECTION_MEMORY,
+            vector(vec![[vec![0], pages.to_wasm_bytes()].concat()]),
+        ));
+        module.extend(section(
+            SECTION_EXPORT,
+            vector(vec![
+                [name(ENTRY_EXPORT), vec![EXPORT_FUNCTION, 0]].concat(),
+                [name(MEMORY_EXPORT), vec![EXPORT_MEMORY, 0]].concat(),
+            ]),
+        ));
+        module.extend(section(
+            SECTION_CODE,
+            vector(
+                self.bodies
+                    .into_iter()
+                    .map(|body| [body.len().to_wasm_bytes(), body].concat())
+                    .collect(),
+            ),
+        ));
+
+        if !self.data.is_empty() {
+            let mut segment = vec![0, I32_CONST];
+            segment.extend(0i32.to_wasm_bytes());
+            segment.push(END);
+            segment.extend(self.data.len().to_wasm_bytes());
+            segment.extend(self.data);
+
+            module.extend(section(SECTION_DATA, vector(vec![segment])));
+        }
+
+        module
+    }
+
+    /// The index of a definition or helper, queueing it to be emitted on first use.
+    fn function_index(&mut self, name: &str) -> u32 {
+        if let Some(index) = self.indices.get(name) {
+            return *index;
+        }
+
+        let index = self.indices.len() as u32;
+        self.indices.insert(name.to_string(), index);
+        self.queue.push_back(name.to_string());
+        index
+    }
+
+    fn type_index(&mut self, parameters: Vec<u8>, result: u8) -> u32 {
+        let signature = (parameters, result);
+
+        match self.types.iter().position(|other| *other == signature) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(signature);
+                (self.types.len() - 1) as u32
+            }
+        }
+    }
+
+    /// The address of a string in the data segment, laid out on first use.
+    fn string(&mut self, string: &str) -> u32 {
+        if let Some(address) = self.strings.get(string) {
+            return *address;
+        }
+
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+
+        let address = self.data.len() as u32;
+        self.data.extend((string.len() as u32).to_le_bytes());
+        self.data.extend(string.as_bytes());
+        self.strings.insert(string.to_string(), address);
+        address
+    }
+
+    fn compile_definition(&mut self, name: &str, holder: &mut DiagnosticHolder) -> (u32, Vec<u8>) {
+        let definition = self.definitions[name];
+        let (parameters, body) = match name {
+            ENTRY => (vec![], definition),
+            _ => split_parameters(definition),
+        };
+
+        let parameter_types = parameters
+            .iter()
+            .map(|(_, parameter_type)| value_type(parameter_type, holder).unwrap_or(F64))
+            .collect();
+        let result = value_type(&body.get_type(), holder).unwrap_or(F64);
+        let mut function = Function::new(&parameters);
+
+        self.compile(&mut function, body, holder);
+
+        (self.type_index(parameter_types, result), function.finish())
+    }
+
+    fn compile(
+        &mut self,
+        function: &mut Function,
+        expression: &Option<BoundExpression>,
+        holder: &mut DiagnosticHolder,
+    ) {
+        let Some(expression) = expression else {
+            function.code.push(UNREACHABLE);
+            return;
+        };
+
+        match expression {
+            BoundExpression::Literal(string) => {
+                let address = self.string(string);
+                function.emit(I32_CONST, address as i32);
+            }
+            BoundExpression::Bool(string) => {
+                function.emit(I32_CONST, i32::from(string.parse::<bool>().unwrap()))
+            }
+            BoundExpression::Number(string) => {
+                function.emit(F64_CONST, string.parse::<f64>().unwrap())
+            }
+            BoundExpression::Identifier(name, bound_type) => match function.local(name) {
+                Some(index) => {
+                    value_type(bound_type, holder);
+                    function.emit(LOCAL_GET, index);
+                }
+                None => self.call(function, name, vec![], holder),
+            },
+            BoundExpression::Identity(expression) | BoundExpression::Parenthesis(expression) => {
+                self.compile(function, expression, holder)
+            }
+            BoundExpression::Negation(expression) => {
+                self.compile(function, expression, holder);
+                function.code.push(F64_NEG);
+            }
+            BoundExpression::LogicalNot(expression) => {
+                self.compile(function, expression, holder);
+                function.code.push(I32_EQZ);
+            }
+            // The right operand is only evaluated if the left one does not decide the result.
+            BoundExpression::LogicalOr(left, right) => {
+                self.compile(function, left, holder);
+                function.code.extend([IF, I32]);
+                function.emit(I32_CONST, 1);
+                function.code.push(ELSE);
+                self.compile(function, right, holder);
+                function.code.push(END);
+            }
+            BoundExpression::LogicalAnd(left, right) => {
+                self.compile(function, left, holder);
+                function.code.extend([IF, I32]);
+                self.compile(function, right, holder);
+                function.code.push(ELSE);
+                function.emit(I32_CONST, 0);
+                function.code.push(END);
+            }
+            BoundExpression::Equal(left, right) => {
+                self.equality(function, left, right, false, holder)
+            }
+            BoundExpression::NotEqual(left, right) => {
+                self.equality(function, left, right, true, holder)
+            }
+            BoundExpression::Greater(left, right) => {
+                self.binary(function, left, right, F64_GT, holder)
+            }
+            BoundExpression::GreaterEqual(left, right) => {
+                self.binary(function, left, right, F64_GE, holder)
+            }
+            BoundExpression::Less(left, right) => {
+                self.binary(function, left, right, F64_LT, holder)
+            }
+            BoundExpression::LessEqual(left, right) => {
+                self.binary(function, left, right, F64_LE, holder)
+            }
+            BoundExpression::Addition(left, right) => {
+                self.binary(function, left, right, F64_ADD, holder)
+            }
+            BoundExpression::Subtraction(left, right) => {
+                self.binary(function, left, right, F64_SUB, holder)
+            }
+            BoundExpression::Multiplication(left, right) => {
+                self.binary(function, left, right, F64_MUL, holder)
+            }
+            BoundExpression::Division(left, right) => {
+                self.binary(function, left, right, F64_DIV, holder)
+            }
+            BoundExpression::Remainder(left, right) => {
+                let remainder = self.function_index(REMAINDER);
+                self.binary(function, left, right, CALL, holder);
+                function.code.extend(remainder.to_wasm_bytes());
+            }
+            BoundExpression::Call(_, _, _) => {
+                let mut arguments = vec![];
+                let mut callee = expression;
+
+                while let BoundExpression::Call(inner, argument, _) = callee {
+                    arguments.push(&**argument);
+
+                    match &**inner {
+                        Some(inner) => callee = inner,
+                        None => return function.code.push(UNREACHABLE),
+                    }
+                }
+
+                arguments.reverse();
+
+                match callee {
+                    BoundExpression::Identifier(name, _) if function.local(name).is_none() => {
+                        self.call(function, name, arguments, holder)
+                    }
+                    _ => unsupported(function, "calls of function values", holder),
+                }
+            }
+            BoundExpression::If(condition, then_branch, else_branch) => {
+                let Some(result) = value_type(&expression.get_type(), holder) else {
+                    return function.code.push(UNREACHABLE);
+                };
+
+                self.compile(function, condition, holder);
+                function.code.extend([IF, result]);
+                self.compile(function, then_branch, holder);
+                function.code.push(ELSE);
+                self.compile(function, else_branch, holder);
+                function.code.push(END);
+            }
+            BoundExpression::Let(name, value, body) => {
+                let Some(local_type) = value_type(&value.get_type(), holder) else {
+                    return function.code.push(UNREACHABLE);
+                };
+
+                self.compile(function, value, holder);
+
+                let local = function.new_local(local_type);
+                function.emit(LOCAL_SET, local);
+                function.scopes.push((name.clone(), local));
+                self.compile(function, body, holder);
+                function.scopes.pop();
+            }
+            BoundExpression::Match(target, arms, bound_type) => {
+                self.match_expression(function, target, arms, bound_type, holder)
+            }
+            BoundExpression::List(_, _) | BoundExpression::Index(_, _, _) => {
+                unsupported(function, "lists", holder)
+            }
+            BoundExpression::Tuple(_, _) | BoundExpression::TupleField(_, _, _) => {
+                unsupported(function, "tuples", holder)
+            }
+            BoundExpression::Record(_, _)
+            | BoundExpression::RecordUpdate(_, _, _)
+            | BoundExpression::Field(_, _, _) => unsupported(function, "records", holder),
+            BoundExpression::Lambda(_, _, _) => unsupported(function, "lambdas", holder),
+            BoundExpression::LetRec(_, _, _) => {
+                unsupported(function, "local recursive functions", holder)
+            }
+            BoundExpression::Constructor(_, _, _) => unsupported(function, "constructors", holder),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        function: &mut Function,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        opcode: u8,
+        holder: &mut DiagnosticHolder,
+    ) {
+        self.compile(function, left, holder);
+        self.compile(function, right, holder);
+        function.code.push(opcode);
+    }
+
+    fn equality(
+        &mut self,
+        function: &mut Function,
+        left: &Option<BoundExpression>,
+        right: &Option<BoundExpression>,
+        negated: bool,
+        holder: &mut DiagnosticHolder,
+    ) {
+        match left.get_type() {
+            BoundType::Number => self.binary(
+                function,
+                left,
+                right,
+                if negated { F64_NE } else { F64_EQ },
+                holder,
+            ),
+            BoundType::Bool => self.binary(
+                function,
+                left,
+                right,
+                if negated { I32_NE } else { I32_EQ },
+                holder,
+            ),
+            BoundType::String => {
+                let string_equal = self.function_index(STRING_EQUAL);
+                self.binary(function, left, right, CALL, holder);
+                function.code.extend(string_equal.to_wasm_bytes());
+
+                if negated {
+                    function.code.push(I32_EQZ);
+                }
+            }
+            other => {
+                value_type(&other, holder);
+                function.code.push(UNREACHABLE);
+            }
+        }
+    }
+
+    /// Calls a top-level definition with all of its arguments.
+    fn call(
+        &mut self,
+        function: &mut Function,
+        name: &str,
+        arguments: Vec<&Option<BoundExpression>>,
+        holder: &mut DiagnosticHolder,
+    ) {
+        let Some(definition) = self.definitions.get(name) else {
+            holder.error(&format!(
+                "Cannot compile \"{:}\" to WebAssembly, only top-level definitions can be called",
+                name
+            ));
+            return function.code.push(UNREACHABLE);
+        };
+
+        let arity = split_parameters(definition).0.len();
+
+        if arguments.len() != arity {
+            holder.error(&format!(
+                "Cannot compile \"{:}\" applied to {:} of its {:} arguments to WebAssembly",
+                name,
+                arguments.len(),
+                arity
+            ));
+            return function.code.push(UNREACHABLE);
+        }
+
+        for argument in arguments {
+            self.compile(function, argument, holder);
+        }
+
+        let index = self.function_index(name);
+        function.emit(CALL, index);
+    }
+
+    /// Tries the arms in order, each testing the target kept in a local.
+    fn match_expression(
+        &mut self,
+        function: &mut Function,
+        target: &Option<BoundExpression>,
+        arms: &[(BoundPattern, Option<BoundExpression>)],
+        bound_type: &BoundType,
+        holder: &mut DiagnosticHolder,
+    ) {
+        let (Some(target_type), Some(result)) = (
+            value_type(&target.get_type(), holder),
+            value_type(bound_type, holder),
+        ) else {
+            return function.code.push(UNREACHABLE);
+        };
+
+        self.compile(function, target, holder);
+
+        let local = function.new_local(target_type);
+        function.emit(LOCAL_SET, local);
+
+        let mut opened = 0;
+        let mut exhausted = false;
+
+        for (pattern, body) in arms {
+            match pattern {
+                BoundPattern::Wildcard | BoundPattern::Variable(_) => {
+                    if let BoundPattern::Variable(name) = pattern {
+                        function.scopes.push((name.clone(), local));
+                    }
+
+                    self.compile(function, body, holder);
+
+                    if let BoundPattern::Variable(_) = pattern {
+                        function.scopes.pop();
+                    }
+
+                    exhausted = true;
+                    break;
+                }
+                BoundPattern::Number(number) => {
+                    function.emit(LOCAL_GET, local);
+                    function.emit(F64_CONST, number.parse::<f64>().unwrap());
+                    function.code.push(F64_EQ);
+                }
+                BoundPattern::Bool(bool) => {
+                    function.emit(LOCAL_GET, local);
+
+                    if !bool.parse::<bool>().unwrap() {
+                        function.code.push(I32_EQZ);
+                    }
+                }
+                BoundPattern::Literal(string) => {
+                    let address = self.string(string);
+                    let string_equal = self.function_index(STRING_EQUAL);
+
+                    function.emit(LOCAL_GET, local);
+                    function.emit(I32_CONST, address as i32);
+                    function.emit(CALL, string_equal);
+                }
+                BoundPattern::Constructor(_, _)
+                | BoundPattern::Tuple(_)
+                | BoundPattern::List(_) => {
+                    unsupported(function, "constructor, tuple and list patterns", holder);
+                    exhausted = true;
+                    break;
+                }
+            }
+
+            function.code.extend([IF, result]);
+            self.compile(function, body, holder);
+            function.code.push(ELSE);
+            opened += 1;
+        }
+
+        if !exhausted {
+            function.code.push(UNREACHABLE);
+        }
+
+        function.code.extend(std::iter::repeat_n(END, opened));
+    }
+}
+
+/// The parameters of a definition written as nested lambdas, and the innermost body.
+fn split_parameters(
+    definition: &Option<BoundExpression>,
+) -> (Vec<(&str, BoundType)>, &Option<BoundExpression>) {
+    let mut parameters = vec![];
+    let mut body = definition;
+
+    while let Some(BoundExpression::Lambda(
+        parameter,
+        inner,
+        BoundType::Function(parameter_type, _),
+    )) = body
+    {
+        parameters.push((parameter.as_str(), (**parameter_type).clone()));
+        body = inner;
+    }
+
+    (parameters, body)
+}
+
+fn value_type(bound_type: &BoundType, holder: &mut DiagnosticHolder) -> Option<u8> {
+    match bound_type {
+        BoundType::Number => Some(F64),
+        BoundType::Bool | BoundType::String => Some(I32),
+        _ => {
+            holder.error(&format!(
+                "Cannot compile values of type \"{:}\" to WebAssembly",
+                bound_type
+            ));
+            None
+        }
+    }
+}
+
+fn unsupported(function: &mut Function, what: &str, holder: &mut DiagnosticHolder) {
+    holder.error(&format!("Cannot compile {:} to WebAssembly", what));
+    function.code.push(UNREACHABLE);
+}
+
+fn section(id: u8, contents: Vec<u8>) -> Vec<u8> {
+    [vec![id], contents.len().to_wasm_bytes(), contents].concat()
+}
+
+fn vector(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut bytes = items.len().to_wasm_bytes();
+    bytes.extend(items.into_iter().flatten());
+    bytes
+}
+
+fn name(name: &str) -> Vec<u8> {
+    [name.len().to_wasm_bytes(), name.as_bytes().to_vec()].concat()
+}
+
+/// Compares the lengths, then the bytes, of the strings at the two addresses.
+fn string_equal() -> Vec<u8> {
+    let mut function = Function::new(&[("left", BoundType::String), ("right", BoundType::String)]);
+    let index = function.new_local(I32);
+    let load_length = |function: &mut Function, address: u32| {
+        function.emit(LOCAL_GET, address);
+        function.code.extend([I32_LOAD, 2, 0]);
+    };
+    let load_byte = |function: &mut Function, address: u32| {
+        function.emit(LOCAL_GET, address);
+        function.emit(LOCAL_GET, index);
+        function.code.extend([I32_ADD, I32_LOAD8_U, 0, 4]);
+    };
+
+    load_length(&mut function, 0);
+    load_length(&mut function, 1);
+    function.code.extend([I32_NE, IF, EMPTY]);
+    function.emit(I32_CONST, 0);
+    function
+        .code
+        .extend([RETURN, END, BLOCK, EMPTY, LOOP, EMPTY]);
+    function.emit(LOCAL_GET, index);
+    load_length(&mut function, 0);
+    function.code.push(I32_GE_U);
+    function.emit(BR_IF, 1u32);
+    load_byte(&mut function, 0);
+    load_byte(&mut function, 1);
+    function.code.extend([I32_NE, IF, EMPTY]);
+    function.emit(I32_CONST, 0);
+    function.code.extend([RETURN, END]);
+    function.emit(LOCAL_GET, index);
+    function.emit(I32_CONST, 1);
+    function.code.push(I32_ADD);
+    function.emit(LOCAL_SET, index);
+    function.emit(BR, 0u32);
+    function.code.extend([END, END]);
+    function.emit(I32_CONST, 1);
+    function.finish()
+}
+
+/// Computes `left - right * trunc(left / right)`, as WebAssembly has no `f64` remainder.
+fn remainder() -> Vec<u8> {
+    let mut function = Function::new(&[("left", BoundType::Number), ("right", BoundType::Number)]);
+
+    function.emit(LOCAL_GET, 0u32);
+    function.emit(LOCAL_GET, 0u32);
+    function.emit(LOCAL_GET, 1u32);
+    function.code.extend([F64_DIV, F64_TRUNC]);
+    function.emit(LOCAL_GET, 1u32);
+    function.code.extend([F64_MUL, F64_SUB]);
+    function.finish()
+}